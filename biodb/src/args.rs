@@ -1,5 +1,11 @@
-use clap::{App, Arg};
+use clap::builder::PossibleValuesParser;
+use clap::{value_parser, App, Arg, ArgAction, ArgGroup, ArgMatches, ErrorKind};
+use clap_complete::Shell;
+use std::any::Any;
+use std::io;
+use std::net::SocketAddr;
 use std::ops::Range;
+use std::path::Path;
 
 pub struct Args {
     pub action: String,
@@ -11,91 +17,437 @@ pub struct Args {
     pub start: u32,
     pub limit: i32,
     pub coords: Range<usize>,
+    pub coord_type: String,
+    pub strand: String,
+    pub frame: u8,
+    pub compression: String,
+    pub dict_size: i32,
+    pub gene: String,
+    pub min_score: f64,
+    pub max_evalue: f64,
+    pub format: String,
+    pub output: String,
+    pub verify: bool,
+    pub list: String,
+    pub all: bool,
+    pub with_aa: bool,
+    pub gzip: bool,
+    pub store: String,
+    pub species: Vec<String>,
+    pub bind: String,
+    pub shell: Option<Shell>,
+    pub query: String,
+    pub kmer_index: bool,
+    pub table_prefix: String,
 }
 
 impl Args {
     pub fn new() -> Self {
-        // Specify cli arguments
-        let matches = App::new("biodb")
-            .version("0.1")
-            .author("Matt Dizak <matt@apexpl.io>")
-            .about("Retrieve the sequences you need.")
-            .arg(Arg::with_name("action")
-                .short('a')
-                .long("action")
+        let mut app = build_cli();
+        let matches = app.get_matches_mut();
+
+        // Get action, with the nested hmmsearch commands as "hmmsearch <command>"
+        let (command, m) = matches.subcommand().unwrap();
+        let (action, m): (String, &ArgMatches) = match m.subcommand() {
+            Some((name, sub)) => (format!("{} {}", command, name), sub),
+            None => (command.to_string(), m),
+        };
+
+        // Get args
+        let input_dir = value::<String>(m, "input").unwrap_or_default();
+        let input_dir = input_dir.trim_end_matches("/").to_string();
+        let store = value::<String>(m, "store").unwrap_or_default();
+        let store = store.trim_end_matches("/").to_string();
+        let header = value::<String>(m, "header")
+            .or(value::<u32>(m, "id").map(|id| id.to_string()))
+            .unwrap_or_default();
+
+        // Check the database to use was given
+        let needs_input = ["upgrade", "import", "verify"].contains(&action.as_str());
+        if action != "completions" {
+            if needs_input && input_dir == "" {
+                app.error(
+                    ErrorKind::MissingRequiredArgument,
+                    format!("biodb {} requires the --input (-i) directory.", action),
+                )
+                .exit();
+            } else if input_dir == "" && store == "" {
+                app.error(
+                    ErrorKind::MissingRequiredArgument,
+                    "Please specify either the --input (-i) directory, or a shared --store.",
+                )
+                .exit();
+            } else if action == "import" && store == "" {
+                app.error(
+                    ErrorKind::MissingRequiredArgument,
+                    "Please specify the shared store to import into with --store.",
+                )
+                .exit();
+            }
+        }
+
+        // Parse input dir
+        let sqlite_file = match input_dir.as_str() {
+            "" => String::new(),
+            dir => format!(
+                "{}/{}.sqlite",
+                dir,
+                dir.split("/").last().unwrap().trim_end_matches(".fa")
+            ),
+        };
+
+        // Export to the original SQLite database, unless an output file is given
+        let output = match value::<String>(m, "output") {
+            Some(r) => r,
+            None if action == "export-sqlite" && sqlite_file == "" => {
+                app.error(
+                    ErrorKind::MissingRequiredArgument,
+                    "biodb export-sqlite requires either the --input (-i) directory or an --output (-o) file.",
+                )
+                .exit();
+            }
+            None if action == "export-sqlite" => sqlite_file.to_string(),
+            None => String::new(),
+        };
+
+        // Return
+        Self {
+            rocksdb: match store.as_str() {
+                "" => format!("{}/rocksdb", input_dir),
+                r => r.to_string(),
+            },
+            input_dir: input_dir,
+            sqlite_file: sqlite_file,
+            header: header,
+            seq_type: value::<String>(m, "type").unwrap_or_default(),
+            start: value::<u32>(m, "start").unwrap_or(0),
+            limit: value::<i32>(m, "limit").unwrap_or(500),
+            coords: value::<Range<usize>>(m, "coords").unwrap_or(0..0),
+            coord_type: value::<String>(m, "coord-type").unwrap_or_default(),
+            strand: value::<String>(m, "strand").unwrap_or_default(),
+            frame: value::<u8>(m, "frame").unwrap_or(0),
+            compression: value::<String>(m, "compression").unwrap_or_default(),
+            dict_size: value::<i32>(m, "dict-size").unwrap_or(16384),
+            gene: value::<String>(m, "gene").unwrap_or_default(),
+            min_score: value::<f64>(m, "min-score").unwrap_or(f64::NEG_INFINITY),
+            max_evalue: value::<f64>(m, "max-evalue").unwrap_or(f64::INFINITY),
+            format: value::<String>(m, "format").unwrap_or_default(),
+            gzip: flag(m, "gzip") || output.ends_with(".gz"),
+            output: output,
+            verify: flag(m, "verify"),
+            list: value::<String>(m, "list").unwrap_or_default(),
+            all: flag(m, "all"),
+            with_aa: flag(m, "with-aa"),
+            store: store,
+            species: value::<String>(m, "species")
+                .unwrap_or("all".to_string())
+                .split(",")
+                .map(|s| s.trim().to_string())
+                .filter(|s| s != "" && s != "all")
+                .collect(),
+            bind: value::<SocketAddr>(m, "bind")
+                .map(|r| r.to_string())
+                .unwrap_or("127.0.0.1:8080".to_string()),
+            shell: value::<Shell>(m, "shell"),
+            query: value::<String>(m, "query").unwrap_or_default(),
+            kmer_index: flag(m, "kmer-index"),
+            table_prefix: value::<String>(m, "dbtable-prefix").unwrap_or("orthograph".to_string()),
+            action: action,
+        }
+    }
+}
+
+/**
+ * Subcommands of biodb along with their options.  Options used by several
+ * subcommands are defined once below, and the input directory, store and
+ * species apply to all of them.
+ */
+pub fn build_cli() -> App<'static> {
+    App::new("biodb")
+        .version("0.1")
+        .author("Matt Dizak <matt@apexpl.io>")
+        .about("Retrieve the sequences you need.")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(Arg::new("input")
+            .short('i')
+            .long("input")
+            .global(true)
+            .takes_value(true)
+            .value_parser(existing_dir)
+            .help("Input directory (orthograph_results/species_name)"))
+        .arg(Arg::new("store")
+            .long("store")
+            .global(true)
+            .takes_value(true)
+            .help("Directory of a shared store holding many species, instead of the RocksDB within the input directory."))
+        .arg(Arg::new("species")
+            .long("species")
+            .global(true)
+            .takes_value(true)
+            .help("With --store, comma separated names of the species to query, or 'all'.  Defaults to all.  For 'import', the name to import the species as, defaults to the name of the input directory without any .fa extension."))
+        .subcommand(App::new("upgrade")
+            .visible_alias("upgrade-db")
+            .about("Transfer the SQLite database of the input directory into its RocksDB, or upgrade an existing RocksDB to the current schema.")
+            .args(compression_args()))
+        .subcommand(App::new("import")
+            .about("Add the species of the input directory to a shared store, creating the store if needed.")
+            .args(compression_args()))
+        .subcommand(App::new("verify")
+            .about("Compare the RocksDB of the input directory against its SQLite database."))
+        .subcommand(App::new("seq")
+            .visible_alias("get-sequence")
+            .about("Retrieve the sequence of a transcript.")
+            .arg(Arg::new("header")
+                .required(true)
+                .help("Header of sequence to retrieve, either the base header or with [revcomp] and [translate(N)] info."))
+            .args(region_args())
+            .arg(format_arg(&["raw", "fasta", "json"], "Output format (raw, fasta, json).  Defaults to raw, or fasta within a shared store.")))
+        .subcommand(App::new("hmmsearch")
+            .about("Retrieve hmm searches along with their blast results.")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(App::new("get")
+                .about("Retrieve the hmm search with the id#.")
+                .arg(Arg::new("id")
+                    .required(true)
+                    .value_parser(value_parser!(u32))
+                    .help("Id# of the hmm search."))
+                .arg(hmmsearch_format_arg()))
+            .subcommand(App::new("list")
+                .about("Retrieve all hmm searches, in order of id#.")
+                .arg(Arg::new("start")
+                    .short('s')
+                    .long("start")
+                    .takes_value(true)
+                    .value_parser(value_parser!(u32))
+                    .help("Id# to start at.  Defaults to 0."))
+                .args(filter_args())
+                .arg(hmmsearch_format_arg()))
+            .subcommand(App::new("gene")
+                .about("Retrieve the hmm searches of a gene, highest score first.")
+                .arg(Arg::new("gene")
+                    .required(true)
+                    .help("The gene (ortholog id) to retrieve hmm searches of."))
+                .args(filter_args())
+                .arg(hmmsearch_format_arg()))
+            .subcommand(App::new("header")
+                .about("Retrieve the hmm searches of a transcript, in order of id#.")
+                .arg(Arg::new("header")
+                    .required(true)
+                    .help("Base header of the transcript."))
+                .args(filter_args())
+                .arg(hmmsearch_format_arg())))
+        .subcommand(App::new("search")
+            .about("Find the transcripts containing an nt substring on either strand, or an aa motif in any of the six frames.")
+            .arg(Arg::new("query")
+                .required(true)
+                .help("Nt or aa sequence to search for."))
+            .arg(Arg::new("type")
+                .short('t')
+                .long("type")
                 .takes_value(true)
-                .help("The action to perform, either 'upgrade-db', 'get-sequence', 'get-hmmsearch' or 'get-hmmsearches'"))
-            .arg(Arg::with_name("input")
-                .short('i')
-                .long("input")
+                .value_parser(["nt", "aa"])
+                .help("Type of the query (nt, aa).  Defaults to nt."))
+            .arg(Arg::new("limit")
+                .short('l')
+                .long("limit")
                 .takes_value(true)
-                .help("Input directory (orthograph_results/species_name)"))
-            .arg(Arg::with_name("header")
-                .short('h')
-                .long("header")
+                .allow_hyphen_values(true)
+                .value_parser(value_parser!(i32))
+                .help("The number of hits to return, -1 for all.  Defaults to 500."))
+            .arg(format_arg(&["json", "tsv"], "Output format (json, tsv).  Defaults to json.")))
+        .subcommand(App::new("export")
+            .about("Export many sequences at once as FASTA.")
+            .group(ArgGroup::new("source")
+                .args(&["list", "gene", "all"])
+                .required(true))
+            .arg(Arg::new("list")
+                .long("list")
                 .takes_value(true)
-                .help("Base header of sequence to retrieve."))
-            .arg(Arg::with_name("type")
-                .short('t')
-                .long("type")
+                .help("File of headers to export one per line, or '-' for STDIN.  Each header may be followed by tab separated START-END coords and frame (eg. -2 for the reverse strand in frame 2)."))
+            .arg(Arg::new("gene")
+                .short('g')
+                .long("gene")
                 .takes_value(true)
-                .help("Type of sequence to retrieve (nt, aa)"))
-            .arg(Arg::with_name("coords")
-                .short('c')
-                .long("coords")
+                .help("Export the transcripts hit by the hmm searches of the gene."))
+            .arg(Arg::new("all")
+                .long("all")
+                .action(ArgAction::SetTrue)
+                .help("Export all transcripts within the database."))
+            .arg(Arg::new("with-aa")
+                .long("with-aa")
+                .action(ArgAction::SetTrue)
+                .help("Also export the translated aa sequence after each nt sequence."))
+            .arg(Arg::new("output")
+                .short('o')
+                .long("output")
                 .takes_value(true)
-                .help("Optional coordinates to retrieve, fomatted as START-END (eg. 5-61)"))
-            .arg(Arg::with_name("start")
-                .short('s')
-                .long("start")
+                .help("The FASTA file to write, defaults to STDOUT."))
+            .arg(Arg::new("gzip")
+                .long("gzip")
+                .action(ArgAction::SetTrue)
+                .help("Gzip the output.  Always enabled if the output file ends with .gz"))
+            .args(region_args())
+            .args(filter_args()))
+        .subcommand(App::new("export-sqlite")
+            .about("Rebuild the Orthograph SQLite tables from the RocksDB.")
+            .arg(Arg::new("output")
+                .short('o')
+                .long("output")
                 .takes_value(true)
-                .help("For action 'get-hmmsearches', and where in the result set to start.  Defaults to 0."))
-            .arg(Arg::with_name("limit")
-                .short('l')
-                .long("limit")
+                .help("The SQLite file to create.  Defaults to the SQLite database within the input directory."))
+            .arg(Arg::new("verify")
+                .long("verify")
+                .action(ArgAction::SetTrue)
+                .help("Compare row counts and checksums of the export against the original SQLite database."))
+            .arg(Arg::new("dbtable-prefix")
+                .long("dbtable-prefix")
+                .takes_value(true)
+                .help("Prefix of the table names, same as the dbtable-prefix setting of Orthograph.  Defaults to orthograph.")))
+        .subcommand(App::new("stats")
+            .about("Display the number of records and bytes used per record type."))
+        .subcommand(App::new("serve")
+            .about("Serve read-only JSON endpoints over HTTP.")
+            .arg(Arg::new("bind")
+                .long("bind")
                 .takes_value(true)
-                .help("For 'get-hmmsearches' action, and the number of hmm searches to return."))
-            .get_matches();
+                .value_parser(value_parser!(SocketAddr))
+                .help("The address and port to listen on.  Defaults to 127.0.0.1:8080.")))
+        .subcommand(App::new("completions")
+            .about("Print the shell completion script for biodb.")
+            .arg(Arg::new("shell")
+                .required(true)
+                .value_parser(value_parser!(Shell))
+                .help("The shell to generate completions for.")))
+}
 
-        // Get args
-        let action = matches.value_of("action").unwrap_or("");
-        let input_dir = matches.value_of("input").unwrap_or("/home/boxer/devel/clients/kevin/test-data/old/Syrphidae/orthograph_results/210222/AB34950737_S20_R1.fa");
-        let header = matches.value_of("header").unwrap_or("");
-        let seq_type = matches.value_of("type").unwrap_or("nt");
-        let start = matches.value_of("start").unwrap_or("0");
-        let limit = matches.value_of("limit").unwrap_or("500");
-
-        // Get coords
-        let coords_str = matches.value_of("coords").unwrap_or("0-0").split("-");
-        let coords = coords_str
-            .into_iter()
-            .map(|c| c.parse::<usize>().unwrap())
-            .collect::<Vec<usize>>();
+// Print the completion script of the shell to STDOUT
+pub fn print_completions(shell: Shell) {
+    clap_complete::generate(shell, &mut build_cli(), "biodb", &mut io::stdout());
+}
 
-        // Parse input dir
-        let parts = input_dir.split("/");
-        let sqlite_file = format!(
-            "{}/{}.sqlite",
-            input_dir,
-            parts
-                .last()
-                .unwrap()
-                .trim_end_matches("/")
-                .trim_end_matches(".fa")
-                .to_string()
-        );
+fn compression_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::new("kmer-index")
+            .long("kmer-index")
+            .action(ArgAction::SetTrue)
+            .help("Also build the k-mer index used by search, of every nt k-mer and aa k-mer of the six frames.  Adds to the size of the database."),
+        Arg::new("compression")
+            .short('z')
+            .long("compression")
+            .takes_value(true)
+            .value_parser(["none", "snappy", "lz4", "zstd"])
+            .help("Compression of the RocksDB (none, snappy, lz4, zstd), only when creating it.  Defaults to snappy."),
+        Arg::new("dict-size")
+            .long("dict-size")
+            .takes_value(true)
+            .value_parser(value_parser!(i32))
+            .help("For zstd compression, the size in bytes of the dictionary trained per column family, 0 to disable.  Defaults to 16384."),
+    ]
+}
 
-        // Return
-        Self {
-            action: action.to_string(),
-            input_dir: input_dir.trim_end_matches("/").to_string(),
-            rocksdb: format!("{}/rocksdb", input_dir.trim_end_matches("/").to_string()).to_string(),
-            sqlite_file: sqlite_file,
-            header: header.to_string(),
-            seq_type: seq_type.to_string(),
-            start: start.parse::<u32>().unwrap(),
-            limit: limit.parse::<i32>().unwrap(),
-            coords: coords[0]..coords[1],
-        }
+fn region_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::new("type")
+            .short('t')
+            .long("type")
+            .takes_value(true)
+            .value_parser(["nt", "aa"])
+            .help("Type of sequence to retrieve (nt, aa).  Defaults to aa if the header contains [translate(N)], otherwise nt."),
+        Arg::new("strand")
+            .long("strand")
+            .takes_value(true)
+            .value_parser(["+", "-"])
+            .allow_hyphen_values(true)
+            .help("Strand of sequence to retrieve, '-' for the reverse complement.  Overrides any [revcomp] within the header."),
+        Arg::new("frame")
+            .long("frame")
+            .takes_value(true)
+            .value_parser(value_parser!(u8).range(1..=3))
+            .help("Reading frame to translate in.  Overrides any [translate(N)] within the header, defaults to 1."),
+        Arg::new("coord-type")
+            .long("coord-type")
+            .takes_value(true)
+            .value_parser(["nt", "aa"])
+            .help("Whether --coords are nt or aa positions.  Defaults to the type of sequence retrieved."),
+        Arg::new("coords")
+            .short('c')
+            .long("coords")
+            .takes_value(true)
+            .value_parser(coords_arg)
+            .help("Optional coordinates to retrieve, formatted as START-END (eg. 5-61)"),
+    ]
+}
+
+fn filter_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::new("min-score")
+            .long("min-score")
+            .takes_value(true)
+            .value_parser(value_parser!(f64))
+            .help("Only retrieve hmm searches with a score of at least this value."),
+        Arg::new("max-evalue")
+            .long("max-evalue")
+            .takes_value(true)
+            .value_parser(value_parser!(f64))
+            .help("Only retrieve hmm searches with an e-value of at most this value."),
+        Arg::new("limit")
+            .short('l')
+            .long("limit")
+            .takes_value(true)
+            .allow_hyphen_values(true)
+            .value_parser(value_parser!(i32))
+            .help("The number of hmm searches to return, -1 for all.  Defaults to 500."),
+    ]
+}
+
+fn hmmsearch_format_arg() -> Arg<'static> {
+    format_arg(
+        &["json", "tsv", "fasta"],
+        "Output format (json, tsv, fasta), where fasta is the target sequence of each hmm search.  Defaults to json.",
+    )
+}
+
+fn format_arg(formats: &'static [&'static str], help: &'static str) -> Arg<'static> {
+    Arg::new("format")
+        .short('f')
+        .long("format")
+        .takes_value(true)
+        .value_parser(PossibleValuesParser::new(formats.iter().copied()))
+        .help(help)
+}
+
+// Value of the option, if the subcommand has it and it was given
+fn value<T: Any + Clone + Send + Sync + 'static>(m: &ArgMatches, name: &str) -> Option<T> {
+    m.try_get_one::<T>(name).ok().flatten().cloned()
+}
+
+fn flag(m: &ArgMatches, name: &str) -> bool {
+    value::<bool>(m, name).unwrap_or(false)
+}
+
+fn existing_dir(dir: &str) -> Result<String, String> {
+    match Path::new(dir).is_dir() {
+        true => Ok(dir.to_string()),
+        false => Err(format!("Directory does not exist at {}", dir)),
+    }
+}
+
+fn coords_arg(coords_str: &str) -> Result<Range<usize>, String> {
+    parse_coords(&coords_str).ok_or("must be formatted as START-END (eg. 5-61)".to_string())
+}
+
+// Parse coords formatted as START-END
+pub fn parse_coords(coords_str: &str) -> Option<Range<usize>> {
+    let coords = coords_str
+        .split("-")
+        .map(|c| c.trim().parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>()
+        .ok()?;
+
+    match coords.len() {
+        2 => Some(coords[0]..coords[1]),
+        _ => None,
     }
 }
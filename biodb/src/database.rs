@@ -1,25 +1,44 @@
 extern crate rocksdb;
 
+use crate::models::{Blast, Est, HmmSearch};
 use crate::BIODB_ARGS;
-use rocksdb::{DBCompactionStyle, DBCompressionType, DB};
+use biotools::db::schema::{
+    self, COLUMN_FAMILIES, META_COMPRESSION, META_SCHEMA_VERSION, OPTIONAL_COLUMN_FAMILIES,
+};
+use log::warn;
+use rocksdb::{
+    ColumnFamily, ColumnFamilyDescriptor, DBCompactionStyle, DBCompressionType, Direction,
+    IteratorMode, WriteBatch, DB,
+};
+use std::convert::TryInto;
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
 
 pub trait Database {
     fn new() -> Self;
-    fn put(&self, header: &String, sequence: &String);
-    fn get(&self, header: &String) -> Option<String>;
-    fn delete(&self, header: &String) -> bool;
+    fn put(&self, cf: &str, key: &[u8], value: &[u8]);
+    fn get(&self, cf: &str, key: &[u8]) -> Option<Vec<u8>>;
+    fn delete(&self, cf: &str, key: &[u8]) -> bool;
 }
 
+// Column family, key and value of a record to write
+pub type Entry = (&'static str, Vec<u8>, Vec<u8>);
+
 #[derive(Clone)]
 pub struct RocksDB {
     pub db: Arc<DB>,
+    // Key prefix of the species within a shared store, empty otherwise
+    pub prefix: Vec<u8>,
 }
 
 impl Database for RocksDB {
     fn new() -> Self {
+        // The serve action only reads, so it may run alongside other readers
+        if BIODB_ARGS.action == "serve" {
+            return Self::open_read_only();
+        }
+
         // Create directory, if needed
         if !Path::new(&BIODB_ARGS.rocksdb).exists() {
             match fs::create_dir_all(&BIODB_ARGS.rocksdb) {
@@ -28,46 +47,433 @@ impl Database for RocksDB {
             };
         }
 
+        // Use the compression the database was created with
+        let compression = compression();
+
         // Set options
         let mut opts = rocksdb::Options::default();
         //opts.set_compaction_style(DBCompactionStyle::Universal);
         opts.create_if_missing(true);
-        opts.set_compression_type(DBCompressionType::Snappy);
+        opts.create_missing_column_families(true);
+        set_compression(&mut opts, &compression);
         opts.increase_parallelism(24);
 
+        // Open all existing column families, plus those of the current schema
+        let mut cf_names: Vec<String> =
+            DB::list_cf(&opts, &BIODB_ARGS.rocksdb).unwrap_or(Vec::new());
+        for name in COLUMN_FAMILIES.iter().chain(&OPTIONAL_COLUMN_FAMILIES) {
+            if !cf_names.contains(&name.to_string()) {
+                cf_names.push(name.to_string());
+            }
+        }
+        let descriptors = cf_names.iter().map(|name| {
+            let mut cf_opts = rocksdb::Options::default();
+            set_compression(&mut cf_opts, &compression);
+            ColumnFamilyDescriptor::new(name, cf_opts)
+        });
+
         // Connect to database
-        let database = match DB::open(&opts, BIODB_ARGS.rocksdb.as_str()) {
+        let database =
+            match DB::open_cf_descriptors(&opts, BIODB_ARGS.rocksdb.as_str(), descriptors) {
+                Ok(r) => r,
+                Err(e) => panic!(
+                    "Unable to open RocksDB at {}, error: {}",
+                    BIODB_ARGS.rocksdb, e
+                ),
+            };
+
+        // Save compression when creating the database
+        let meta = database.cf_handle(schema::CF_META).unwrap();
+        if ["upgrade", "import"].contains(&BIODB_ARGS.action.as_str())
+            && database.get_cf(meta, META_COMPRESSION).unwrap().is_none()
+        {
+            database
+                .put_cf(meta, META_COMPRESSION, compression.as_bytes())
+                .unwrap();
+        }
+
+        // Return
+        RocksDB {
+            db: Arc::new(database),
+            prefix: Vec::new(),
+        }
+    }
+
+    fn put(&self, cf: &str, key: &[u8], value: &[u8]) {
+        self.db.put_cf(self.cf(cf), self.key(key), value).unwrap();
+    }
+
+    fn get(&self, cf: &str, key: &[u8]) -> Option<Vec<u8>> {
+        match self.db.get_cf(self.cf(cf), self.key(key)) {
             Ok(r) => r,
             Err(e) => panic!(
-                "Unable to open RocksDB at {}, error: {}",
-                BIODB_ARGS.rocksdb, e
+                "Received database error when trying to retrieve record from {}, error: {}",
+                cf, e
             ),
+        }
+    }
+
+    fn delete(&self, cf: &str, key: &[u8]) -> bool {
+        self.db.delete_cf(self.cf(cf), self.key(key)).is_ok()
+    }
+}
+
+/**
+ * Compression of the database, as saved within its meta column family when
+ * created.  The --compression flag only applies to new databases, and is
+ * ignored with a warning if it disagrees with the saved compression.
+ */
+fn compression() -> String {
+    let saved: Option<String> = DB::list_cf(&rocksdb::Options::default(), &BIODB_ARGS.rocksdb)
+        .ok()
+        .filter(|cf_names| cf_names.contains(&schema::CF_META.to_string()))
+        .and_then(|_| {
+            let db = DB::open_cf_for_read_only(
+                &rocksdb::Options::default(),
+                &BIODB_ARGS.rocksdb,
+                [schema::CF_META],
+                false,
+            )
+            .ok()?;
+            let value = db
+                .get_cf(db.cf_handle(schema::CF_META)?, META_COMPRESSION)
+                .ok()??;
+            Some(String::from_utf8_lossy(&value).to_string())
+        });
+
+    match (saved, BIODB_ARGS.compression.as_str()) {
+        (Some(r), "") => r,
+        (Some(r), flag) => {
+            if r != flag {
+                warn!(
+                    "RocksDB at {} was created with {} compression, so ignoring --compression {}.",
+                    BIODB_ARGS.rocksdb, r, flag
+                );
+            }
+            r
+        }
+        (None, "") => String::from("snappy"),
+        (None, flag) => flag.to_string(),
+    }
+}
+
+// Set compression, plus the zstd dictionary as per the --dict-size flag
+fn set_compression(opts: &mut rocksdb::Options, compression: &str) {
+    let compression_type = match compression {
+        "none" => DBCompressionType::None,
+        "lz4" => DBCompressionType::Lz4,
+        "zstd" => DBCompressionType::Zstd,
+        _ => DBCompressionType::Snappy,
+    };
+    opts.set_compression_type(compression_type);
+
+    // Train a zstd dictionary, as records of the same type share most of their structure
+    if compression == "zstd" && BIODB_ARGS.dict_size > 0 {
+        opts.set_compression_options(-14, 32767, 0, BIODB_ARGS.dict_size);
+        opts.set_zstd_max_train_bytes(BIODB_ARGS.dict_size * 100);
+    }
+}
+
+impl RocksDB {
+    // Open the existing database without write access
+    fn open_read_only() -> Self {
+        let opts = rocksdb::Options::default();
+        let cf_names: Vec<String> = match DB::list_cf(&opts, &BIODB_ARGS.rocksdb) {
+            Ok(r) => r,
+            Err(e) => panic!("No RocksDB exists at {}, error: {}", BIODB_ARGS.rocksdb, e),
         };
 
-        // Return
+        let database =
+            match DB::open_cf_for_read_only(&opts, BIODB_ARGS.rocksdb.as_str(), cf_names, false) {
+                Ok(r) => r,
+                Err(e) => panic!(
+                    "Unable to open RocksDB at {} read-only, error: {}",
+                    BIODB_ARGS.rocksdb, e
+                ),
+            };
+
         RocksDB {
             db: Arc::new(database),
+            prefix: Vec::new(),
         }
     }
 
-    fn put(&self, header: &String, sequence: &String) {
-        self.db.put(header.as_bytes(), sequence.as_bytes()).unwrap();
+    pub fn cf(&self, name: &str) -> &ColumnFamily {
+        match self.db.cf_handle(name) {
+            Some(r) => r,
+            None => panic!("Column family {} does not exist within the RocksDB", name),
+        }
     }
 
-    fn get(&self, header: &String) -> Option<String> {
-        let sequence = match self.db.get(header.as_bytes()) {
-            Ok(Some(r)) => String::from_utf8(r).unwrap(),
-            Ok(None) => return None,
-            Err(e) => panic!(
-                "Received database error when trying to retrieve sequence, error: {}",
-                e
+    // Key with the species prefix, if any
+    fn key(&self, key: &[u8]) -> Vec<u8> {
+        [&self.prefix, key].concat()
+    }
+
+    /**
+     * Records of the column family starting at the given key, for as long as
+     * they are of the species.  Keys are returned without the species prefix.
+     */
+    pub fn iter(&self, cf: &str, from: &[u8]) -> impl Iterator<Item = (Vec<u8>, Box<[u8]>)> + '_ {
+        let start = self.key(from);
+        self.db
+            .iterator_cf(self.cf(cf), IteratorMode::From(&start, Direction::Forward))
+            .take_while(move |(k, _v)| k.starts_with(&self.prefix))
+            .map(move |(k, v)| (k[self.prefix.len()..].to_vec(), v))
+    }
+
+    /// View of the species with the given number, within a shared store.
+    pub fn species_view(&self, num: u32) -> RocksDB {
+        RocksDB {
+            db: self.db.clone(),
+            prefix: schema::store_prefix(num).to_vec(),
+        }
+    }
+
+    /**
+     * Get the schema version.  Returns None for an empty database, and 0 for one
+     * in the original layout which predates the meta column family.
+     */
+    pub fn schema_version(&self) -> Option<u32> {
+        if let Some(r) = self.get(schema::CF_META, META_SCHEMA_VERSION.as_bytes()) {
+            return Some(u32::from_be_bytes(
+                r[..4].try_into().expect("Invalid schema version"),
+            ));
+        }
+
+        match self.prefix.is_empty() && self.db.iterator(IteratorMode::Start).next().is_some() {
+            true => Some(0),
+            false => None,
+        }
+    }
+
+    pub fn set_schema_version(&self, version: u32) {
+        self.put(
+            schema::CF_META,
+            META_SCHEMA_VERSION.as_bytes(),
+            &version.to_be_bytes(),
+        );
+    }
+
+    /// Panic unless the database is at the current schema version.
+    pub fn check_schema_version(&self) {
+        if !self.prefix.is_empty() && self.schema_version() != Some(schema::SCHEMA_VERSION) {
+            panic!(
+                "Species within the shared store at {} has not been fully imported, please run import again.",
+                BIODB_ARGS.rocksdb
+            );
+        }
+        if self.is_upgrading() {
+            panic!(
+                "Upgrade of RocksDB at {} was interrupted, please run biodb upgrade again to resume it.",
+                BIODB_ARGS.rocksdb
+            );
+        }
+
+        match self.schema_version() {
+            Some(v) if v == schema::SCHEMA_VERSION => {}
+            Some(v) => panic!(
+                "RocksDB at {} is at schema version {}, but version {} is required.  Please run biodb upgrade first.",
+                BIODB_ARGS.rocksdb, v, schema::SCHEMA_VERSION
+            ),
+            None => panic!(
+                "RocksDB at {} is empty, please run biodb upgrade first.",
+                BIODB_ARGS.rocksdb
             ),
         };
+    }
+
+    pub fn put_est(&self, est: &Est) {
+        for (cf, key, value) in est_entries(&est) {
+            self.put(cf, &key, &value);
+        }
+    }
+
+    pub fn put_hmmsearch(&self, hmmsearch: &HmmSearch) {
+        let (cf, key, value) = hmmsearch_entry(&hmmsearch);
+        self.put(cf, &key, &value);
+    }
+
+    pub fn put_blast(&self, blast: &Blast) {
+        let (cf, key, value) = blast_entry(&blast);
+        self.put(cf, &key, &value);
+    }
+
+    /// Write the entries within one batch, so either all or none are written.
+    pub fn write_batch(&self, entries: &[Entry]) {
+        let mut batch = WriteBatch::default();
+        for (cf, key, value) in entries {
+            batch.put_cf(self.cf(cf), self.key(key), value);
+        }
+
+        match self.db.write(batch) {
+            Ok(_) => {}
+            Err(e) => panic!("Unable to write batch to RocksDB, error: {}", e),
+        };
+    }
+
+    /// Last id# of the SQLite table transferred by an interrupted upgrade, or 0.
+    pub fn get_progress(&self, table: &str) -> u32 {
+        match self.get(schema::CF_META, &schema::progress_key(&table)) {
+            Some(r) => schema::decode_id(&r),
+            None => 0,
+        }
+    }
+
+    pub fn is_upgrading(&self) -> bool {
+        let prefix = schema::META_UPGRADE_PROGRESS.as_bytes();
+        match self.iter(schema::CF_META, prefix).next() {
+            Some((key, _value)) => key.starts_with(prefix),
+            None => false,
+        }
+    }
+
+    pub fn clear_progress(&self) {
+        let prefix = schema::META_UPGRADE_PROGRESS.as_bytes();
+        for (key, _value) in self.iter(schema::CF_META, prefix) {
+            if !key.starts_with(prefix) {
+                break;
+            }
+            self.delete(schema::CF_META, &key);
+        }
+    }
+
+    /// Total records within the column family.
+    pub fn count(&self, cf: &str) -> u64 {
+        self.iter(cf, &[]).count() as u64
+    }
+
+    pub fn get_est(&self, id: u32) -> Option<Est> {
+        self.get(schema::CF_ESTS, &schema::id_key(id))
+            .map(|r| schema::decode::<Est>(&r))
+    }
 
-        Some(sequence)
+    pub fn get_est_by_header(&self, header: &str) -> Option<Est> {
+        let key = self.get(schema::CF_EST_HEADERS, &schema::header_key(&header))?;
+        self.get_est(schema::decode_id(&key))
     }
 
-    fn delete(&self, header: &String) -> bool {
-        self.db.delete(header.as_bytes()).is_ok()
+    pub fn get_est_by_digest(&self, digest: &str) -> Option<Est> {
+        let key = self.get(schema::CF_EST_DIGESTS, digest.as_bytes())?;
+        self.get_est(schema::decode_id(&key))
     }
+
+    /// Nt transcripts in order of id#, without the translated ORFs of Orthograph.
+    pub fn iter_nt_ests(&self) -> impl Iterator<Item = Est> + '_ {
+        self.iter(schema::CF_ESTS, &[])
+            .map(|(_k, v)| schema::decode::<Est>(&v))
+            .filter(|est| biotools::translate_header(&est.header).2 == 0)
+    }
+
+    pub fn get_hmmsearch(&self, id: u32) -> Option<HmmSearch> {
+        self.get(schema::CF_HMMSEARCH, &schema::id_key(id))
+            .map(|r| schema::decode::<HmmSearch>(&r))
+    }
+
+    /// Hmm searches in order of id#, starting at the given id#.
+    pub fn iter_hmmsearches(&self, start: u32) -> impl Iterator<Item = HmmSearch> + '_ {
+        self.iter(schema::CF_HMMSEARCH, &schema::id_key(start))
+            .map(|(_k, v)| schema::decode::<HmmSearch>(&v))
+    }
+
+    /// Add the hmm search to the header and gene score indexes.
+    pub fn index_hmmsearch(&self, hmmsearch: &HmmSearch, header: &str) {
+        let base = schema::base_header(&header);
+        self.put(
+            schema::CF_HEADER_INDEX,
+            &schema::index_key(&base, hmmsearch.id),
+            &[],
+        );
+        self.put(
+            schema::CF_GENE_SCORE_INDEX,
+            &schema::score_index_key(&hmmsearch.query, hmmsearch.score, hmmsearch.id),
+            &[],
+        );
+    }
+
+    // Delete all entries of the column family
+    pub fn clear(&self, cf: &str) {
+        for (key, _value) in self.iter(cf, &[]) {
+            self.delete(cf, &key);
+        }
+    }
+
+    /// Hmm search id#s of the gene, or base header, within the given index.
+    pub fn get_indexed_ids(&self, cf: &str, name: &str) -> Vec<u32> {
+        let prefix = schema::index_prefix(&name);
+        self.iter(cf, &prefix)
+            .take_while(|(k, _v)| k.starts_with(&prefix))
+            .map(|(k, _v)| schema::decode_index_id(&k))
+            .collect()
+    }
+
+    /// Nt and aa k-mer sizes of the k-mer index, if it has been built.
+    pub fn kmer_sizes(&self) -> Option<(usize, usize)> {
+        let sizes = self.get(schema::CF_META, schema::META_KMER_INDEX.as_bytes())?;
+        Some((sizes[0] as usize, sizes[1] as usize))
+    }
+
+    /// Est id#s with the k-mer, within the k-mer index of the sequence type.
+    pub fn get_kmer_ids(&self, seq_type: &str, kmer: &[u8]) -> Vec<u32> {
+        let prefix = schema::kmer_prefix(&seq_type, &kmer);
+        self.iter(schema::CF_KMER_INDEX, &prefix)
+            .take_while(|(k, _v)| k.starts_with(&prefix))
+            .map(|(k, _v)| schema::decode_index_id(&k))
+            .collect()
+    }
+
+    /// Blast results of the hmm search, in order of score descending.
+    pub fn get_blast_results(&self, hmmsearch_id: u32) -> Vec<Blast> {
+        let prefix = schema::id_key(hmmsearch_id);
+        let mut blasts: Vec<Blast> = self
+            .iter(schema::CF_BLAST, &prefix)
+            .take_while(|(k, _v)| k.starts_with(&prefix))
+            .map(|(_k, v)| schema::decode::<Blast>(&v))
+            .collect();
+
+        blasts.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+        blasts
+    }
+}
+
+// Entries of the est sequence, plus its header and digest lookups
+pub fn est_entries(est: &Est) -> Vec<Entry> {
+    let key = schema::id_key(est.id).to_vec();
+    vec![
+        (schema::CF_ESTS, key.to_vec(), schema::encode(&est)),
+        (
+            schema::CF_EST_HEADERS,
+            schema::header_key(&est.header),
+            key.to_vec(),
+        ),
+        (schema::CF_EST_DIGESTS, est.digest.as_bytes().to_vec(), key),
+    ]
+}
+
+/**
+ * Last id# of the SQLite table transferred, written within the same batch as
+ * the records so an interrupted upgrade can resume after the last batch.
+ */
+pub fn progress_entry(table: &str, last_id: u32) -> Entry {
+    (
+        schema::CF_META,
+        schema::progress_key(&table),
+        schema::id_key(last_id).to_vec(),
+    )
+}
+
+pub fn hmmsearch_entry(hmmsearch: &HmmSearch) -> Entry {
+    (
+        schema::CF_HMMSEARCH,
+        schema::id_key(hmmsearch.id).to_vec(),
+        schema::encode(&hmmsearch),
+    )
+}
+
+pub fn blast_entry(blast: &Blast) -> Entry {
+    (
+        schema::CF_BLAST,
+        schema::blast_key(blast.hmmsearch_id, blast.id).to_vec(),
+        schema::encode(&blast),
+    )
 }
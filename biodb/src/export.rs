@@ -0,0 +1,268 @@
+use crate::database::RocksDB;
+use crate::models::{Blast, Est};
+use crate::{store, BIODB_ARGS};
+use biotools::db::schema;
+use log::{error, info};
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+// Tables in the same layout as created by the Orthograph analyzer, with {p} the table prefix
+const CREATE_SQL: &str = "
+    CREATE TABLE {p}_species_info (id INTEGER PRIMARY KEY, name TEXT UNIQUE NOT NULL);
+    CREATE TABLE {p}_ests (id INTEGER PRIMARY KEY, digest TEXT(32) NOT NULL, taxid INTEGER NOT NULL, type INTEGER NOT NULL, date INTEGER, header TEXT NOT NULL, sequence TEXT);
+    CREATE TABLE {p}_hmmsearch (id INTEGER PRIMARY KEY, taxid INTEGER NOT NULL, query TEXT NOT NULL, target TEXT(32) NOT NULL, score DOUBLE NOT NULL, evalue TEXT(8) NOT NULL, log_evalue DOUBLE NOT NULL DEFAULT '-999', env_start INTEGER NOT NULL, env_end INTEGER NOT NULL, ali_start INTEGER NOT NULL, ali_end INTEGER NOT NULL, hmm_start INTEGER NOT NULL, hmm_end INTEGER NOT NULL);
+    CREATE TABLE {p}_blast (id INTEGER PRIMARY KEY, taxid INTEGER NOT NULL, query TEXT(32) NOT NULL, target INTEGER NOT NULL, score DOUBLE NOT NULL, evalue TEXT(8) NOT NULL, log_evalue DOUBLE NOT NULL DEFAULT '-999', start INTEGER NOT NULL, end INTEGER NOT NULL, hmmsearch_id INTEGER NOT NULL);
+    CREATE INDEX {p}_ests_digest ON {p}_ests (digest);
+    CREATE INDEX {p}_hmmsearch_query ON {p}_hmmsearch (query);
+    CREATE INDEX {p}_blast_hmmsearch_id ON {p}_blast (hmmsearch_id);
+";
+
+// Tables compared during verification without their prefix, and the columns of each
+const TABLES: [(&str, &str); 4] = [
+    ("species_info", "id,name"),
+    ("ests", "id,digest,taxid,type,date,header,sequence"),
+    (
+        "hmmsearch",
+        "id,taxid,query,target,score,evalue,log_evalue,env_start,env_end,ali_start,ali_end,hmm_start,hmm_end",
+    ),
+    (
+        "blast",
+        "id,taxid,query,target,score,evalue,log_evalue,start,end,hmmsearch_id",
+    ),
+];
+
+// Name of the table with the --dbtable-prefix
+fn table(name: &str) -> String {
+    format!("{}_{}", BIODB_ARGS.table_prefix, name)
+}
+
+pub fn export_sqlite() {
+    let db = store::select_one();
+    db.check_schema_version();
+
+    // Check output file
+    if Path::new(&BIODB_ARGS.output).exists() {
+        error!("SQLite database already exists at {}, please delete it or specify a different file with --output.", BIODB_ARGS.output);
+        return;
+    }
+
+    // Create database
+    let mut conn = match Connection::open(&BIODB_ARGS.output) {
+        Ok(r) => r,
+        Err(e) => panic!(
+            "Unable to create SQLite database at {}, error: {}",
+            BIODB_ARGS.output, e
+        ),
+    };
+    conn.execute_batch(&CREATE_SQL.replace("{p}", &BIODB_ARGS.table_prefix))
+        .expect("Unable to create tables within SQLite database");
+
+    // Export all records within one transaction
+    let tx = conn.transaction().unwrap();
+    let species = export_species(&tx, &db);
+    let ests = export_ests(&tx, &db);
+    let hmmsearches = export_hmmsearches(&tx, &db);
+    let blasts = export_blasts(&tx, &db);
+    tx.commit().expect("Unable to commit SQLite transaction");
+    info!(
+        "Exported {} species, {} est sequences, {} hmm searches and {} blast results to {}",
+        species, ests, hmmsearches, blasts, BIODB_ARGS.output
+    );
+
+    // Verify, if needed
+    if BIODB_ARGS.verify {
+        verify(&conn);
+    }
+}
+
+fn export_species(conn: &Connection, db: &RocksDB) -> usize {
+    let prefix = schema::species_key("");
+    let mut stmt = conn
+        .prepare(&format!(
+            "INSERT INTO {} (id, name) VALUES (?, ?)",
+            table("species_info")
+        ))
+        .unwrap();
+
+    let mut total = 0;
+    for (key, value) in db.iter(schema::CF_META, &prefix) {
+        if !key.starts_with(&prefix) {
+            break;
+        }
+        let name = String::from_utf8_lossy(&key[prefix.len()..]).to_string();
+        match stmt.execute(params![schema::decode_id(&value), name]) {
+            Ok(_) => {}
+            Err(e) => panic!("Unable to insert species {}, error: {}", name, e),
+        };
+        total += 1;
+    }
+    total
+}
+
+fn export_ests(conn: &Connection, db: &RocksDB) -> usize {
+    let mut stmt = conn.prepare(&format!("INSERT INTO {} (id, digest, taxid, type, date, header, sequence) VALUES (?, ?, ?, ?, ?, ?, ?)", table("ests"))).unwrap();
+
+    let mut total = 0;
+    for (_key, value) in db.iter(schema::CF_ESTS, &[]) {
+        let est: Est = schema::decode(&value);
+        match stmt.execute(params![
+            est.id,
+            est.digest,
+            est.taxid,
+            est.seq_type,
+            est.date,
+            est.header,
+            est.sequence
+        ]) {
+            Ok(_) => {}
+            Err(e) => panic!("Unable to insert est sequence id# {}, error: {}", est.id, e),
+        };
+        total += 1;
+    }
+    total
+}
+
+fn export_hmmsearches(conn: &Connection, db: &RocksDB) -> usize {
+    let mut stmt = conn.prepare(&format!("INSERT INTO {} (id, taxid, query, target, score, evalue, log_evalue, env_start, env_end, ali_start, ali_end, hmm_start, hmm_end) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", table("hmmsearch"))).unwrap();
+
+    let mut total = 0;
+    for h in db.iter_hmmsearches(0) {
+        match stmt.execute(params![
+            h.id,
+            h.taxid,
+            h.query,
+            h.target,
+            h.score,
+            h.evalue,
+            h.log_evalue,
+            h.env_start,
+            h.env_end,
+            h.ali_start,
+            h.ali_end,
+            h.hmm_start,
+            h.hmm_end
+        ]) {
+            Ok(_) => {}
+            Err(e) => panic!("Unable to insert hmm search id# {}, error: {}", h.id, e),
+        };
+        total += 1;
+    }
+    total
+}
+
+fn export_blasts(conn: &Connection, db: &RocksDB) -> usize {
+    let mut stmt = conn.prepare(&format!("INSERT INTO {} (id, taxid, query, target, score, evalue, log_evalue, start, end, hmmsearch_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", table("blast"))).unwrap();
+
+    let mut total = 0;
+    for (_key, value) in db.iter(schema::CF_BLAST, &[]) {
+        let b: Blast = schema::decode(&value);
+        match stmt.execute(params![
+            b.id,
+            b.taxid,
+            b.query,
+            b.target,
+            b.score,
+            b.evalue,
+            b.log_evalue,
+            b.start,
+            b.end,
+            b.hmmsearch_id
+        ]) {
+            Ok(_) => {}
+            Err(e) => panic!("Unable to insert blast result id# {}, error: {}", b.id, e),
+        };
+        total += 1;
+    }
+    total
+}
+
+/**
+ * Compare the row count and checksum of each table against the original SQLite
+ * database.  The checksum is the MD5 of all values in order of id#, so it
+ * catches any changed id#, digest or value.  Exits with status 1 on mismatch.
+ */
+fn verify(exported: &Connection) {
+    if BIODB_ARGS.output == BIODB_ARGS.sqlite_file || !Path::new(&BIODB_ARGS.sqlite_file).exists() {
+        error!(
+            "No original SQLite database found at {} to verify against.",
+            BIODB_ARGS.sqlite_file
+        );
+        std::process::exit(1);
+    }
+    let original = match Connection::open(&BIODB_ARGS.sqlite_file) {
+        Ok(r) => r,
+        Err(e) => panic!(
+            "Unable to open SQLite database at {}, error: {}",
+            BIODB_ARGS.sqlite_file, e
+        ),
+    };
+
+    println!(
+        "{:<24} {:>10} {:>10} {:>34} {:>34}",
+        "Table", "Original", "Exported", "Original Checksum", "Exported Checksum"
+    );
+    let mut is_ok = true;
+    for (name, columns) in TABLES {
+        // Only blast results of an existing hmm search were upgraded to RocksDB
+        let source = match name {
+            "blast" => format!(
+                "{} WHERE hmmsearch_id IN (SELECT id FROM {})",
+                table(&name),
+                table("hmmsearch")
+            ),
+            _ => table(&name),
+        };
+        let (orig_count, orig_sum) = checksum(&original, &source, &columns);
+        let (exp_count, exp_sum) = checksum(&exported, &source, &columns);
+        println!(
+            "{:<24} {:>10} {:>10} {:>34} {:>34}",
+            table(&name),
+            orig_count,
+            exp_count,
+            orig_sum,
+            exp_sum
+        );
+        if orig_count != exp_count || orig_sum != exp_sum {
+            is_ok = false;
+        }
+    }
+
+    if !is_ok {
+        error!("Exported SQLite database does not match the original.");
+        std::process::exit(1);
+    }
+    info!("Exported SQLite database matches the original.");
+}
+
+fn checksum(conn: &Connection, source: &str, columns: &str) -> (u64, String) {
+    let sql = format!("SELECT {} FROM {} ORDER BY id", columns, source);
+    let mut stmt = match conn.prepare(&sql) {
+        Ok(r) => r,
+        Err(e) => panic!("Unable to read {}, error: {}", source, e),
+    };
+    let total = columns.split(",").count();
+
+    // Text and blobs hash the same, as sequences may have been stored as either
+    let mut ctx = md5::Context::new();
+    let mut count: u64 = 0;
+    let mut rows = stmt.query([]).unwrap();
+    while let Some(row) = rows.next().unwrap() {
+        for x in 0..total {
+            match row.get_ref(x).unwrap() {
+                ValueRef::Null => ctx.consume(b"N"),
+                ValueRef::Integer(i) => ctx.consume(format!("I{}", i)),
+                ValueRef::Real(r) => ctx.consume(format!("R{:?}", r)),
+                ValueRef::Text(t) | ValueRef::Blob(t) => {
+                    ctx.consume(b"S");
+                    ctx.consume(t);
+                }
+            };
+            ctx.consume(b"\x1f");
+        }
+        ctx.consume(b"\x1e");
+        count += 1;
+    }
+
+    (count, format!("{:x}", ctx.compute()))
+}
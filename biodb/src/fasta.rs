@@ -0,0 +1,233 @@
+use crate::args::parse_coords;
+use crate::database::RocksDB;
+use crate::hmmsearch::Filters;
+use crate::sequence::{extract, Region, RegionDefaults};
+use crate::{store, BIODB_ARGS};
+use biotools::db::schema;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{error, info, warn};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+pub fn export() {
+    if BIODB_ARGS.list == "" && !BIODB_ARGS.all && BIODB_ARGS.gene == "" {
+        error!("No sequences to export, please use the --list, --gene (-g) or --all flag.");
+        return;
+    }
+
+    // Read list once, as it may be from STDIN
+    let defaults = RegionDefaults::from_args();
+    let list: Vec<Region> = match BIODB_ARGS.list.as_str() {
+        "" => Vec::new(),
+        r => read_list(&r, &defaults).collect(),
+    };
+
+    // Get sequence and coord types
+    let seq_types = seq_types(&BIODB_ARGS.seq_type, BIODB_ARGS.with_aa);
+    let coord_type: &str = match BIODB_ARGS.coord_type.as_str() {
+        "" => seq_types[0],
+        r => r,
+    };
+
+    // Open output
+    let mut out = open_output();
+    let (mut total, mut skipped) = (0, 0);
+    for (species, db) in store::select() {
+        db.check_schema_version();
+
+        // Get regions to export
+        let regions: Box<dyn Iterator<Item = Region>> = if BIODB_ARGS.list != "" {
+            Box::new(list.clone().into_iter())
+        } else if BIODB_ARGS.all {
+            Box::new(all_regions(&db, &defaults))
+        } else {
+            let filters = Filters::from_args();
+            Box::new(gene_regions(&db, &BIODB_ARGS.gene, &defaults, filters).into_iter())
+        };
+
+        let (written, missing) =
+            write_fasta(&mut out, &species, &db, regions, &seq_types, coord_type);
+        total += written;
+        skipped += missing;
+    }
+    out.flush().expect("Unable to write sequence to output");
+
+    let dest = match BIODB_ARGS.output.as_str() {
+        "" => "STDOUT",
+        r => r,
+    };
+    info!(
+        "Exported {} sequences to {}, skipped {}",
+        total, dest, skipped
+    );
+}
+
+// Types of sequence to export for each region
+pub fn seq_types(seq_type: &str, with_aa: bool) -> Vec<&'static str> {
+    match (seq_type, with_aa) {
+        ("aa", _) => vec!["aa"],
+        (_, true) => vec!["nt", "aa"],
+        _ => vec!["nt"],
+    }
+}
+
+/**
+ * Write the regions of the species as FASTA, with each header prefixed by the
+ * species if within a shared store.  Returns the number of sequences written,
+ * and the number skipped as they do not exist or are out of range.
+ */
+pub fn write_fasta(
+    out: &mut dyn Write,
+    species: &str,
+    db: &RocksDB,
+    regions: impl Iterator<Item = Region>,
+    seq_types: &[&str],
+    coord_type: &str,
+) -> (usize, usize) {
+    let (mut total, mut skipped) = (0, 0);
+    for region in regions {
+        // Within a shared store, not every species has each header
+        let est = match db.get_est_by_header(&region.header) {
+            Some(r) => r,
+            None if store::is_store() => continue,
+            None => {
+                warn!("No sequence exists with header: {}", region.header);
+                skipped += 1;
+                continue;
+            }
+        };
+
+        for seq_type in seq_types {
+            let seq = match extract(&est.sequence, &region, seq_type, coord_type) {
+                Ok(r) => r,
+                Err(e) => {
+                    warn!("Skipping {}, {}", region.header, e);
+                    skipped += 1;
+                    continue;
+                }
+            };
+
+            // Prefix header with species, if within a shared store
+            let mut header = region.fasta_header(seq_type, coord_type);
+            if store::is_store() {
+                header = format!("{}|{}", species, header);
+            }
+
+            let line = format!(">{}\n{}\n", header, seq);
+            out.write_all(line.as_bytes())
+                .expect("Unable to write sequence to output");
+            total += 1;
+        }
+    }
+    (total, skipped)
+}
+
+// Output file or STDOUT, gzipped if needed
+fn open_output() -> BufWriter<Box<dyn Write>> {
+    let out: Box<dyn Write> = match BIODB_ARGS.output.as_str() {
+        "" => Box::new(io::stdout()),
+        file => match File::create(&file) {
+            Ok(r) => Box::new(r),
+            Err(e) => panic!("Unable to create output file at {}, error: {}", file, e),
+        },
+    };
+
+    if BIODB_ARGS.gzip {
+        return BufWriter::new(Box::new(GzEncoder::new(out, Compression::default())));
+    }
+    BufWriter::new(out)
+}
+
+/**
+ * Regions from a list of headers, one per line.  Each header may be followed
+ * by tab separated START-END coords and a frame, where a frame of -1 to -3 is
+ * on the reverse strand.  Blank lines and lines starting with # are ignored.
+ */
+fn read_list<'a>(
+    filename: &str,
+    defaults: &'a RegionDefaults,
+) -> impl Iterator<Item = Region> + 'a {
+    let reader: Box<dyn BufRead> = match filename {
+        "-" => Box::new(BufReader::new(io::stdin())),
+        _ => match File::open(&filename) {
+            Ok(r) => Box::new(BufReader::new(r)),
+            Err(e) => panic!("Unable to open header list at {}, error: {}", filename, e),
+        },
+    };
+
+    reader.lines().enumerate().filter_map(move |(x, line)| {
+        let line = line.expect("Unable to read header list");
+        let region = parse_line(&line, &defaults);
+        if region.is_none() && !is_blank(&line) {
+            warn!("Skipping invalid line {} of header list: {}", x + 1, line);
+        }
+        region
+    })
+}
+
+pub fn is_blank(line: &str) -> bool {
+    let line = line.trim();
+    line == "" || line.starts_with("#")
+}
+
+// Region of a line of the header list, or None if it is blank or invalid
+pub fn parse_line(line: &str, defaults: &RegionDefaults) -> Option<Region> {
+    if is_blank(&line) {
+        return None;
+    }
+    let mut cols = line.trim().trim_start_matches(">").split("\t");
+    let header = cols.next()?.trim().to_string();
+
+    // Get coords
+    let coords = match cols.next().map(|c| c.trim()) {
+        None | Some("") => defaults.coords.clone(),
+        Some(c) => parse_coords(&c)?,
+    };
+
+    // Get strand and frame
+    let (strand, frame): (&str, u8) = match cols.next().map(|c| c.trim()) {
+        None | Some("") => (defaults.strand.as_str(), defaults.frame),
+        Some(f) => {
+            let frame = f.trim_start_matches(&['+', '-'][..]).parse::<u8>().ok()?;
+            if frame < 1 || frame > 3 {
+                return None;
+            }
+            match f.chars().next() {
+                Some('-') => ("-", frame),
+                Some('+') => ("+", frame),
+                _ => (defaults.strand.as_str(), frame),
+            }
+        }
+    };
+
+    Some(Region::new(&header, &strand, frame, &coords))
+}
+
+// Every nt transcript within the database, in order of id#
+fn all_regions<'a>(
+    db: &'a RocksDB,
+    defaults: &'a RegionDefaults,
+) -> impl Iterator<Item = Region> + 'a {
+    db.iter_nt_ests()
+        .map(move |est| defaults.region(&est.header))
+}
+
+// Transcripts hit by the hmm searches of the gene, in the strand and frame of the hit, highest score first
+pub fn gene_regions(
+    db: &RocksDB,
+    gene: &str,
+    defaults: &RegionDefaults,
+    filters: Filters,
+) -> Vec<Region> {
+    let mut seen: HashSet<String> = HashSet::new();
+    db.get_indexed_ids(schema::CF_GENE_SCORE_INDEX, &gene)
+        .into_iter()
+        .filter_map(|id| db.get_hmmsearch(id))
+        .filter(|h| filters.passes(h))
+        .filter_map(|h| db.get_est_by_digest(&h.target))
+        .filter(|est| seen.insert(est.header.to_string()))
+        .map(move |est| defaults.region(&est.header))
+        .collect()
+}
@@ -1,15 +1,42 @@
 extern crate serde;
 
-use crate::database::Database;
-use crate::{BIODB_ARGS, ROCKSDB};
+use crate::database::RocksDB;
+use crate::models::{HmmSearch, HmmSearchResult};
+use crate::{store, BIODB_ARGS};
+use biotools::db::schema;
 use log::error;
 use std::io::{self, Write};
 use std::string::String;
 
 pub fn get() {
+    // Get id#
+    let id = match BIODB_ARGS.header.parse::<u32>() {
+        Ok(r) => r,
+        Err(_e) => {
+            error!("Invalid hmm search id# {}", BIODB_ARGS.header);
+            return;
+        }
+    };
+
+    // Within a shared store, the id# may exist for each species
+    if store::is_store() {
+        let selected = store::select();
+        print_results(selected.iter().flat_map(|(species, db)| {
+            db.check_schema_version();
+            to_results(
+                &species,
+                &db,
+                db.get_hmmsearch(id).into_iter(),
+                Filters::from_args(),
+            )
+        }));
+        return;
+    }
+
     // Get hmm search
-    let key = format!("hmmsearch:{}", BIODB_ARGS.header);
-    let json: String = match ROCKSDB.get(&key) {
+    let (species, db) = store::select().remove(0);
+    db.check_schema_version();
+    let hmmsearch = match db.get_hmmsearch(id) {
         Some(r) => r,
         None => {
             error!("No hmm search exists with the id# {}", BIODB_ARGS.header);
@@ -18,36 +45,177 @@ pub fn get() {
     };
 
     // Print result
-    io::stdout().write_all(json.as_bytes()).unwrap();
+    let result = to_result(&species, &db, hmmsearch);
+    if BIODB_ARGS.format == "json" || BIODB_ARGS.format == "" {
+        let json = serde_json::to_string(&result).unwrap();
+        io::stdout().write_all(json.as_bytes()).unwrap();
+        return;
+    }
+    print_results(vec![result].into_iter());
 }
 
 pub fn get_multi() {
-    // Initialize
-    let mut iter = ROCKSDB.db.raw_iterator();
-    let key = format!("hmmsearch:{}", BIODB_ARGS.start);
-    let mut x: i32 = 0;
-    io::stdout().write_all("[".as_bytes()).unwrap();
-
-    // Go through rows
-    iter.seek(&key.as_bytes());
-    while iter.valid() {
-        let json = match iter.value() {
-            Some(r) => String::from_utf8(r.to_vec()).unwrap(),
-            None => break,
-        };
+    // Go through hmm searches, in order of species and id#
+    let selected = store::select();
+    print_results(selected.iter().flat_map(|(species, db)| {
+        db.check_schema_version();
+        to_results(
+            &species,
+            &db,
+            db.iter_hmmsearches(BIODB_ARGS.start),
+            Filters::from_args(),
+        )
+    }));
+}
 
-        if x > 0 {
-            io::stdout().write_all(",".as_bytes()).unwrap();
-        }
-        io::stdout().write_all(json.as_bytes()).unwrap();
-        io::stdout().flush().unwrap();
+pub fn get_by_gene() {
+    // Go through hmm searches of the gene, in order of species and highest score first
+    let selected = store::select();
+    print_results(selected.iter().flat_map(|(species, db)| {
+        db.check_schema_version();
+        let ids = db.get_indexed_ids(schema::CF_GENE_SCORE_INDEX, &BIODB_ARGS.gene);
+        let hmmsearches = ids
+            .into_iter()
+            .filter_map(move |id| db.get_hmmsearch(id))
+            .take_while(|h| h.score >= BIODB_ARGS.min_score);
+        to_results(&species, &db, hmmsearches, Filters::from_args())
+    }));
+}
+
+pub fn get_by_header() {
+    // Go through hmm searches of the transcript, in order of species and id#
+    let base = schema::base_header(&BIODB_ARGS.header);
+    let selected = store::select();
+    print_results(selected.iter().flat_map(|(species, db)| {
+        db.check_schema_version();
+        let ids = db.get_indexed_ids(schema::CF_HEADER_INDEX, &base);
+        to_results(
+            &species,
+            &db,
+            ids.into_iter().filter_map(move |id| db.get_hmmsearch(id)),
+            Filters::from_args(),
+        )
+    }));
+}
+
+/**
+ * Print hmm searches as either JSON, TSV or the FASTA of their target
+ * sequences.  Within a shared store, a species column is added to TSV and
+ * FASTA headers are prefixed by the species.
+ */
+fn print_results(results: impl Iterator<Item = HmmSearchResult>) {
+    let tsv = BIODB_ARGS.format == "tsv";
+    let fasta = BIODB_ARGS.format == "fasta";
+    let species_col = if store::is_store() { "species\t" } else { "" };
+    let mut stdout = io::stdout();
 
+    // Start output
+    if tsv {
+        stdout.write_all(format!("{}id\tgene\theader\tscore\tevalue\tenv_start\tenv_end\tali_start\tali_end\thmm_start\thmm_end\tblast_hits\n", species_col).as_bytes()).unwrap();
+    } else if !fasta {
+        stdout.write_all("[".as_bytes()).unwrap();
+    }
+
+    let mut x: i32 = 0;
+    for res in results {
         if BIODB_ARGS.limit > -1 && x >= BIODB_ARGS.limit {
             break;
         }
-        iter.next();
+
+        let line = if tsv {
+            let h = &res.hmmsearch;
+            let species = if store::is_store() {
+                format!("{}\t", res.species)
+            } else {
+                String::new()
+            };
+            format!(
+                "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                species,
+                h.id,
+                h.query,
+                res.header,
+                h.score,
+                h.evalue,
+                h.env_start,
+                h.env_end,
+                h.ali_start,
+                h.ali_end,
+                h.hmm_start,
+                h.hmm_end,
+                res.blast.len()
+            )
+        } else if fasta {
+            let species = if store::is_store() {
+                format!("{}|", res.species)
+            } else {
+                String::new()
+            };
+            format!(">{}{}\n{}\n", species, res.header, res.sequence)
+        } else if x > 0 {
+            format!(",{}", serde_json::to_string(&res).unwrap())
+        } else {
+            serde_json::to_string(&res).unwrap()
+        };
+        stdout.write_all(line.as_bytes()).unwrap();
+        stdout.flush().unwrap();
         x += 1;
     }
 
-    io::stdout().write_all("]".as_bytes()).unwrap();
+    if !tsv && !fasta {
+        stdout.write_all("]".as_bytes()).unwrap();
+    }
+}
+
+// Minimum score and maximum e-value of the hmm searches to retrieve
+#[derive(Clone, Copy)]
+pub struct Filters {
+    pub min_score: f64,
+    pub max_evalue: f64,
+}
+
+impl Filters {
+    pub fn from_args() -> Self {
+        Self {
+            min_score: BIODB_ARGS.min_score,
+            max_evalue: BIODB_ARGS.max_evalue,
+        }
+    }
+
+    // An e-value that does not parse only passes when not filtering by e-value
+    pub fn passes(&self, hmmsearch: &HmmSearch) -> bool {
+        let evalue_ok = match hmmsearch.evalue.parse::<f64>() {
+            Ok(r) => r <= self.max_evalue,
+            Err(_e) => self.max_evalue == f64::INFINITY,
+        };
+        hmmsearch.score >= self.min_score && evalue_ok
+    }
+}
+
+// Results of the hmm searches that pass the score and e-value filters
+pub fn to_results<'a>(
+    species: &'a str,
+    db: &'a RocksDB,
+    hmmsearches: impl Iterator<Item = HmmSearch> + 'a,
+    filters: Filters,
+) -> impl Iterator<Item = HmmSearchResult> + 'a {
+    hmmsearches
+        .filter(move |h| filters.passes(h))
+        .map(move |h| to_result(&species, &db, h))
+}
+
+// Add species, transcript header and blast results
+pub fn to_result(species: &str, db: &RocksDB, hmmsearch: HmmSearch) -> HmmSearchResult {
+    let (header, sequence) = match db.get_est_by_digest(&hmmsearch.target) {
+        Some(est) => (est.header, est.sequence),
+        None => (String::new(), String::new()),
+    };
+
+    HmmSearchResult {
+        species: species.to_string(),
+        blast: db.get_blast_results(hmmsearch.id),
+        header: header,
+        sequence: sequence,
+        hmmsearch: hmmsearch,
+    }
 }
@@ -8,10 +8,16 @@ use std::io::Write;
 
 mod args;
 mod database;
+mod export;
+mod fasta;
 mod hmmsearch;
 mod models;
+mod search;
 mod sequence;
+mod serve;
 mod sqlite;
+mod stats;
+mod store;
 mod translate;
 mod upgrade_db;
 
@@ -26,11 +32,24 @@ fn main() {
 
     // Perform action
     match BIODB_ARGS.action.as_str() {
-        "upgrade-db" => upgrade_db::upgrade(),
-        "get-sequence" => sequence::get(),
-        "get-hmmsearch" => hmmsearch::get(),
-        "get-hmmsearches" => hmmsearch::get_multi(),
-        _ => error!("Usage: biodb -a (upgrade-db|get-sequence|get-hmmsearch|get-hmmsearches) [-i <INPUT_DIR>] [-h <HEADER|HMM_SEARCH_ID>] [-c S-E] [-t <aa|nt>] [-s <START>] [-l <LIMIT>]]")
+        "upgrade" => upgrade_db::upgrade(),
+        "import" => store::import(),
+        "verify" => upgrade_db::verify(),
+        "seq" => sequence::get(),
+        "hmmsearch get" => hmmsearch::get(),
+        "hmmsearch list" => hmmsearch::get_multi(),
+        "hmmsearch gene" => hmmsearch::get_by_gene(),
+        "hmmsearch header" => hmmsearch::get_by_header(),
+        "search" => search::search(),
+        "export" => fasta::export(),
+        "export-sqlite" => export::export_sqlite(),
+        "stats" => stats::print(),
+        "serve" => serve::serve(),
+        "completions" => args::print_completions(BIODB_ARGS.shell.unwrap()),
+        r => error!(
+            "Unknown command {}, please run biodb --help for the list of commands.",
+            r
+        ),
     };
 }
 
@@ -38,10 +57,10 @@ fn init_logger() {
     // Get log level
     let log_level = LevelFilter::Debug;
 
-    // Init logger
+    // Init logger, on STDERR so it does not mix with sequences written to STDOUT
     Builder::new()
         .format(|buf, record| writeln!(buf, "{}: {}", record.level(), record.args()))
         .filter(None, log_level)
-        .target(Target::Stdout)
+        .target(Target::Stderr)
         .init();
 }
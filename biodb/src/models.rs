@@ -2,8 +2,49 @@ extern crate serde;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct HmmSearch {
+pub use biotools::db::models::{Blast, Est, HmmSearch};
+
+// Hmm search along with its transcript header and blast results, as output
+#[derive(Serialize, Debug)]
+pub struct HmmSearchResult {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub species: String,
+    #[serde(flatten)]
+    pub hmmsearch: HmmSearch,
+    pub header: String,
+    pub blast: Vec<Blast>,
+    // Target sequence, only output as FASTA
+    #[serde(skip)]
+    pub sequence: String,
+}
+
+// Region of a transcript, as output
+#[derive(Serialize, Debug)]
+pub struct SequenceResult {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub species: String,
+    pub header: String,
+    pub seq_type: String,
+    pub sequence: String,
+}
+
+// Occurrence of a search query, with coords on the strand and within the frame translated
+#[derive(Serialize, Debug)]
+pub struct SearchHit {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub species: String,
+    pub header: String,
+    pub seq_type: String,
+    pub strand: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame: Option<u8>,
+    pub start: usize,
+    pub end: usize,
+}
+
+// Records of schema version 0, only read while migrating
+#[derive(Deserialize, Debug)]
+pub struct V0HmmSearch {
     pub taxid: u16,
     pub gene: String,
     pub header: String,
@@ -17,11 +58,11 @@ pub struct HmmSearch {
     pub hmm_start: u16,
     pub hmm_end: u16,
     pub seq_type: u8,
-    pub blast: Vec<Blast>,
+    pub blast: Vec<V0Blast>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Blast {
+#[derive(Deserialize, Debug)]
+pub struct V0Blast {
     pub taxid: u16,
     pub target: u32,
     pub score: f32,
@@ -32,9 +73,3 @@ pub struct Blast {
     pub header: String,
     pub seq_type: u8,
 }
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Sequence {
-    pub seq_type: u8,
-    pub sequence: String,
-}
@@ -0,0 +1,251 @@
+use crate::database::{Database, Entry, RocksDB};
+use crate::models::{Est, SearchHit};
+use crate::sequence::{extract, Region};
+use crate::{store, BIODB_ARGS};
+use biotools::db::schema::{self, AA_KMER_SIZE, CF_KMER_INDEX, NT_KMER_SIZE};
+use log::{info, warn};
+use rayon::prelude::*;
+use std::collections::{BTreeSet, HashSet};
+use std::io::{self, Write};
+
+// Transcripts indexed per WriteBatch
+const BATCH_SIZE: usize = 1000;
+
+/**
+ * Find the nt transcripts containing the query, either an nt substring on
+ * either strand or an aa motif within any of the six frames.  Candidates are
+ * looked up within the k-mer index if it has been built, otherwise every
+ * transcript is scanned.
+ */
+pub fn search() {
+    let query = BIODB_ARGS.query.trim().to_uppercase();
+    let seq_type: &str = match BIODB_ARGS.seq_type.as_str() {
+        "aa" => "aa",
+        _ => "nt",
+    };
+
+    let selected = store::select();
+    let hits = selected.iter().flat_map(|(species, db)| {
+        db.check_schema_version();
+        let query = query.to_string();
+        candidates(&db, &query, seq_type)
+            .flat_map(move |est| find_hits(&species, &est, &query, seq_type))
+    });
+    print_hits(hits);
+}
+
+// Print hits as either JSON or TSV, with a species column if within a shared store
+fn print_hits(hits: impl Iterator<Item = SearchHit>) {
+    let tsv = BIODB_ARGS.format == "tsv";
+    let species_col = if store::is_store() { "species\t" } else { "" };
+    let mut stdout = io::stdout();
+
+    if tsv {
+        stdout
+            .write_all(
+                format!(
+                    "{}header\tseq_type\tstrand\tframe\tstart\tend\n",
+                    species_col
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+    } else {
+        stdout.write_all("[".as_bytes()).unwrap();
+    }
+
+    let mut x: i32 = 0;
+    for hit in hits {
+        if BIODB_ARGS.limit > -1 && x >= BIODB_ARGS.limit {
+            break;
+        }
+
+        let line = if tsv {
+            let species = if store::is_store() {
+                format!("{}\t", hit.species)
+            } else {
+                String::new()
+            };
+            let frame = match hit.frame {
+                Some(r) => r.to_string(),
+                None => String::from("-"),
+            };
+            format!(
+                "{}{}\t{}\t{}\t{}\t{}\t{}\n",
+                species, hit.header, hit.seq_type, hit.strand, frame, hit.start, hit.end
+            )
+        } else if x > 0 {
+            format!(",{}", serde_json::to_string(&hit).unwrap())
+        } else {
+            serde_json::to_string(&hit).unwrap()
+        };
+        stdout.write_all(line.as_bytes()).unwrap();
+        x += 1;
+    }
+
+    if !tsv {
+        stdout.write_all("]".as_bytes()).unwrap();
+    }
+}
+
+// Transcripts that may contain the query, in order of id#
+fn candidates<'a>(
+    db: &'a RocksDB,
+    query: &str,
+    seq_type: &str,
+) -> Box<dyn Iterator<Item = Est> + 'a> {
+    let k: usize = match (db.kmer_sizes(), seq_type) {
+        (Some((nt, _aa)), "nt") => nt,
+        (Some((_nt, aa)), _) => aa,
+        (None, _) => {
+            warn!("No k-mer index has been built, so scanning all transcripts.  Build it with biodb upgrade --kmer-index.");
+            return Box::new(db.iter_nt_ests());
+        }
+    };
+    if query.len() < k {
+        warn!(
+            "Query is shorter than the k-mer size of {}, so scanning all transcripts.",
+            k
+        );
+        return Box::new(db.iter_nt_ests());
+    }
+
+    // Look up both strands of nt queries, as only the forward strand is indexed
+    let mut ids = lookup(&db, &query, seq_type, k);
+    if seq_type == "nt" {
+        ids.extend(lookup(
+            &db,
+            &biotools::reverse_seq(&query.to_string()),
+            seq_type,
+            k,
+        ));
+    }
+    Box::new(ids.into_iter().filter_map(move |id| db.get_est(id)))
+}
+
+// Est id#s having every non-overlapping k-mer of the query, plus its last k-mer
+fn lookup(db: &RocksDB, query: &str, seq_type: &str, k: usize) -> BTreeSet<u32> {
+    let mut starts: Vec<usize> = (0..=query.len() - k).step_by(k).collect();
+    starts.push(query.len() - k);
+
+    let mut ids: Option<BTreeSet<u32>> = None;
+    for start in starts {
+        let found: BTreeSet<u32> = db
+            .get_kmer_ids(seq_type, &query.as_bytes()[start..start + k])
+            .into_iter()
+            .collect();
+        let matched: BTreeSet<u32> = match ids {
+            Some(r) => r.intersection(&found).copied().collect(),
+            None => found,
+        };
+        if matched.is_empty() {
+            return matched;
+        }
+        ids = Some(matched);
+    }
+    ids.unwrap_or_default()
+}
+
+/**
+ * Every occurrence of the query within the transcript.  Coords are on the
+ * strand searched, and aa coords within the translation of the frame, the
+ * same as taken by biodb seq.
+ */
+fn find_hits(species: &str, est: &Est, query: &str, seq_type: &str) -> Vec<SearchHit> {
+    let frames: Vec<u8> = match seq_type {
+        "aa" => vec![1, 2, 3],
+        _ => vec![0],
+    };
+
+    let mut hits: Vec<SearchHit> = Vec::new();
+    for (strand, frame, seq) in translations(&est, &frames, seq_type) {
+        let mut pos = 0;
+        while let Some(offset) = seq.get(pos..).and_then(|r| r.find(&query)) {
+            hits.push(SearchHit {
+                species: species.to_string(),
+                header: est.header.to_string(),
+                seq_type: seq_type.to_string(),
+                strand: strand.to_string(),
+                frame: if frame > 0 { Some(frame) } else { None },
+                start: pos + offset,
+                end: pos + offset + query.len(),
+            });
+            pos += offset + 1;
+        }
+    }
+    hits
+}
+
+// Sequence of the transcript on each strand, translated in each of the frames for aa
+fn translations(est: &Est, frames: &[u8], seq_type: &str) -> Vec<(&'static str, u8, String)> {
+    let nt_seq = est.sequence.to_uppercase();
+    let mut seqs = Vec::new();
+    for strand in ["+", "-"] {
+        for frame in frames {
+            let region = Region::new(&est.header, strand, *frame, &(0..0));
+            let seq = extract(&nt_seq, &region, seq_type, seq_type).unwrap();
+            seqs.push((strand, *frame, seq));
+        }
+    }
+    seqs
+}
+
+/**
+ * (Re-)build the k-mer index from the nt transcripts.  The sizes are only
+ * written once finished, so an interrupted build is not used.
+ */
+pub fn build_kmer_index(db: &RocksDB) {
+    db.delete(schema::CF_META, schema::META_KMER_INDEX.as_bytes());
+    db.clear(CF_KMER_INDEX);
+
+    let mut batch: Vec<Est> = Vec::with_capacity(BATCH_SIZE);
+    let mut total = 0;
+    for est in db.iter_nt_ests() {
+        batch.push(est);
+        if batch.len() >= BATCH_SIZE {
+            total += write_kmers(&db, &batch);
+            batch.clear();
+        }
+    }
+    total += write_kmers(&db, &batch);
+
+    db.put(
+        schema::CF_META,
+        schema::META_KMER_INDEX.as_bytes(),
+        &[NT_KMER_SIZE as u8, AA_KMER_SIZE as u8],
+    );
+    info!("Indexed k-mers of {} transcripts", total);
+}
+
+fn write_kmers(db: &RocksDB, ests: &[Est]) -> usize {
+    let entries: Vec<Entry> = ests
+        .par_iter()
+        .flat_map_iter(|est| kmer_entries(est))
+        .collect();
+    db.write_batch(&entries);
+    ests.len()
+}
+
+// Index entries of the distinct nt k-mers of the forward strand, and aa k-mers of all six frames
+fn kmer_entries(est: &Est) -> Vec<Entry> {
+    let mut kmers: HashSet<(&str, Vec<u8>)> = HashSet::new();
+    for kmer in est.sequence.to_uppercase().as_bytes().windows(NT_KMER_SIZE) {
+        kmers.insert(("nt", kmer.to_vec()));
+    }
+    for (_strand, _frame, seq) in translations(&est, &[1, 2, 3], "aa") {
+        for kmer in seq.as_bytes().windows(AA_KMER_SIZE) {
+            kmers.insert(("aa", kmer.to_vec()));
+        }
+    }
+
+    kmers
+        .into_iter()
+        .map(|(seq_type, kmer)| {
+            (
+                CF_KMER_INDEX,
+                schema::kmer_key(&seq_type, &kmer, est.id),
+                Vec::new(),
+            )
+        })
+        .collect()
+}
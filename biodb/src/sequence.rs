@@ -1,35 +1,215 @@
 extern crate serde;
 
-use crate::database::Database;
-use crate::models::Sequence;
-use crate::{BIODB_ARGS, ROCKSDB};
+use crate::models::SequenceResult;
+use crate::{store, BIODB_ARGS};
 use log::error;
 use std::io::{self, Write};
+use std::ops::Range;
 use std::string::String;
 
-pub fn get() {
-    // Get sequence
-    let mut seq: String = match ROCKSDB.get(&BIODB_ARGS.header) {
-        Some(r) => r,
-        None => {
-            error!("No sequence exists with header: {}", BIODB_ARGS.header);
-            return;
+/**
+ * Region of a transcript to retrieve.  The strand and reading frame are taken
+ * from any [revcomp] and [translate(N)] within the header, unless given.
+ */
+#[derive(Clone)]
+pub struct Region {
+    pub header: String,
+    pub revcomp: bool,
+    pub frame: usize,
+    pub translated: bool,
+    pub coords: Option<Range<usize>>,
+}
+
+impl Region {
+    pub fn new(header: &String, strand: &str, frame: u8, coords: &Range<usize>) -> Self {
+        let (base_header, is_revcomp, translate) = biotools::translate_header(&header);
+
+        Self {
+            header: base_header,
+            revcomp: match strand {
+                "-" => true,
+                "+" => false,
+                _ => is_revcomp,
+            },
+            frame: match (frame, translate) {
+                (0, 0) => 1,
+                (0, t) => t as usize,
+                (f, _) => f as usize,
+            },
+            translated: translate > 0,
+            coords: match coords.end {
+                0 => None,
+                _ => Some(coords.clone()),
+            },
+        }
+    }
+
+    // Type of sequence to retrieve, aa for a translated header unless given
+    pub fn seq_type<'a>(&self, seq_type: &'a str) -> &'a str {
+        match seq_type {
+            "" if self.translated => "aa",
+            "" => "nt",
+            r => r,
+        }
+    }
+
+    // Header for FASTA output, with the strand, frame and coords retrieved
+    pub fn fasta_header(&self, seq_type: &str, coord_type: &str) -> String {
+        let translate: u8 = if seq_type == "aa" {
+            self.frame as u8
+        } else {
+            0
+        };
+        let header = biotools::format_header(&self.header, &(self.revcomp as u8), &translate);
+
+        match &self.coords {
+            Some(r) => format!("{} [{}({}-{})]", header, coord_type, r.start, r.end),
+            None => header,
         }
+    }
+}
+
+// Strand, frame and coords of each region without its own
+#[derive(Clone)]
+pub struct RegionDefaults {
+    pub strand: String,
+    pub frame: u8,
+    pub coords: Range<usize>,
+}
+
+impl RegionDefaults {
+    pub fn from_args() -> Self {
+        Self {
+            strand: BIODB_ARGS.strand.to_string(),
+            frame: BIODB_ARGS.frame,
+            coords: BIODB_ARGS.coords.clone(),
+        }
+    }
+
+    pub fn region(&self, header: &String) -> Region {
+        Region::new(header, &self.strand, self.frame, &self.coords)
+    }
+}
+
+pub fn get() {
+    let region = RegionDefaults::from_args().region(&BIODB_ARGS.header);
+
+    // Get sequence and coord types
+    let seq_type: &str = region.seq_type(&BIODB_ARGS.seq_type);
+    let coord_type: &str = match BIODB_ARGS.coord_type.as_str() {
+        "" => seq_type,
+        r => r,
+    };
+
+    // Raw sequence by default, or FASTA within a shared store to tell species apart
+    let format: &str = match BIODB_ARGS.format.as_str() {
+        "" if store::is_store() => "fasta",
+        "" => "raw",
+        r => r,
+    };
+
+    // Go through species
+    let mut results: Vec<SequenceResult> = Vec::new();
+    for (species, db) in store::select() {
+        db.check_schema_version();
+        let est = match db.get_est_by_header(&region.header) {
+            Some(r) => r,
+            None => continue,
+        };
+
+        let seq = match extract(&est.sequence, &region, seq_type, coord_type) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("{}", e);
+                continue;
+            }
+        };
+        results.push(SequenceResult {
+            species: species,
+            header: region.fasta_header(seq_type, coord_type),
+            seq_type: seq_type.to_string(),
+            sequence: seq,
+        });
+    }
+
+    if results.len() == 0 {
+        error!("No sequence exists with header: {}", region.header);
+        return;
+    }
+
+    // Output sequences, with their species if within a shared store
+    let mut stdout = io::stdout();
+    if format == "json" {
+        let json = serde_json::to_string(&results).unwrap();
+        stdout.write_all(json.as_bytes()).unwrap();
+        return;
+    }
+    for res in results {
+        let line = match (format, store::is_store()) {
+            ("raw", false) => res.sequence,
+            ("raw", true) => format!("{}\n", res.sequence),
+            (_, true) => format!(">{}|{}\n{}\n", res.species, res.header, res.sequence),
+            (_, false) => format!(">{}\n{}\n", res.header, res.sequence),
+        };
+        stdout.write_all(line.as_bytes()).unwrap();
+    }
+}
+
+/**
+ * Get the region of the nt sequence, as either nt or translated to aa.  Aa
+ * coords of a nt sequence are the codons of those residues, and nt coords of
+ * an aa sequence are translated starting at the frame offset within them.
+ */
+pub fn extract(
+    nt_seq: &String,
+    region: &Region,
+    seq_type: &str,
+    coord_type: &str,
+) -> Result<String, String> {
+    let mut seq = nt_seq.to_string();
+
+    // Reverse complement, if needed
+    if region.revcomp {
+        seq = biotools::reverse_seq(&seq);
+    }
+
+    // Get coords, in nt positions of the strand if needed
+    let offset = region.frame - 1;
+    let coords: Option<Range<usize>> = match (&region.coords, coord_type, seq_type) {
+        (None, _, _) => None,
+        (Some(r), "aa", "nt") => Some((offset + r.start * 3)..(offset + r.end * 3)),
+        (Some(r), _, _) => Some(r.clone()),
     };
 
-    // Extract sequence from json
-    //let mut seq: Sequence = serde_json::from_str(&json).unwrap();
+    // Slice nt sequence
+    if coord_type == "nt" || seq_type == "nt" {
+        if let Some(range) = &coords {
+            seq = slice(&seq, &range, "nt")?;
+        }
+    }
 
-    // Check for coords
-    if BIODB_ARGS.coords.end > 0 {
-        seq = seq[BIODB_ARGS.coords.start..BIODB_ARGS.coords.end].to_string();
+    // Translate in reading frame, and slice aa sequence
+    if seq_type == "aa" {
+        seq = crate::translate::translate(&seq.get(offset..).unwrap_or("").to_string());
+        if let (Some(range), "aa") = (&coords, coord_type) {
+            seq = slice(&seq, &range, "aa")?;
+        }
     }
 
-    // Translate sequence, if needed
-    if BIODB_ARGS.seq_type == "aa".to_string() {
-        seq = crate::translate::translate(&seq);
+    Ok(seq)
+}
+
+// Slice of the sequence, or an error if the coords are out of range
+fn slice(seq: &String, range: &Range<usize>, coord_type: &str) -> Result<String, String> {
+    if range.start >= range.end || range.end > seq.len() {
+        return Err(format!(
+            "Coordinates {}-{} are out of range of the {} sequence, which has length {}.",
+            range.start,
+            range.end,
+            coord_type,
+            seq.len()
+        ));
     }
 
-    // Output sequence
-    io::stdout().write_all(seq.as_bytes()).unwrap();
+    Ok(seq[range.start..range.end].to_string())
 }
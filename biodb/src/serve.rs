@@ -0,0 +1,392 @@
+use crate::args::parse_coords;
+use crate::database::RocksDB;
+use crate::fasta::{gene_regions, parse_line, seq_types, write_fasta};
+use crate::hmmsearch::{to_result, to_results, Filters};
+use crate::models::{HmmSearchResult, SequenceResult};
+use crate::sequence::{extract, Region, RegionDefaults};
+use crate::{store, BIODB_ARGS};
+use biotools::db::schema;
+use log::{error, info, warn};
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::panic;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// Threads handling requests
+const WORKERS: usize = 4;
+
+// Largest request body read, plenty for a header list of many thousand lines
+const MAX_BODY_BYTES: u64 = 8 * 1024 * 1024;
+
+// Content type and body of a response, or its status code and error message
+type Reply = Result<(&'static str, Vec<u8>), (u16, String)>;
+
+/**
+ * Serve read-only JSON endpoints over HTTP, so notebooks and scripts may query
+ * the database without starting biodb for each lookup.  The database is opened
+ * read-only, so other readers may use it at the same time.
+ */
+pub fn serve() {
+    // Check database before accepting requests
+    for (_species, db) in store::select() {
+        db.check_schema_version();
+    }
+
+    let server = match Server::http(&BIODB_ARGS.bind) {
+        Ok(r) => Arc::new(r),
+        Err(e) => panic!("Unable to listen on {}, error: {}", BIODB_ARGS.bind, e),
+    };
+    info!(
+        "Serving {} at http://{}",
+        BIODB_ARGS.rocksdb, BIODB_ARGS.bind
+    );
+
+    let workers: Vec<thread::JoinHandle<()>> = (0..WORKERS)
+        .map(|_| {
+            let server = server.clone();
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    handle(request);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
+
+fn handle(mut request: Request) {
+    let method = request.method().clone();
+    let url = request.url().to_string();
+
+    // Read header list posted to export, up to the maximum size
+    let mut body = String::new();
+    let mut too_large = false;
+    if method == Method::Post {
+        match request
+            .as_reader()
+            .take(MAX_BODY_BYTES + 1)
+            .read_to_string(&mut body)
+        {
+            Ok(len) => too_large = len as u64 > MAX_BODY_BYTES,
+            Err(e) => warn!("Unable to read request body of {}, error: {}", url, e),
+        };
+    }
+
+    // A panic, such as from a database error, only fails the one request
+    let reply = if too_large {
+        Err((
+            413,
+            format!("Request body is larger than {} bytes.", MAX_BODY_BYTES),
+        ))
+    } else {
+        match panic::catch_unwind(|| route(&method, &url, &body)) {
+            Ok(r) => r,
+            Err(_e) => Err((
+                500,
+                "Internal error, please see the server log.".to_string(),
+            )),
+        }
+    };
+
+    let (status, content_type, data) = match reply {
+        Ok((content_type, data)) => (200, content_type, data),
+        Err((status, message)) => {
+            let json = serde_json::json!({ "error": message });
+            (status, "application/json", json.to_string().into_bytes())
+        }
+    };
+    info!("{} {} {}", method, url, status);
+
+    let response = Response::from_data(data)
+        .with_status_code(status)
+        .with_header(Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()).unwrap());
+    if let Err(e) = request.respond(response) {
+        error!("Unable to send response to {}, error: {}", url, e);
+    }
+}
+
+fn route(method: &Method, url: &str, body: &str) -> Reply {
+    let (path, query) = url.split_once("?").unwrap_or((url, ""));
+    let params = Params(
+        form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect(),
+    );
+    let segments: Vec<String> = path
+        .trim_matches('/')
+        .split("/")
+        .map(|s| percent_decode_str(s).decode_utf8_lossy().to_string())
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(|s| s.as_str()).collect();
+
+    match (method, segments.as_slice()) {
+        (Method::Get, ["sequence", header]) => get_sequence(&header, &params),
+        (Method::Get, ["hmmsearch", id]) => get_hmmsearch(&id, &params),
+        (Method::Get, ["gene", gene]) => get_by_gene(&gene, &params),
+        (Method::Get, ["header", header]) => get_by_header(&header, &params),
+        (Method::Get, ["export"]) | (Method::Post, ["export"]) => export(&params, &body),
+        _ => Err((404, format!("No endpoint exists at {} {}", method, path))),
+    }
+}
+
+// GET /sequence/<header>, the region of the transcript within each species that has it
+fn get_sequence(header: &str, params: &Params) -> Reply {
+    let region = params.defaults()?.region(&header.to_string());
+    let seq_type = region.seq_type(params.choice("type", &["nt", "aa"])?);
+    let coord_type = match params.choice("coord_type", &["nt", "aa"])? {
+        "" => seq_type,
+        r => r,
+    };
+
+    let mut results: Vec<SequenceResult> = Vec::new();
+    for (species, db) in params.species()? {
+        let est = match db.get_est_by_header(&region.header) {
+            Some(r) => r,
+            None => continue,
+        };
+        let seq = extract(&est.sequence, &region, seq_type, coord_type).map_err(|e| (400, e))?;
+        results.push(SequenceResult {
+            species: species,
+            header: region.fasta_header(seq_type, coord_type),
+            seq_type: seq_type.to_string(),
+            sequence: seq,
+        });
+    }
+
+    if results.len() == 0 {
+        return Err((
+            404,
+            format!("No sequence exists with header: {}", region.header),
+        ));
+    }
+    to_json(&results)
+}
+
+// GET /hmmsearch/<id>, the hmm search with the id# within each species
+fn get_hmmsearch(id: &str, params: &Params) -> Reply {
+    let id = match id.parse::<u32>() {
+        Ok(r) => r,
+        Err(_e) => return Err((400, format!("Invalid hmm search id# {}", id))),
+    };
+
+    let results: Vec<HmmSearchResult> = params
+        .species()?
+        .iter()
+        .filter_map(|(species, db)| Some(to_result(&species, &db, db.get_hmmsearch(id)?)))
+        .collect();
+
+    if results.len() == 0 {
+        return Err((404, format!("No hmm search exists with the id# {}", id)));
+    }
+    to_json(&results)
+}
+
+// GET /gene/<gene>, hmm searches of the gene, highest score first
+fn get_by_gene(gene: &str, params: &Params) -> Reply {
+    let filters = params.filters()?;
+    let selected = params.species()?;
+    let results = selected.iter().flat_map(|(species, db)| {
+        let hmmsearches = db
+            .get_indexed_ids(schema::CF_GENE_SCORE_INDEX, &gene)
+            .into_iter()
+            .filter_map(move |id| db.get_hmmsearch(id))
+            .take_while(move |h| h.score >= filters.min_score);
+        to_results(&species, &db, hmmsearches, filters)
+    });
+    to_json(&params.limit(results)?)
+}
+
+// GET /header/<header>, hmm searches of the transcript, in order of id#
+fn get_by_header(header: &str, params: &Params) -> Reply {
+    let filters = params.filters()?;
+    let base = schema::base_header(&header);
+    let selected = params.species()?;
+    let results = selected.iter().flat_map(|(species, db)| {
+        let ids = db.get_indexed_ids(schema::CF_HEADER_INDEX, &base);
+        to_results(
+            &species,
+            &db,
+            ids.into_iter().filter_map(move |id| db.get_hmmsearch(id)),
+            filters,
+        )
+    });
+    to_json(&params.limit(results)?)
+}
+
+/**
+ * GET /export?gene=<gene> or ?headers=<header,...>, or POST /export with a
+ * header list in the same format as the --list file.  Returns FASTA.
+ */
+fn export(params: &Params, body: &str) -> Reply {
+    let defaults = params.defaults()?;
+
+    // Get regions of list
+    let mut list: Vec<Region> = Vec::new();
+    for (x, line) in body.lines().enumerate() {
+        match parse_line(&line, &defaults) {
+            Some(r) => list.push(r),
+            None if crate::fasta::is_blank(&line) => {}
+            None => {
+                return Err((
+                    400,
+                    format!("Invalid line {} of header list: {}", x + 1, line),
+                ))
+            }
+        };
+    }
+    for header in params.get("headers").split(",").map(|h| h.trim()) {
+        if header != "" {
+            list.push(defaults.region(&header.to_string()));
+        }
+    }
+
+    let gene = params.get("gene");
+    if list.len() == 0 && gene == "" {
+        return Err((
+            400,
+            "No sequences to export, please give a gene, headers, or POST a list of headers."
+                .to_string(),
+        ));
+    }
+
+    // Get sequence and coord types
+    let seq_types = seq_types(
+        params.choice("type", &["nt", "aa"])?,
+        params.flag("with_aa"),
+    );
+    let coord_type = match params.choice("coord_type", &["nt", "aa"])? {
+        "" => seq_types[0],
+        r => r,
+    };
+
+    let filters = params.filters()?;
+    let mut out: Vec<u8> = Vec::new();
+    for (species, db) in params.species()? {
+        let regions = match list.len() {
+            0 => gene_regions(&db, &gene, &defaults, filters),
+            _ => list.clone(),
+        };
+        write_fasta(
+            &mut out,
+            &species,
+            &db,
+            regions.into_iter(),
+            &seq_types,
+            coord_type,
+        );
+    }
+    Ok(("text/x-fasta", out))
+}
+
+fn to_json<T: Serialize>(results: &T) -> Reply {
+    Ok(("application/json", serde_json::to_vec(results).unwrap()))
+}
+
+// Query parameters of a request, which default to the flags the server was started with
+struct Params(HashMap<String, String>);
+
+impl Params {
+    fn get(&self, name: &str) -> &str {
+        self.0.get(name).map(|s| s.as_str()).unwrap_or("")
+    }
+
+    fn parse<T: FromStr>(&self, name: &str, default: T) -> Result<T, (u16, String)> {
+        match self.get(name) {
+            "" => Ok(default),
+            r => r
+                .parse::<T>()
+                .map_err(|_e| (400, format!("Invalid value of {}: {}", name, r))),
+        }
+    }
+
+    // Value that must be one of the choices, or empty
+    fn choice(&self, name: &str, choices: &[&str]) -> Result<&str, (u16, String)> {
+        match self.get(name) {
+            "" => Ok(""),
+            r if choices.contains(&r) => Ok(r),
+            r => Err((
+                400,
+                format!(
+                    "Invalid value of {}: {}, must be one of {}",
+                    name,
+                    r,
+                    choices.join(", ")
+                ),
+            )),
+        }
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.0.contains_key(name) && !["0", "false"].contains(&self.get(name))
+    }
+
+    fn defaults(&self) -> Result<RegionDefaults, (u16, String)> {
+        let mut defaults = RegionDefaults::from_args();
+        defaults.strand = match self.choice("strand", &["+", "-"])? {
+            "" => defaults.strand,
+            r => r.to_string(),
+        };
+        defaults.frame = match self.parse::<u8>("frame", defaults.frame)? {
+            r if r > 3 => return Err((400, format!("Invalid frame {}, must be 1, 2 or 3", r))),
+            r => r,
+        };
+        if self.get("coords") != "" {
+            defaults.coords = match parse_coords(self.get("coords")) {
+                Some(r) => r,
+                None => {
+                    return Err((
+                        400,
+                        "Invalid coords, must be formatted as START-END (eg. 5-61).".to_string(),
+                    ))
+                }
+            };
+        }
+        Ok(defaults)
+    }
+
+    fn filters(&self) -> Result<Filters, (u16, String)> {
+        let defaults = Filters::from_args();
+        Ok(Filters {
+            min_score: self.parse::<f64>("min_score", defaults.min_score)?,
+            max_evalue: self.parse::<f64>("max_evalue", defaults.max_evalue)?,
+        })
+    }
+
+    // Results up to the limit, where -1 is no limit
+    fn limit<T>(&self, results: impl Iterator<Item = T>) -> Result<Vec<T>, (u16, String)> {
+        let limit = self.parse::<i32>("limit", BIODB_ARGS.limit)?;
+        Ok(match limit {
+            -1 => results.collect(),
+            r => results.take(r.max(0) as usize).collect(),
+        })
+    }
+
+    // Databases of the species given as a comma separated list, within a shared store
+    fn species(&self) -> Result<Vec<(String, RocksDB)>, (u16, String)> {
+        let names: Vec<String> = self
+            .get("species")
+            .split(",")
+            .map(|s| s.trim().to_string())
+            .filter(|s| s != "" && s != "all")
+            .collect();
+        if names.len() == 0 || !store::is_store() {
+            return Ok(store::select());
+        }
+
+        let species = store::list_species();
+        if let Some(name) = names.iter().find(|n| !species.iter().any(|s| &&s.0 == n)) {
+            return Err((
+                404,
+                format!("No species named {} within the shared store", name),
+            ));
+        }
+        Ok(store::select_species(&names))
+    }
+}
@@ -1,10 +1,16 @@
-use crate::models::Blast;
+use crate::models::{Blast, Est, HmmSearch};
 use crate::BIODB_ARGS;
-use rusqlite::{Connection, Error};
-use std::collections::HashMap;
+use rusqlite::{Connection, Error, Row};
 use std::path::Path;
 use std::sync::Arc;
 
+// Columns of each table, in the order read by the below functions
+pub const EST_COLUMNS: &str = "id,digest,taxid,type,date,header,sequence";
+pub const HMMSEARCH_COLUMNS: &str =
+    "id,taxid,query,target,score,evalue,log_evalue,env_start,env_end,ali_start,ali_end,hmm_start,hmm_end";
+pub const BLAST_COLUMNS: &str =
+    "id,taxid,query,target,score,evalue,log_evalue,start,end,hmmsearch_id";
+
 pub struct Sqlite {
     pub conn: Arc<Connection>,
 }
@@ -41,42 +47,51 @@ impl Sqlite {
             .unwrap();
         total
     }
+}
 
-    pub fn get_blast_results(&self, hmmsearch_id: &u32) -> Result<Vec<Blast>, Error> {
-        // Execute sql
-        let mut stmt = self.conn.prepare("SELECT b.*,e.header,e.type FROM orthograph_blast b, orthograph_ests e WHERE b.hmmsearch_id = ? AND b.query = e.digest ORDER BY score DESC").unwrap();
-        let mut rows = match stmt.query([&hmmsearch_id]) {
-            Ok(r) => r,
-            Err(e) => panic!(
-                "Unable to execute SQL to obtain blast results, error: {}",
-                e
-            ),
-        };
-        let mut blast_results: Vec<Blast> = Vec::new();
-
-        // Go through rows
-        loop {
-            // Get row
-            let row = match rows.next()? {
-                Some(r) => r,
-                None => break,
-            };
-            let header: String = row.get(10)?;
+pub fn est_from_row(row: &Row) -> Result<Est, Error> {
+    Ok(Est {
+        id: row.get(0)?,
+        digest: row.get(1)?,
+        taxid: row.get(2)?,
+        seq_type: row.get(3)?,
+        date: row.get(4)?,
+        header: row.get(5)?,
+        sequence: row.get(6)?,
+    })
+}
 
-            let blast = Blast {
-                taxid: row.get(1)?,
-                target: row.get(3)?,
-                score: row.get(4)?,
-                evalue: row.get(5)?,
-                log_evalue: row.get(6)?,
-                blast_start: row.get(7)?,
-                blast_end: row.get(8)?,
-                seq_type: row.get(11)?,
-                header: header.replace(" ", "_").to_string(),
-            };
-            blast_results.push(blast);
-        }
+// Hmm search from the row, with its columns starting at the offset
+pub fn hmmsearch_from_row(row: &Row, offset: usize) -> Result<HmmSearch, Error> {
+    Ok(HmmSearch {
+        id: row.get(offset)?,
+        taxid: row.get(offset + 1)?,
+        query: row.get(offset + 2)?,
+        target: row.get(offset + 3)?,
+        score: row.get(offset + 4)?,
+        evalue: row.get(offset + 5)?,
+        log_evalue: row.get(offset + 6)?,
+        env_start: row.get(offset + 7)?,
+        env_end: row.get(offset + 8)?,
+        ali_start: row.get(offset + 9)?,
+        ali_end: row.get(offset + 10)?,
+        hmm_start: row.get(offset + 11)?,
+        hmm_end: row.get(offset + 12)?,
+    })
+}
 
-        Ok(blast_results)
-    }
+// Blast result from the row, with its columns starting at the offset
+pub fn blast_from_row(row: &Row, offset: usize) -> Result<Blast, Error> {
+    Ok(Blast {
+        id: row.get(offset)?,
+        taxid: row.get(offset + 1)?,
+        query: row.get(offset + 2)?,
+        target: row.get(offset + 3)?,
+        score: row.get(offset + 4)?,
+        evalue: row.get(offset + 5)?,
+        log_evalue: row.get(offset + 6)?,
+        start: row.get(offset + 7)?,
+        end: row.get(offset + 8)?,
+        hmmsearch_id: row.get(offset + 9)?,
+    })
 }
@@ -0,0 +1,99 @@
+use crate::{BIODB_ARGS, ROCKSDB};
+use biotools::db::schema::{self, COLUMN_FAMILIES, OPTIONAL_COLUMN_FAMILIES};
+use rocksdb::{properties, IteratorMode};
+
+// Record type stored within each column family
+fn record_type(cf: &str) -> &str {
+    match cf {
+        schema::CF_META => "Meta",
+        schema::CF_ESTS => "Est Sequences",
+        schema::CF_EST_HEADERS => "Header Index",
+        schema::CF_EST_DIGESTS => "Digest Index",
+        schema::CF_HMMSEARCH => "Hmm Searches",
+        schema::CF_BLAST => "Blast Results",
+        schema::CF_HEADER_INDEX => "Header Hits",
+        schema::CF_GENE_SCORE_INDEX => "Score Index",
+        schema::CF_KMER_INDEX => "K-mer Index",
+        _ => cf,
+    }
+}
+
+/**
+ * Print the number of records and bytes used per record type.  Raw bytes are
+ * the encoded keys and values before compression, and disk bytes the size of
+ * the SST files of the column family, after flushing the memtables.
+ */
+pub fn print() {
+    ROCKSDB.check_schema_version();
+
+    println!("RocksDB: {}", BIODB_ARGS.rocksdb);
+    println!(
+        "{:<16} {:>10} {:>14} {:>14} {:>10} {:>8}",
+        "Record Type", "Records", "Raw Bytes", "Disk Bytes", "Per Record", "Ratio"
+    );
+
+    let (mut total_records, mut total_raw, mut total_disk) = (0, 0, 0);
+    for name in COLUMN_FAMILIES.iter().chain(&OPTIONAL_COLUMN_FAMILIES) {
+        let cf = ROCKSDB.cf(name);
+        match ROCKSDB.db.flush_cf(cf) {
+            Ok(_) => {}
+            Err(e) => panic!("Unable to flush column family {}, error: {}", name, e),
+        };
+
+        // Count records and raw bytes
+        let (mut records, mut raw): (u64, u64) = (0, 0);
+        for (key, value) in ROCKSDB.db.iterator_cf(cf, IteratorMode::Start) {
+            records += 1;
+            raw += (key.len() + value.len()) as u64;
+        }
+
+        // Get bytes on disk
+        let disk = match ROCKSDB
+            .db
+            .property_int_value_cf(cf, properties::TOTAL_SST_FILES_SIZE)
+        {
+            Ok(r) => r.unwrap_or(0),
+            Err(e) => panic!(
+                "Unable to retrieve size of column family {}, error: {}",
+                name, e
+            ),
+        };
+
+        println!(
+            "{:<16} {:>10} {:>14} {:>14} {:>10} {:>8}",
+            record_type(name),
+            records,
+            raw,
+            disk,
+            per_record(disk, records),
+            ratio(raw, disk)
+        );
+        total_records += records;
+        total_raw += raw;
+        total_disk += disk;
+    }
+
+    println!(
+        "{:<16} {:>10} {:>14} {:>14} {:>10} {:>8}",
+        "Total",
+        total_records,
+        total_raw,
+        total_disk,
+        per_record(total_disk, total_records),
+        ratio(total_raw, total_disk)
+    );
+}
+
+fn per_record(bytes: u64, records: u64) -> String {
+    match records {
+        0 => String::from("-"),
+        _ => format!("{:.1}", bytes as f64 / records as f64),
+    }
+}
+
+fn ratio(raw: u64, disk: u64) -> String {
+    match disk {
+        0 => String::from("-"),
+        _ => format!("{:.2}", raw as f64 / disk as f64),
+    }
+}
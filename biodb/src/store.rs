@@ -0,0 +1,251 @@
+use crate::database::{Database, Entry, RocksDB};
+use crate::{search, upgrade_db};
+use crate::{BIODB_ARGS, ROCKSDB};
+use biotools::db::schema::{self, COLUMN_FAMILIES, OPTIONAL_COLUMN_FAMILIES, SCHEMA_VERSION};
+use log::{info, warn};
+use rocksdb::{IteratorMode, DB};
+use std::path::Path;
+
+// Records per WriteBatch when copying a per-species RocksDB
+const BATCH_SIZE: usize = 10000;
+
+pub fn is_store() -> bool {
+    BIODB_ARGS.store != ""
+}
+
+/**
+ * Databases to query along with their species name.  Either the species of the
+ * shared store selected with --species, or the RocksDB of the input directory
+ * with an empty name.
+ */
+pub fn select() -> Vec<(String, RocksDB)> {
+    select_species(&BIODB_ARGS.species)
+}
+
+// Databases of the given species names, or all species if none are given
+pub fn select_species(names: &[String]) -> Vec<(String, RocksDB)> {
+    if !is_store() {
+        return vec![(String::new(), ROCKSDB.clone())];
+    }
+    check_store();
+    let species = list_species();
+
+    // All species, skipping any not fully imported
+    if names.len() == 0 {
+        return species
+            .into_iter()
+            .map(|(name, num)| (name, ROCKSDB.species_view(num)))
+            .filter(|(name, db)| {
+                let is_ok = db.schema_version() == Some(SCHEMA_VERSION) && !db.is_upgrading();
+                if !is_ok {
+                    warn!(
+                        "Skipping species {}, as it has not been fully imported.",
+                        name
+                    );
+                }
+                is_ok
+            })
+            .collect();
+    }
+
+    names
+        .iter()
+        .map(|name| match species.iter().find(|s| &s.0 == name) {
+            Some((_, num)) => (name.to_string(), ROCKSDB.species_view(*num)),
+            None => panic!(
+                "No species named {} within the shared store at {}",
+                name, BIODB_ARGS.rocksdb
+            ),
+        })
+        .collect()
+}
+
+// The one database to use, for actions that only work on a single species
+pub fn select_one() -> RocksDB {
+    let mut selected = select();
+    if selected.len() != 1 {
+        panic!(
+            "biodb {} works on one species at a time, please specify it with --species.",
+            BIODB_ARGS.action
+        );
+    }
+    selected.remove(0).1
+}
+
+// Name and number of each species within the store
+pub fn list_species() -> Vec<(String, u32)> {
+    let prefix = schema::store_species_key("");
+    ROCKSDB
+        .iter(schema::CF_META, &prefix)
+        .take_while(|(k, _v)| k.starts_with(&prefix))
+        .map(|(k, v)| {
+            (
+                String::from_utf8_lossy(&k[prefix.len()..]).to_string(),
+                schema::decode_id(&v),
+            )
+        })
+        .collect()
+}
+
+pub fn check_store() {
+    if ROCKSDB
+        .get(schema::CF_META, schema::META_STORE.as_bytes())
+        .is_some()
+    {
+        return;
+    }
+
+    match ROCKSDB.schema_version() {
+        None => panic!(
+            "No shared store exists at {}, please add a species to it with biodb import first.",
+            BIODB_ARGS.rocksdb
+        ),
+        Some(_) => panic!(
+            "RocksDB at {} is the database of a single species, not a shared store.",
+            BIODB_ARGS.rocksdb
+        ),
+    };
+}
+
+/**
+ * Add a species to the shared store, from the RocksDB within its input
+ * directory if it exists, otherwise its SQLite database.  Importing a species
+ * again replaces it, unless resuming an interrupted import from SQLite.
+ */
+pub fn import() {
+    if !is_store() {
+        panic!("Please specify the shared store to import into with --store.");
+    }
+
+    // Create store, if needed
+    if ROCKSDB.schema_version() == None {
+        ROCKSDB.put(schema::CF_META, schema::META_STORE.as_bytes(), &[]);
+        ROCKSDB.set_schema_version(SCHEMA_VERSION);
+        info!("Created shared store at {}", BIODB_ARGS.rocksdb);
+    }
+    check_store();
+
+    // Get species name, the input directory without any .fa by default
+    let name: String = match BIODB_ARGS.species.len() {
+        0 => BIODB_ARGS
+            .input_dir
+            .split("/")
+            .last()
+            .unwrap()
+            .trim_end_matches(".fa")
+            .to_string(),
+        1 => BIODB_ARGS.species[0].to_string(),
+        _ => panic!("Please specify only one species name to import as."),
+    };
+
+    // Get number of species, or add it
+    let species = list_species();
+    let num: u32 = match species.iter().find(|s| s.0 == name) {
+        Some((_, num)) => *num,
+        None => {
+            let num = species.iter().map(|s| s.1).max().unwrap_or(0) + 1;
+            ROCKSDB.put(
+                schema::CF_META,
+                &schema::store_species_key(&name),
+                &schema::id_key(num),
+            );
+            num
+        }
+    };
+    let db = ROCKSDB.species_view(num);
+
+    // Resume import from SQLite
+    let rocksdb_dir = format!("{}/rocksdb", BIODB_ARGS.input_dir);
+    let has_sqlite = Path::new(&BIODB_ARGS.sqlite_file).exists();
+    if db.is_upgrading() && has_sqlite {
+        info!("Resuming import of species {} from SQLite", name);
+        upgrade_db::transfer_from_sqlite(&db);
+        upgrade_db::finish(&db, true);
+        info!("Imported species {} into shared store", name);
+        return;
+    }
+
+    // Remove existing records of the species
+    for cf in COLUMN_FAMILIES.iter().chain(&OPTIONAL_COLUMN_FAMILIES) {
+        db.clear(cf);
+    }
+
+    // Import
+    if Path::new(&rocksdb_dir).exists() {
+        info!("Importing species {} from RocksDB at {}", name, rocksdb_dir);
+        copy_rocksdb(&rocksdb_dir, &db);
+        if BIODB_ARGS.kmer_index && db.kmer_sizes().is_none() {
+            search::build_kmer_index(&db);
+        }
+        db.set_schema_version(SCHEMA_VERSION);
+    } else if has_sqlite {
+        info!(
+            "Importing species {} from SQLite database at {}",
+            name, BIODB_ARGS.sqlite_file
+        );
+        upgrade_db::transfer_from_sqlite(&db);
+        upgrade_db::finish(&db, true);
+    } else {
+        panic!(
+            "Neither a RocksDB nor SQLite database exists within {} to import.",
+            BIODB_ARGS.input_dir
+        );
+    }
+    info!("Imported species {} into shared store", name);
+}
+
+// Copy all records of a per-species RocksDB, except its schema version which is set once finished
+fn copy_rocksdb(dir: &str, db: &RocksDB) {
+    // Open with any optional column families it has
+    let opts = rocksdb::Options::default();
+    let existing: Vec<String> = DB::list_cf(&opts, &dir).unwrap_or(Vec::new());
+    let cf_names: Vec<&str> = COLUMN_FAMILIES
+        .iter()
+        .chain(
+            OPTIONAL_COLUMN_FAMILIES
+                .iter()
+                .filter(|cf| existing.contains(&cf.to_string())),
+        )
+        .copied()
+        .collect();
+    let source = match DB::open_cf_for_read_only(&opts, &dir, &cf_names, false) {
+        Ok(r) => r,
+        Err(e) => panic!(
+            "Unable to open RocksDB at {}, error: {}.  If it was created by an earlier version of biodb, please run biodb upgrade on it first.",
+            dir, e
+        ),
+    };
+
+    // Check schema version
+    let version = source
+        .get_cf(
+            source.cf_handle(schema::CF_META).unwrap(),
+            schema::META_SCHEMA_VERSION,
+        )
+        .unwrap()
+        .map(|r| schema::decode_id(&r));
+    if version != Some(SCHEMA_VERSION) {
+        panic!(
+            "RocksDB at {} is not at schema version {}, please run biodb upgrade on it first.",
+            dir, SCHEMA_VERSION
+        );
+    }
+
+    for cf in cf_names {
+        let mut batch: Vec<Entry> = Vec::with_capacity(BATCH_SIZE);
+        let mut total = 0;
+        for (key, value) in source.iterator_cf(source.cf_handle(cf).unwrap(), IteratorMode::Start) {
+            if cf == schema::CF_META && &*key == schema::META_SCHEMA_VERSION.as_bytes() {
+                continue;
+            }
+            batch.push((cf, key.to_vec(), value.to_vec()));
+            if batch.len() >= BATCH_SIZE {
+                db.write_batch(&batch);
+                batch.clear();
+            }
+            total += 1;
+        }
+        db.write_batch(&batch);
+        info!("Copied {} records of {}", total, cf);
+    }
+}
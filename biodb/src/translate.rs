@@ -6,24 +6,23 @@ lazy_static! {
     pub static ref NT_TABLE: HashMap<String, String> = instantiate_nt_table();
 }
 
+/**
+ * Translate the nt sequence in the first frame.  Every codon gives one
+ * residue, with X for any not within the table such as those holding an N,
+ * so aa positions always map back to their codons.
+ */
 pub fn translate(sequence: &String) -> String {
-    // Translate
-    let chunks = sequence
-        .as_bytes()
-        .chunks(3)
-        .map(|buf| unsafe { str::from_utf8_unchecked(buf) })
-        .collect::<Vec<&str>>();
-
-    let mut res: String = "".to_owned();
-    for c in chunks {
-        let s = match NT_TABLE.get(&c.to_string()) {
-            Some(r) => r,
-            None => continue,
+    let sequence = sequence.to_uppercase();
+    let mut res = String::with_capacity(sequence.len() / 3);
+    for codon in sequence.as_bytes().chunks_exact(3) {
+        let residue = str::from_utf8(codon).ok().and_then(|c| NT_TABLE.get(c));
+        match residue {
+            Some(r) => res.push_str(r.as_str()),
+            None => res.push('X'),
         };
-        res.push_str(s.as_str());
     }
 
-    res.to_string()
+    res
 }
 
 pub fn instantiate_nt_table() -> HashMap<String, String> {
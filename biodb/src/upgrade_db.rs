@@ -1,110 +1,289 @@
 extern crate serde;
 
-use crate::database::Database;
-use crate::models::{HmmSearch, Sequence};
-use crate::sqlite::Sqlite;
-use crate::ROCKSDB;
-use log::info;
-use rusqlite::Error;
+use crate::database::{
+    blast_entry, est_entries, hmmsearch_entry, progress_entry, Database, Entry, RocksDB,
+};
+use crate::models::{Blast, Est, HmmSearch, V0HmmSearch};
+use crate::sqlite::{
+    blast_from_row, est_from_row, hmmsearch_from_row, Sqlite, BLAST_COLUMNS, EST_COLUMNS,
+    HMMSEARCH_COLUMNS,
+};
+use crate::{search, store, BIODB_ARGS, ROCKSDB};
+use biotools::db::schema::{self, SCHEMA_VERSION};
+use log::{error, info, warn};
+use rayon::prelude::*;
+use rocksdb::{Direction, IteratorMode};
+use rusqlite::{Error, Row};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::io;
 use std::io::Write;
+use std::path::Path;
+
+// Records per WriteBatch during transfer from SQLite
+const BATCH_SIZE: usize = 10000;
+
+// Records of each table compared by verify
+const VERIFY_SAMPLES: u32 = 100;
+
+// Blast results of an existing hmm search, being those transferred to RocksDB
+const JOINED_BLAST: &str =
+    "orthograph_blast WHERE hmmsearch_id IN (SELECT id FROM orthograph_hmmsearch)";
 
 pub fn upgrade() {
+    if store::is_store() {
+        panic!("A shared store is not upgraded, please use biodb import to add each species to it instead.");
+    }
+
+    // Check schema version
+    let version = ROCKSDB.schema_version();
+    if let Some(v) = version {
+        if v > SCHEMA_VERSION {
+            panic!("RocksDB is at schema version {}, which is newer than this version of biodb supports ({}).", v, SCHEMA_VERSION);
+        }
+    }
+    let has_sqlite = Path::new(&BIODB_ARGS.sqlite_file).exists();
+
+    // Only add the k-mer index to an already upgraded database
+    if version == Some(SCHEMA_VERSION) && BIODB_ARGS.kmer_index && !ROCKSDB.is_upgrading() {
+        search::build_kmer_index(&ROCKSDB);
+        return;
+    }
+
+    // Transfer from SQLite, if still available
+    if has_sqlite {
+        transfer_from_sqlite(&ROCKSDB);
+    } else if ROCKSDB.is_upgrading() {
+        panic!(
+            "Upgrade of RocksDB was interrupted, but the SQLite database no longer exists at {} to resume it from.",
+            BIODB_ARGS.sqlite_file
+        );
+    } else if version == Some(0) {
+        info!("SQLite database not found at {}, migrating existing RocksDB from schema version 0 instead.", BIODB_ARGS.sqlite_file);
+        migrate_v0();
+    } else if version == Some(1) {
+        info!("SQLite database not found at {}, re-encoding existing RocksDB from schema version 1 instead.", BIODB_ARGS.sqlite_file);
+        reencode::<Est>(schema::CF_ESTS);
+        reencode::<HmmSearch>(schema::CF_HMMSEARCH);
+        reencode::<Blast>(schema::CF_BLAST);
+    } else if version == Some(2) {
+        info!("SQLite database not found at {}, adding indexes to existing RocksDB of schema version 2 instead.", BIODB_ARGS.sqlite_file);
+    } else if version == Some(SCHEMA_VERSION) {
+        info!(
+            "RocksDB is already at schema version {}, nothing to upgrade.",
+            SCHEMA_VERSION
+        );
+        return;
+    } else {
+        panic!(
+            "SQLite database file does not exist at {}.",
+            BIODB_ARGS.sqlite_file
+        );
+    }
+
+    // Remove version 0 keys, and finish
+    remove_v0_keys();
+    finish(&ROCKSDB, has_sqlite);
+    info!(
+        "RocksDB is now at schema version {}.  If desired, you may now delete the SQLite database from your hard drive.",
+        SCHEMA_VERSION
+    );
+}
+
+pub fn verify() {
+    let db = store::select_one();
+    db.check_schema_version();
+    if !verify_transfer(&Sqlite::new(), &db) {
+        error!("RocksDB does not match the SQLite database.");
+        std::process::exit(1);
+    }
+}
+
+/**
+ * Build indexes, and verify against SQLite if it was transferred from it,
+ * before setting the schema version to mark the database as complete.
+ */
+pub fn finish(db: &RocksDB, has_sqlite: bool) {
+    build_indexes(&db);
+
+    // Build the k-mer index if asked for, or rebuild an existing one to keep it current
+    if BIODB_ARGS.kmer_index || db.kmer_sizes().is_some() {
+        search::build_kmer_index(&db);
+    }
+    if has_sqlite && !verify_transfer(&Sqlite::new(), &db) {
+        error!("RocksDB does not match the SQLite database, so its schema version has not been set.  Please run biodb {} again.", BIODB_ARGS.action);
+        std::process::exit(1);
+    }
+    db.clear_progress();
+    db.set_schema_version(SCHEMA_VERSION);
+}
+
+pub fn transfer_from_sqlite(db: &RocksDB) {
     // Connect to SQLite
     let sqlite = Sqlite::new();
 
+    // Resume, or unset the schema version until finished so a partial database is not used
+    if db.is_upgrading() {
+        info!("Resuming interrupted upgrade of RocksDB.");
+    } else {
+        db.delete(schema::CF_META, schema::META_SCHEMA_VERSION.as_bytes());
+    }
+
+    // Transfer species
+    match transfer_species(&sqlite, &db) {
+        Ok(_) => {}
+        Err(e) => panic!("Unable to transfer species, error: {}", e),
+    };
+
     // Transfer est sequences
-    match transfer_est_sequences(&sqlite) {
+    match transfer_est_sequences(&sqlite, &db) {
         Ok(_) => {}
         Err(e) => panic!("Unable to transfer est sequences, error: {}", e),
     };
 
     // Transfer hmm searches
-    match transfer_hmm_searches(&sqlite) {
+    match transfer_hmm_searches(&sqlite, &db) {
         Ok(_) => {}
         Err(e) => panic!("Unable to transfer hmm searches, error: {}", e),
     };
-    info!("Successfully transferred SQLite database to RocksDB.  If desired, you may now delete the SQLite database from your hard drive.");
+    info!("Successfully transferred SQLite database to RocksDB.");
 }
 
-fn transfer_hmm_searches(sqlite: &Sqlite) -> Result<(), Error> {
-    // Get total est sequences
+// Species names and id#s, so the reporter can find its species id# without SQLite
+fn transfer_species(sqlite: &Sqlite, db: &RocksDB) -> Result<(), Error> {
+    let mut stmt = sqlite
+        .conn
+        .prepare("SELECT id,name FROM orthograph_species_info")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let (id, name): (u32, String) = (row.get(0)?, row.get(1)?);
+        db.put(
+            schema::CF_META,
+            &schema::species_key(&name),
+            &schema::id_key(id),
+        );
+    }
+    Ok(())
+}
+
+/**
+ * Hmm searches along with their blast results, read with one join and grouped
+ * by hmm search.  They are written in batches of whole hmm searches, so a
+ * resumed upgrade never leaves one with only part of its blast results.
+ */
+fn transfer_hmm_searches(sqlite: &Sqlite, db: &RocksDB) -> Result<(), Error> {
+    // Get total hmm searches
     let total: u64 = sqlite.get_total("orthograph_hmmsearch");
+    let start = db.get_progress("orthograph_hmmsearch");
     info!("Transferring {} hmm searches to RocksDB", total);
+    if start > 0 {
+        info!("Resuming after hmm search id# {}", start);
+    }
+    let orphans = sqlite.get_total("orthograph_blast") - sqlite.get_total(JOINED_BLAST);
+    if orphans > 0 {
+        warn!(
+            "Skipping {} blast results whose hmm search does not exist.",
+            orphans
+        );
+    }
 
     // Execute SQL
-    let mut stmt = sqlite.conn.prepare("SELECT s.*,e.header,e.type FROM orthograph_hmmsearch s, orthograph_ests e WHERE s.target = e.digest ORDER BY id").unwrap();
-    let mut rows = match stmt.query([]) {
+    let sql = format!(
+        "SELECT {},{} FROM orthograph_hmmsearch h LEFT JOIN orthograph_blast b ON b.hmmsearch_id = h.id WHERE h.id > ? ORDER BY h.id, b.id",
+        prefix_columns(HMMSEARCH_COLUMNS, "h"),
+        prefix_columns(BLAST_COLUMNS, "b")
+    );
+    let mut stmt = sqlite.conn.prepare(&sql).unwrap();
+    let mut rows = match stmt.query([start]) {
         Ok(r) => r,
         Err(e) => panic!(
-            "Unable to execute SQL statement to retrieve est sequencs, error: {}",
+            "Unable to execute SQL statement to retrieve hmm searches, error: {}",
             e
         ),
     };
 
     // Go through rows
-    let mut x = 0;
-    loop {
-        // Get row
-        let row = match rows.next()? {
-            Some(r) => r,
-            None => break,
-        };
-
-        // Get blast results
+    let (mut x, mut total_blast) = (0, 0);
+    let mut batch: Vec<(HmmSearch, Vec<Blast>)> = Vec::with_capacity(BATCH_SIZE);
+    while let Some(row) = rows.next()? {
         let hmmsearch_id: u32 = row.get(0)?;
-        let blast_results = sqlite.get_blast_results(&hmmsearch_id).unwrap();
-        let header: String = row.get(13)?;
-
-        // Set hmm search
-        let hmm_search = HmmSearch {
-            taxid: row.get(1)?,
-            gene: row.get(2)?,
-            header: header.replace(" ", "_").to_string(),
-            score: row.get(4)?,
-            evalue: row.get(5)?,
-            log_evalue: row.get(6)?,
-            env_start: row.get(7)?,
-            env_end: row.get(8)?,
-            ali_start: row.get(9)?,
-            ali_end: row.get(10)?,
-            hmm_start: row.get(11)?,
-            hmm_end: row.get(12)?,
-            seq_type: row.get(14)?,
-            blast: blast_results,
-        };
 
-        // Set key and json
-        let key = format!("hmmsearch:{}", x);
-        let json = serde_json::to_string(&hmm_search).unwrap();
+        // Start next hmm search, writing the batch once full
+        if batch.last().map(|b| b.0.id) != Some(hmmsearch_id) {
+            if batch.len() >= BATCH_SIZE {
+                x += write_hmm_searches(&db, &mut batch, &mut total_blast);
+                print!(".");
+                io::stdout().flush().unwrap();
+            }
+            batch.push((hmmsearch_from_row(&row, 0)?, Vec::new()));
+        }
 
-        // Add to RocksDB
-        ROCKSDB.put(&key, &json);
-        x += 1;
-        if x % 1000 == 0 {
-            print!(".");
-            io::stdout().flush().unwrap();
+        // Add blast result, if any
+        if row.get::<_, Option<u32>>(13)?.is_some() {
+            let blast = blast_from_row(&row, 13)?;
+            batch.last_mut().unwrap().1.push(blast);
         }
     }
+    x += write_hmm_searches(&db, &mut batch, &mut total_blast);
 
     // Finish
     println!("");
-    info!("Successfully transferred total of {} hmm searches", x + 1);
+    info!(
+        "Successfully transferred total of {} hmm searches with {} blast results",
+        x, total_blast
+    );
 
     Ok(())
 }
 
-fn transfer_est_sequences(sqlite: &Sqlite) -> Result<(), Error> {
+fn write_hmm_searches(
+    db: &RocksDB,
+    batch: &mut Vec<(HmmSearch, Vec<Blast>)>,
+    total_blast: &mut usize,
+) -> usize {
+    let last_id = match batch.last() {
+        Some(r) => r.0.id,
+        None => return 0,
+    };
+
+    // Encode in parallel
+    let mut entries: Vec<Entry> = batch
+        .par_iter()
+        .map(|(hmmsearch, blasts)| {
+            let mut entries: Vec<Entry> = blasts.iter().map(|b| blast_entry(&b)).collect();
+            entries.push(hmmsearch_entry(&hmmsearch));
+            entries
+        })
+        .collect::<Vec<Vec<Entry>>>()
+        .concat();
+    let total = batch.len();
+    *total_blast += entries.len() - total;
+
+    // Save progress within the same batch
+    entries.push(progress_entry("orthograph_hmmsearch", last_id));
+    db.write_batch(&entries);
+
+    batch.clear();
+    total
+}
+
+fn transfer_est_sequences(sqlite: &Sqlite, db: &RocksDB) -> Result<(), Error> {
     // Get total est sequences
     let total_est: u64 = sqlite.get_total("orthograph_ests");
+    let start = db.get_progress("orthograph_ests");
     info!("Transferring {} est sequences to RocksDB", total_est);
+    if start > 0 {
+        info!("Resuming after est sequence id# {}", start);
+    }
 
     // Execute sql statement
-    let mut stmt = sqlite
-        .conn
-        .prepare("SELECT id,type,header,sequence FROM orthograph_ests ORDER BY id")
-        .unwrap();
-    let mut rows = match stmt.query([]) {
+    let sql = format!(
+        "SELECT {} FROM orthograph_ests WHERE id > ? ORDER BY id",
+        EST_COLUMNS
+    );
+    let mut stmt = sqlite.conn.prepare(&sql).unwrap();
+    let mut rows = match stmt.query([start]) {
         Ok(r) => r,
         Err(e) => panic!(
             "Unable to execute SQL statement to retrieve est sequencs, error: {}",
@@ -114,30 +293,311 @@ fn transfer_est_sequences(sqlite: &Sqlite) -> Result<(), Error> {
 
     // Go through rows
     let mut x = 0;
-    loop {
-        // Get row
-        let row = match rows.next()? {
-            Some(r) => r,
-            None => break,
-        };
-        let header: String = row.get(2)?;
-
-        // Define sequences
-        let seq = Sequence {
-            seq_type: row.get(1)?,
-            sequence: row.get(3)?,
-        };
-
-        // Add to RocksDB
-        ROCKSDB.put(&header.replace(" ", "_"), &seq.sequence);
-        if x % 10000 == 0 {
+    let mut batch: Vec<Est> = Vec::with_capacity(BATCH_SIZE);
+    while let Some(row) = rows.next()? {
+        batch.push(est_from_row(&row)?);
+        if batch.len() >= BATCH_SIZE {
+            x += write_est_sequences(&db, &mut batch);
             print!(".");
             io::stdout().flush().unwrap();
         }
-        x += 1;
     }
+    x += write_est_sequences(&db, &mut batch);
     println!("");
     info!("Successfully saved {} est sequences to RocksDB", x);
 
     Ok(())
 }
+
+fn write_est_sequences(db: &RocksDB, batch: &mut Vec<Est>) -> usize {
+    let last_id = match batch.last() {
+        Some(r) => r.id,
+        None => return 0,
+    };
+
+    // Encode in parallel
+    let mut entries: Vec<Entry> = batch
+        .par_iter()
+        .map(|est| est_entries(&est))
+        .collect::<Vec<Vec<Entry>>>()
+        .concat();
+
+    // Save progress within the same batch
+    entries.push(progress_entry("orthograph_ests", last_id));
+    db.write_batch(&entries);
+
+    let total = batch.len();
+    batch.clear();
+    total
+}
+
+/**
+ * Compare the record counts against the SQLite database, plus a random sample
+ * of records from each table, which must encode the same as what is stored.
+ */
+fn verify_transfer(sqlite: &Sqlite, db: &RocksDB) -> bool {
+    let species_prefix = schema::species_key("");
+    let species = db
+        .iter(schema::CF_META, &species_prefix)
+        .take_while(|(k, _v)| k.starts_with(&species_prefix))
+        .count() as u64;
+
+    let results = [
+        (
+            "orthograph_species_info",
+            species,
+            sqlite.get_total("orthograph_species_info"),
+            0,
+            0,
+        ),
+        verify_table(
+            sqlite,
+            db,
+            "orthograph_ests",
+            "orthograph_ests",
+            EST_COLUMNS,
+            schema::CF_ESTS,
+            |row| Ok(est_entries(&est_from_row(&row)?).remove(0)),
+        ),
+        verify_table(
+            sqlite,
+            db,
+            "orthograph_hmmsearch",
+            "orthograph_hmmsearch",
+            HMMSEARCH_COLUMNS,
+            schema::CF_HMMSEARCH,
+            |row| Ok(hmmsearch_entry(&hmmsearch_from_row(&row, 0)?)),
+        ),
+        verify_table(
+            sqlite,
+            db,
+            "orthograph_blast",
+            JOINED_BLAST,
+            BLAST_COLUMNS,
+            schema::CF_BLAST,
+            |row| Ok(blast_entry(&blast_from_row(&row, 0)?)),
+        ),
+    ];
+
+    println!(
+        "{:<24} {:>10} {:>10} {:>10} {:>10}",
+        "Table", "SQLite", "RocksDB", "Sampled", "Mismatched"
+    );
+    let mut is_ok = true;
+    for (table, rocksdb_total, sqlite_total, sampled, mismatched) in results {
+        println!(
+            "{:<24} {:>10} {:>10} {:>10} {:>10}",
+            table, sqlite_total, rocksdb_total, sampled, mismatched
+        );
+        if rocksdb_total != sqlite_total || mismatched > 0 {
+            is_ok = false;
+        }
+    }
+
+    if is_ok {
+        info!("RocksDB matches the SQLite database.");
+    }
+    is_ok
+}
+
+// Table name, RocksDB and SQLite totals of the source rows, and the number of sampled and mismatched records
+fn verify_table(
+    sqlite: &Sqlite,
+    db: &RocksDB,
+    table: &'static str,
+    source: &str,
+    columns: &str,
+    cf: &str,
+    to_entry: fn(&Row) -> Result<Entry, Error>,
+) -> (&'static str, u64, u64, u32, u32) {
+    let sql = format!(
+        "SELECT {} FROM {} ORDER BY RANDOM() LIMIT {}",
+        columns, source, VERIFY_SAMPLES
+    );
+    let mut stmt = match sqlite.conn.prepare(&sql) {
+        Ok(r) => r,
+        Err(e) => panic!("Unable to read table {}, error: {}", table, e),
+    };
+
+    let (mut sampled, mut mismatched) = (0, 0);
+    let mut rows = stmt.query([]).unwrap();
+    while let Some(row) = rows.next().unwrap() {
+        let (cf, key, value) = match to_entry(&row) {
+            Ok(r) => r,
+            Err(e) => panic!("Unable to read record of {}, error: {}", table, e),
+        };
+        if db.get(cf, &key) != Some(value) {
+            warn!(
+                "Record of {} with key {:?} does not match the SQLite database.",
+                table, key
+            );
+            mismatched += 1;
+        }
+        sampled += 1;
+    }
+
+    (
+        table,
+        db.count(cf),
+        sqlite.get_total(source),
+        sampled,
+        mismatched,
+    )
+}
+
+fn prefix_columns(columns: &str, alias: &str) -> String {
+    columns
+        .split(",")
+        .map(|c| format!("{}.{}", alias, c))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/**
+ * Convert a version 0 RocksDB without its SQLite database.  Version 0 did not
+ * keep everything, so est and blast id#s are re-numbered, hmm search id#s are
+ * taken from their position (id# = n + 1), digests are re-computed as the MD5
+ * of the sequence same as the analyzer, and dates are left empty.  Headers keep
+ * their spaces replaced with underscores.
+ */
+fn migrate_v0() {
+    let mut seq_types: HashMap<String, u8> = HashMap::new();
+    let mut hmmsearches: Vec<(u32, V0HmmSearch)> = Vec::new();
+    let mut sequences: Vec<(String, String)> = Vec::new();
+
+    // Gather records from default column family
+    for (key, value) in ROCKSDB.db.iterator(IteratorMode::Start) {
+        let key = String::from_utf8_lossy(&key).to_string();
+        if let Some(num) = key.strip_prefix("hmmsearch:") {
+            let hmmsearch: V0HmmSearch = match serde_json::from_slice(&value) {
+                Ok(r) => r,
+                Err(e) => panic!(
+                    "Unable to decode version 0 hmm search {}, error: {}",
+                    key, e
+                ),
+            };
+            seq_types.insert(hmmsearch.header.to_string(), hmmsearch.seq_type);
+            for blast in &hmmsearch.blast {
+                seq_types.insert(blast.header.to_string(), blast.seq_type);
+            }
+            hmmsearches.push((num.parse::<u32>().unwrap() + 1, hmmsearch));
+        } else {
+            sequences.push((key, String::from_utf8_lossy(&value).to_string()));
+        }
+    }
+    info!(
+        "Migrating {} est sequences and {} hmm searches from schema version 0",
+        sequences.len(),
+        hmmsearches.len()
+    );
+
+    // Transfer est sequences
+    let taxid: u32 = hmmsearches.first().map(|h| h.1.taxid as u32).unwrap_or(0);
+    let mut digests: HashMap<String, String> = HashMap::new();
+    for (num, (header, sequence)) in sequences.into_iter().enumerate() {
+        let digest = format!("{:x}", md5::compute(sequence.as_bytes()));
+        digests.insert(header.to_string(), digest.to_string());
+        ROCKSDB.put_est(&Est {
+            id: num as u32 + 1,
+            digest: digest,
+            taxid: taxid,
+            seq_type: *seq_types.get(&header).unwrap_or(&0),
+            date: None,
+            header: header,
+            sequence: sequence,
+        });
+    }
+
+    // Transfer hmm searches
+    let mut blast_id: u32 = 0;
+    for (id, h) in hmmsearches {
+        let target = match digests.get(&h.header) {
+            Some(r) => r.to_string(),
+            None => {
+                warn!(
+                    "No est sequence found for header {} of hmm search id# {}, leaving target empty.",
+                    h.header, id
+                );
+                String::from("")
+            }
+        };
+
+        for b in &h.blast {
+            blast_id += 1;
+            ROCKSDB.put_blast(&Blast {
+                id: blast_id,
+                taxid: b.taxid as u32,
+                query: digests
+                    .get(&b.header)
+                    .map(|d| d.to_string())
+                    .unwrap_or_default(),
+                target: b.target,
+                score: b.score as f64,
+                evalue: b.evalue.to_string(),
+                log_evalue: b.log_evalue as f64,
+                start: b.blast_start as u32,
+                end: b.blast_end as u32,
+                hmmsearch_id: id,
+            });
+        }
+
+        ROCKSDB.put_hmmsearch(&HmmSearch {
+            id: id,
+            taxid: h.taxid as u32,
+            query: h.gene,
+            target: target,
+            score: h.score as f64,
+            evalue: h.evalue,
+            log_evalue: h.log_evalue as f64,
+            env_start: h.env_start as u32,
+            env_end: h.env_end as u32,
+            ali_start: h.ali_start as u32,
+            ali_end: h.ali_end as u32,
+            hmm_start: h.hmm_start as u32,
+            hmm_end: h.hmm_end as u32,
+        });
+    }
+}
+
+// (Re-)build the header and gene score indexes from the hmm searches
+fn build_indexes(db: &RocksDB) {
+    for cf in schema::INDEX_COLUMN_FAMILIES {
+        db.clear(cf);
+    }
+
+    let mut total = 0;
+    for hmmsearch in db.iter_hmmsearches(0) {
+        let header: String = match db.get_est_by_digest(&hmmsearch.target) {
+            Some(est) => est.header,
+            None => String::from(""),
+        };
+        db.index_hmmsearch(&hmmsearch, &header);
+        total += 1;
+    }
+    info!("Indexed {} hmm searches by header and gene score", total);
+}
+
+// Re-write all JSON values of schema version 1 with the binary codec
+fn reencode<T: Serialize + DeserializeOwned>(cf: &str) {
+    let mut total = 0;
+    for (key, value) in ROCKSDB.db.iterator_cf(ROCKSDB.cf(cf), IteratorMode::Start) {
+        let record: T = schema::decode(&value);
+        ROCKSDB.put(cf, &key, &schema::encode(&record));
+        total += 1;
+    }
+    info!("Re-encoded {} records within {}", total, cf);
+}
+
+// Delete all keys of the version 0 layout from the default column family
+fn remove_v0_keys() {
+    let mut total = 0;
+    for (key, _value) in ROCKSDB.db.iterator(IteratorMode::Start) {
+        if ROCKSDB.db.delete(&key).is_ok() {
+            total += 1;
+        }
+    }
+
+    if total > 0 {
+        info!("Removed {} keys of schema version 0", total);
+    }
+}
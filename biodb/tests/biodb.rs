@@ -0,0 +1,758 @@
+use biotools::db::schema;
+use rocksdb::{Options, DB};
+use rusqlite::Connection;
+use serde_json::Value;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+use testkit::fixtures::{self, TABLE_PREFIX, TRANSCRIPTS};
+use testkit::mock;
+
+/**
+ * Temporary directory holding the species directories and shared store of a
+ * test.  Removed on drop, unless KEEP_TEST_DIR is set or the test failed.
+ */
+struct TestDir {
+    dir: PathBuf,
+}
+
+impl TestDir {
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("orthograph-biodb-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Unable to create test directory");
+        Self { dir: dir }
+    }
+
+    // Input directory of the species, holding its SQLite database as left by Orthograph
+    fn species(&self, name: &str) -> String {
+        let dir = self.dir.join(name);
+        fs::create_dir_all(&dir).expect("Unable to create species directory");
+        fixtures::create_species_db(&dir.join(format!("{}.sqlite", name)), true);
+        dir.display().to_string()
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.dir.join(name).display().to_string()
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        if std::env::var("KEEP_TEST_DIR").is_ok() || std::thread::panicking() {
+            eprintln!("Keeping test directory at {}", self.dir.display());
+            return;
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn execute(args: &[&str]) -> Output {
+    match Command::new(env!("CARGO_BIN_EXE_biodb"))
+        .args(args)
+        .output()
+    {
+        Ok(r) => r,
+        Err(e) => panic!("Unable to execute biodb, error: {}", e),
+    }
+}
+
+// Run biodb, panicking with its output upon failure, and return its STDOUT
+fn biodb(args: &[&str]) -> String {
+    let output = execute(&args);
+    if !output.status.success() {
+        panic!(
+            "biodb {} exited with {}\n--- stdout ---\n{}\n--- stderr ---\n{}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn json(args: &[&str]) -> Vec<Value> {
+    match serde_json::from_str::<Value>(&biodb(&args)) {
+        Ok(Value::Array(r)) => r,
+        Ok(r) => vec![r],
+        Err(e) => panic!("Invalid JSON from biodb {}, error: {}", args.join(" "), e),
+    }
+}
+
+// Column of each TSV row, skipping the header line
+fn tsv_column(output: &str, column: usize) -> Vec<String> {
+    output
+        .lines()
+        .skip(1)
+        .map(|line| line.split("\t").nth(column).unwrap().to_string())
+        .collect()
+}
+
+fn open_rocksdb(dir: &str) -> DB {
+    let opts = Options::default();
+    let cf_names = DB::list_cf(&opts, &dir).expect("No RocksDB exists");
+    DB::open_cf(&opts, &dir, &cf_names).expect("Unable to open RocksDB")
+}
+
+#[test]
+fn upgrade_then_query() {
+    let t = TestDir::new("upgrade");
+    let input = t.species("alpha");
+    biodb(&["upgrade", "-i", &input]);
+
+    // Transcripts, by header
+    let trans = &TRANSCRIPTS[1];
+    let seq = biodb(&["seq", "-i", &input, trans.header]);
+    assert_eq!(seq.trim(), trans.nt_sequence());
+    let seq = biodb(&["seq", "-i", &input, &trans.aa_header()]);
+    assert_eq!(seq.trim(), trans.aa_sequence());
+
+    // Hmm searches of a gene, highest score first
+    let out = biodb(&["hmmsearch", "gene", "-i", &input, "EOG002", "-f", "tsv"]);
+    assert_eq!(tsv_column(&out, 0), vec!["2", "4"]);
+    let out = biodb(&["hmmsearch", "gene", "-i", &input, "EOG001", "-f", "tsv"]);
+    assert_eq!(tsv_column(&out, 0), vec!["1", "3"]);
+    assert_eq!(tsv_column(&out, 11), vec!["3", "3"]);
+
+    // Hmm searches of a transcript, by base header
+    let results = json(&["hmmsearch", "header", "-i", &input, "TRANS_2"]);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["id"], 2);
+    assert_eq!(results[0]["query"], "EOG002");
+    assert_eq!(results[0]["header"], trans.aa_header());
+    assert_eq!(results[0]["blast"].as_array().unwrap().len(), 3);
+
+    let results = json(&["hmmsearch", "get", "-i", &input, "3"]);
+    assert_eq!(results[0]["score"], TRANSCRIPTS[2].score);
+
+    biodb(&["verify", "-i", &input]);
+}
+
+// Give the nt transcript an N within its 11th codon, stored in lowercase
+fn ambiguous_transcript(input: &str, name: &str) -> String {
+    let trans = &TRANSCRIPTS[0];
+    let mut nt = trans.nt_sequence().to_lowercase();
+    nt.replace_range(31..32, "n");
+    let conn = Connection::open(format!("{}/{}.sqlite", input, name)).unwrap();
+    conn.execute(
+        &format!(
+            "UPDATE {}_ests SET sequence = ? WHERE header = ?",
+            TABLE_PREFIX
+        ),
+        [&nt, trans.header],
+    )
+    .unwrap();
+    nt
+}
+
+#[test]
+fn translates_ambiguous_codons_in_register() {
+    let t = TestDir::new("ambiguous");
+    let input = t.species("alpha");
+    let nt = ambiguous_transcript(&input, "alpha");
+    biodb(&["upgrade", "-i", &input]);
+    let header = TRANSCRIPTS[0].header;
+
+    // Every codon gives a residue, with X for the ambiguous one
+    let aa = biodb(&["seq", "-i", &input, header, "-t", "aa"]);
+    let aa = aa.trim();
+    assert_eq!(aa, mock::translate(&nt));
+    assert_eq!(aa.len(), nt.len() / 3);
+    assert_eq!(&aa[10..11], "X");
+
+    // Aa coords give the same region as either nt or aa
+    let region = biodb(&["seq", "-i", &input, header, "-t", "aa", "-c", "8-14"]);
+    assert_eq!(region.trim(), &aa[8..14]);
+    let region = biodb(&[
+        "seq",
+        "-i",
+        &input,
+        header,
+        "-t",
+        "nt",
+        "--coord-type",
+        "aa",
+        "-c",
+        "8-14",
+    ]);
+    assert_eq!(region.trim(), &nt[24..42]);
+    assert_eq!(mock::translate(region.trim()), &aa[8..14]);
+}
+
+#[test]
+fn migrates_schema_version_0() {
+    let t = TestDir::new("v0");
+    let input = t.path("alpha");
+    let trans = &TRANSCRIPTS[0];
+    let (ali_start, ali_end) = trans.ali_coords();
+
+    // Version 0 kept everything within the default column family, without SQLite
+    fs::create_dir_all(&input).unwrap();
+    {
+        let db = DB::open_default(format!("{}/rocksdb", input)).unwrap();
+        db.put(trans.aa_header().replace(" ", "_"), trans.aa_sequence())
+            .unwrap();
+        db.put(trans.header, trans.nt_sequence()).unwrap();
+        let hmmsearch = format!(
+            r#"{{"taxid":1,"gene":"{}","header":"{}","score":{},"evalue":"{}","log_evalue":-149.0,"env_start":{},"env_end":{},"ali_start":{},"ali_end":{},"hmm_start":1,"hmm_end":50,"seq_type":2,"blast":[{{"taxid":1,"target":1,"score":98.5,"evalue":"1e-30","log_evalue":-100.0,"blast_start":1,"blast_end":50,"header":"{}","seq_type":2}}]}}"#,
+            trans.gene_id,
+            trans.aa_header().replace(" ", "_"),
+            trans.score,
+            trans.evalue,
+            ali_start,
+            ali_end,
+            ali_start,
+            ali_end,
+            trans.aa_header().replace(" ", "_")
+        );
+        db.put("hmmsearch:0", hmmsearch).unwrap();
+    }
+    biodb(&["upgrade", "-i", &input]);
+
+    // Hmm search id#s are taken from the position, and targets from the re-computed digests
+    let results = json(&["hmmsearch", "get", "-i", &input, "1"]);
+    assert_eq!(results[0]["query"], trans.gene_id);
+    assert_eq!(results[0]["target"], fixtures::digest(&trans.aa_sequence()));
+    assert_eq!(results[0]["header"], trans.aa_header().replace(" ", "_"));
+    assert_eq!(results[0]["blast"][0]["target"], 1);
+
+    let out = biodb(&[
+        "hmmsearch",
+        "gene",
+        "-i",
+        &input,
+        trans.gene_id,
+        "-f",
+        "tsv",
+    ]);
+    assert_eq!(tsv_column(&out, 0), vec!["1"]);
+    let seq = biodb(&["seq", "-i", &input, trans.header]);
+    assert_eq!(seq.trim(), trans.nt_sequence());
+
+    // Keys of the version 0 layout are removed
+    let db = open_rocksdb(&format!("{}/rocksdb", input));
+    assert!(db.get("hmmsearch:0").unwrap().is_none());
+}
+
+#[test]
+fn resumes_interrupted_upgrade() {
+    let t = TestDir::new("resume");
+    let input = t.species("alpha");
+    let rocksdb_dir = format!("{}/rocksdb", input);
+    biodb(&["upgrade", "-i", &input]);
+
+    // Interrupt after the first two est sequences and hmm searches
+    {
+        let db = open_rocksdb(&rocksdb_dir);
+        let cf = |name: &str| db.cf_handle(name).unwrap();
+        db.delete_cf(cf(schema::CF_META), schema::META_SCHEMA_VERSION)
+            .unwrap();
+        db.put_cf(
+            cf(schema::CF_META),
+            schema::progress_key("orthograph_ests"),
+            schema::id_key(2),
+        )
+        .unwrap();
+        db.put_cf(
+            cf(schema::CF_META),
+            schema::progress_key("orthograph_hmmsearch"),
+            schema::id_key(2),
+        )
+        .unwrap();
+        for id in 3..=8 {
+            db.delete_cf(cf(schema::CF_ESTS), schema::id_key(id))
+                .unwrap();
+        }
+        for id in 3..=4 {
+            db.delete_cf(cf(schema::CF_HMMSEARCH), schema::id_key(id))
+                .unwrap();
+        }
+    }
+
+    // Not used until resumed
+    let output = execute(&["hmmsearch", "list", "-i", &input]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("interrupted"));
+
+    biodb(&["upgrade", "-i", &input]);
+    let out = biodb(&["hmmsearch", "list", "-i", &input, "-f", "tsv"]);
+    assert_eq!(tsv_column(&out, 0), vec!["1", "2", "3", "4"]);
+    biodb(&["verify", "-i", &input]);
+
+    // Verify finds a missing record
+    {
+        let db = open_rocksdb(&rocksdb_dir);
+        db.delete_cf(
+            db.cf_handle(schema::CF_HMMSEARCH).unwrap(),
+            schema::id_key(4),
+        )
+        .unwrap();
+    }
+    let output = execute(&["verify", "-i", &input]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn skips_orphan_blast_results() {
+    let t = TestDir::new("orphan-blast");
+    let input = t.species("alpha");
+    let conn = Connection::open(format!("{}/alpha.sqlite", input)).unwrap();
+    conn.execute(
+        &format!(
+            "INSERT INTO {}_blast (id, taxid, query, target, score, evalue, log_evalue, start, end, hmmsearch_id) SELECT max(id) + 1, 1, query, target, 50.0, '1e-10', -10, 1, 20, 99 FROM {}_blast",
+            TABLE_PREFIX, TABLE_PREFIX
+        ),
+        [],
+    )
+    .unwrap();
+
+    // Left out of the transfer and its check, so the upgrade still finishes
+    let output = execute(&["upgrade", "-i", &input]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Skipping 1 blast results whose hmm search does not exist"));
+    biodb(&["verify", "-i", &input]);
+    let out = biodb(&["hmmsearch", "list", "-i", &input, "-f", "tsv"]);
+    assert_eq!(tsv_column(&out, 0), vec!["1", "2", "3", "4"]);
+
+    let exported = t.path("export.sqlite");
+    biodb(&["export-sqlite", "-i", &input, "-o", &exported, "--verify"]);
+}
+
+#[test]
+fn store_keeps_species_apart() {
+    let t = TestDir::new("store");
+    let store = t.path("store");
+    let alpha = t.species("alpha");
+    let beta = t.species("beta");
+
+    // Beta lacks the hmm searches of EOG001
+    let conn = Connection::open(format!("{}/beta.sqlite", beta)).unwrap();
+    conn.execute_batch(&format!(
+        "DELETE FROM {p}_blast WHERE hmmsearch_id IN (SELECT id FROM {p}_hmmsearch WHERE query = 'EOG001');
+        DELETE FROM {p}_hmmsearch WHERE query = 'EOG001';",
+        p = TABLE_PREFIX
+    ))
+    .unwrap();
+    drop(conn);
+
+    biodb(&["import", "-i", &alpha, "--store", &store]);
+    biodb(&["import", "-i", &beta, "--store", &store]);
+
+    let gene = |species: &str| {
+        let out = biodb(&[
+            "hmmsearch",
+            "gene",
+            "--store",
+            &store,
+            "--species",
+            species,
+            "EOG001",
+            "-f",
+            "tsv",
+        ]);
+        tsv_column(&out, 0)
+    };
+    assert_eq!(gene("alpha"), vec!["alpha", "alpha"]);
+    assert_eq!(gene("beta"), Vec::<String>::new());
+    assert_eq!(gene("all"), vec!["alpha", "alpha"]);
+
+    let out = biodb(&[
+        "hmmsearch",
+        "list",
+        "--store",
+        &store,
+        "-f",
+        "tsv",
+        "-l",
+        "-1",
+    ]);
+    assert_eq!(
+        tsv_column(&out, 0),
+        vec!["alpha", "alpha", "alpha", "alpha", "beta", "beta"]
+    );
+    assert_eq!(tsv_column(&out, 1), vec!["1", "2", "3", "4", "2", "4"]);
+
+    // Importing again replaces the species, leaving the other as is
+    biodb(&[
+        "import",
+        "-i",
+        &alpha,
+        "--store",
+        &store,
+        "--species",
+        "beta",
+    ]);
+    assert_eq!(gene("beta"), vec!["beta", "beta"]);
+    let out = biodb(&["hmmsearch", "list", "--store", &store, "-f", "tsv"]);
+    assert_eq!(tsv_column(&out, 0).len(), 8);
+}
+
+#[test]
+fn kmer_search_finds_both_strands_and_six_frames() {
+    let t = TestDir::new("search");
+    let input = t.species("alpha");
+    biodb(&["upgrade", "-i", &input, "--kmer-index"]);
+
+    let search = |query: &str, seq_type: &str| -> Vec<(String, String, String)> {
+        let out = biodb(&[
+            "search", "-i", &input, query, "-t", seq_type, "-f", "tsv", "-l", "-1",
+        ]);
+        out.lines()
+            .skip(1)
+            .map(|line| {
+                let cols: Vec<&str> = line.split("\t").collect();
+                (
+                    cols[0].to_string(),
+                    cols[2].to_string(),
+                    cols[3].to_string(),
+                )
+            })
+            .collect()
+    };
+
+    // Nt substrings of the forward strand, and of the reverse complement
+    let trans = &TRANSCRIPTS[0];
+    let hit = (
+        trans.header.to_string(),
+        String::from("+"),
+        String::from("-"),
+    );
+    let nt = trans.nt_sequence();
+    assert!(search(&nt[20..50], "nt").contains(&hit));
+    let hit = (
+        trans.header.to_string(),
+        String::from("-"),
+        String::from("-"),
+    );
+    let revcomp = mock::reverse_complement(&nt);
+    assert!(search(&revcomp[20..50], "nt").contains(&hit));
+
+    // Aa motifs of every frame of both strands
+    for (strand, seq) in [("+", &nt), ("-", &revcomp)] {
+        for frame in 1..=3 {
+            let aa = mock::translate(&seq[frame - 1..]);
+            let start = (10..aa.len() - 8)
+                .find(|x| !aa[*x..*x + 8].contains("*"))
+                .unwrap();
+            let hit = (
+                trans.header.to_string(),
+                strand.to_string(),
+                frame.to_string(),
+            );
+            assert!(
+                search(&aa[start..start + 8], "aa").contains(&hit),
+                "No hit on strand {} in frame {}",
+                strand,
+                frame
+            );
+        }
+    }
+}
+
+#[test]
+fn search_keeps_coords_of_ambiguous_codons() {
+    let t = TestDir::new("search-ambiguous");
+    let input = t.species("alpha");
+    let nt = ambiguous_transcript(&input, "alpha");
+    let header = TRANSCRIPTS[0].header;
+    let aa = mock::translate(&nt);
+
+    // Same hits from the k-mer index as when scanning every transcript
+    for args in [
+        vec!["upgrade", "-i", &input],
+        vec!["upgrade", "-i", &input, "--kmer-index"],
+    ] {
+        biodb(&args);
+        let search = |query: &str| -> Vec<String> {
+            let out = biodb(&[
+                "search", "-i", &input, query, "-t", "aa", "-f", "tsv", "-l", "-1",
+            ]);
+            out.lines()
+                .skip(1)
+                .filter(|line| line.starts_with(&format!("{}\t", header)))
+                .map(|line| line.to_string())
+                .collect()
+        };
+
+        // Motif after the X, at its position within the translation
+        assert_eq!(
+            search(&aa[12..20]),
+            vec![format!("{}\taa\t+\t1\t12\t20", header)]
+        );
+
+        // Motif spanning the X only matches with it
+        let skipped = format!("{}{}", &aa[4..10], &aa[11..14]);
+        assert!(search(&skipped).is_empty());
+        assert_eq!(
+            search(&aa[4..14]),
+            vec![format!("{}\taa\t+\t1\t4\t14", header)]
+        );
+    }
+}
+
+#[test]
+fn keeps_compression_of_database() {
+    let t = TestDir::new("compression");
+    let input = t.species("alpha");
+    biodb(&["upgrade", "-i", &input, "-z", "lz4"]);
+
+    let db = open_rocksdb(&format!("{}/rocksdb", input));
+    let meta = db.cf_handle(schema::CF_META).unwrap();
+    assert_eq!(
+        db.get_cf(meta, schema::META_COMPRESSION).unwrap(),
+        Some(b"lz4".to_vec())
+    );
+    drop(db);
+
+    let output = execute(&["upgrade", "-i", &input, "-z", "zstd"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("was created with lz4 compression, so ignoring --compression zstd"));
+}
+
+#[test]
+fn unparsable_evalue_fails_evalue_filter() {
+    let t = TestDir::new("evalue");
+    let input = t.species("alpha");
+    let conn = Connection::open(format!("{}/alpha.sqlite", input)).unwrap();
+    conn.execute(
+        &format!(
+            "UPDATE {}_hmmsearch SET evalue = 'n/a' WHERE id = 3",
+            TABLE_PREFIX
+        ),
+        [],
+    )
+    .unwrap();
+    drop(conn);
+    biodb(&["upgrade", "-i", &input]);
+
+    let gene = |filters: &[&str]| {
+        let mut args = vec!["hmmsearch", "gene", "-i", &input, "EOG001", "-f", "tsv"];
+        args.extend_from_slice(&filters);
+        tsv_column(&biodb(&args), 0)
+    };
+    assert_eq!(gene(&[]), vec!["1", "3"]);
+    assert_eq!(gene(&["--max-evalue", "1"]), vec!["1"]);
+    assert_eq!(gene(&["--min-score", "50"]), vec!["1", "3"]);
+}
+
+#[test]
+fn export_sqlite_round_trip() {
+    let t = TestDir::new("export");
+    let input = t.species("alpha");
+    biodb(&["upgrade", "-i", &input]);
+
+    // Checksums of every table match the original
+    let exported = t.path("export.sqlite");
+    biodb(&["export-sqlite", "-i", &input, "-o", &exported, "--verify"]);
+
+    let original = Connection::open(format!("{}/alpha.sqlite", input)).unwrap();
+    let exported = Connection::open(&exported).unwrap();
+    for table in ["species_info", "ests", "hmmsearch", "blast"] {
+        let sql = format!("SELECT * FROM {}_{} ORDER BY id", TABLE_PREFIX, table);
+        assert_eq!(rows(&exported, &sql), rows(&original, &sql), "{}", table);
+    }
+
+    // Tables named with another prefix
+    let renamed = t.path("renamed.sqlite");
+    biodb(&[
+        "export-sqlite",
+        "-i",
+        &input,
+        "-o",
+        &renamed,
+        "--dbtable-prefix",
+        "myproject",
+    ]);
+    let renamed = Connection::open(&renamed).unwrap();
+    let sql = "SELECT * FROM myproject_hmmsearch ORDER BY id";
+    assert_eq!(
+        rows(&renamed, &sql),
+        rows(&original, &sql.replace("myproject", TABLE_PREFIX))
+    );
+}
+
+// All values of the rows, formatted for comparison
+fn rows(conn: &Connection, sql: &str) -> Vec<String> {
+    let mut stmt = conn.prepare(&sql).unwrap();
+    let total = stmt.column_count();
+    let mut rows = stmt.query([]).unwrap();
+    let mut formatted: Vec<String> = Vec::new();
+    while let Some(row) = rows.next().unwrap() {
+        let values: Vec<String> = (0..total)
+            .map(|x| format!("{:?}", row.get_ref(x).unwrap()))
+            .collect();
+        formatted.push(values.join("\t"));
+    }
+    formatted
+}
+
+#[test]
+fn import_names_species_after_input_directory() {
+    let t = TestDir::new("import-name");
+    let store = t.path("store");
+
+    // Input directory of Orthograph, with only its RocksDB left
+    let input = t.path("gamma.fa");
+    fs::create_dir_all(&input).unwrap();
+    fixtures::create_species_db(Path::new(&format!("{}/gamma.sqlite", input)), true);
+    biodb(&["upgrade", "-i", &input]);
+    fs::remove_file(format!("{}/gamma.sqlite", input)).unwrap();
+
+    biodb(&["import", "-i", &input, "--store", &store]);
+    let out = biodb(&[
+        "hmmsearch",
+        "gene",
+        "--store",
+        &store,
+        "--species",
+        "gamma",
+        "EOG002",
+        "-f",
+        "tsv",
+    ]);
+    assert_eq!(tsv_column(&out, 0), vec!["gamma", "gamma"]);
+}
+
+// Server started by a test, killed once done
+struct ServerProcess {
+    child: Child,
+    addr: String,
+}
+
+impl ServerProcess {
+    fn start(input: &str) -> Self {
+        // Take a free port
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let child = Command::new(env!("CARGO_BIN_EXE_biodb"))
+            .args(["serve", "-i", input, "--bind", &addr])
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Unable to start biodb serve");
+        let server = Self {
+            child: child,
+            addr: addr,
+        };
+
+        for _ in 0..100 {
+            if TcpStream::connect(&server.addr).is_ok() {
+                return server;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        panic!("biodb serve did not start listening on {}", server.addr);
+    }
+
+    // Status and body of the response
+    fn request(&self, method: &str, url: &str, body: &[u8]) -> (u16, String) {
+        let mut stream = TcpStream::connect(&self.addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            method,
+            url,
+            self.addr,
+            body.len()
+        )
+        .unwrap();
+        // The server may respond without reading all of a body that is too large
+        let _ = stream.write_all(&body);
+
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        let status = response[9..12].parse::<u16>().unwrap();
+        let body = match response.split_once("\r\n\r\n") {
+            Some((_headers, r)) => r.to_string(),
+            None => String::new(),
+        };
+        (status, body)
+    }
+
+    fn get(&self, url: &str) -> (u16, String) {
+        self.request("GET", url, &[])
+    }
+}
+
+impl Drop for ServerProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn serves_json_and_fasta() {
+    let t = TestDir::new("serve");
+    let input = t.species("alpha");
+    biodb(&["upgrade", "-i", &input]);
+    let server = ServerProcess::start(&input);
+    let trans = &TRANSCRIPTS[1];
+
+    // Routing
+    let (status, body) = server.get("/sequence/TRANS_2?type=nt");
+    assert_eq!(status, 200);
+    let results: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(results[0]["sequence"], trans.nt_sequence());
+
+    let (status, body) = server.get("/gene/EOG001?limit=1");
+    assert_eq!(status, 200);
+    let results: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(results.as_array().unwrap().len(), 1);
+    assert_eq!(results[0]["id"], 1);
+
+    let (status, body) = server.get("/hmmsearch/2");
+    assert_eq!(status, 200);
+    let results: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(results[0]["header"], trans.aa_header());
+
+    let (status, body) = server.get("/header/TRANS_2");
+    assert_eq!(status, 200);
+    let results: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(results[0]["id"], 2);
+
+    // Errors
+    let (status, body) = server.get("/nowhere");
+    assert_eq!(status, 404);
+    assert!(body.contains("No endpoint exists"));
+    assert_eq!(server.get("/hmmsearch/99").0, 404);
+    assert_eq!(server.get("/sequence/NO_SUCH_HEADER").0, 404);
+    let (status, body) = server.get("/hmmsearch/abc");
+    assert_eq!(status, 400);
+    assert!(body.contains("Invalid hmm search id#"));
+    assert_eq!(server.get("/gene/EOG001?min_score=x").0, 400);
+    assert_eq!(server.get("/export").0, 400);
+
+    // Export of a gene, and of a posted header list
+    let (status, body) = server.get("/export?gene=EOG002&type=nt");
+    assert_eq!(status, 200);
+    assert!(body.starts_with(">TRANS_2"));
+    assert!(body.contains(">TRANS_4"));
+    assert!(!body.contains("TRANS_1"));
+
+    let (status, body) = server.request("POST", "/export?type=nt", b"TRANS_2\n\nTRANS_1\n");
+    assert_eq!(status, 200);
+    assert_eq!(body.matches(">").count(), 2);
+    assert!(body.contains(&TRANSCRIPTS[0].nt_sequence()));
+    let (status, body) = server.request("POST", "/export", b"TRANS_2\tx-y\n");
+    assert_eq!(status, 400);
+    assert!(body.contains("Invalid line 1"));
+
+    // Bodies over the maximum size are not read
+    let large = vec![b'A'; 8 * 1024 * 1024 + 1];
+    assert_eq!(server.request("POST", "/export", &large).0, 413);
+}
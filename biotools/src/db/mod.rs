@@ -1,2 +1,4 @@
 pub mod models;
+pub mod schema;
 pub mod sqlite;
+pub mod storage;
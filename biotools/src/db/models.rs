@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 pub struct BlastResult {
     pub target: u32,
     pub score: f32,
@@ -5,3 +7,47 @@ pub struct BlastResult {
     pub res_start: u16,
     pub res_end: u16,
 }
+
+// Rows of orthograph_ests, orthograph_hmmsearch and orthograph_blast, as is
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Est {
+    pub id: u32,
+    pub digest: String,
+    pub taxid: u32,
+    pub seq_type: u8,
+    pub date: Option<i64>,
+    pub header: String,
+    #[serde(with = "crate::db::schema::packed")]
+    pub sequence: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HmmSearch {
+    pub id: u32,
+    pub taxid: u32,
+    pub query: String,
+    pub target: String,
+    pub score: f64,
+    pub evalue: String,
+    pub log_evalue: f64,
+    pub env_start: u32,
+    pub env_end: u32,
+    pub ali_start: u32,
+    pub ali_end: u32,
+    pub hmm_start: u32,
+    pub hmm_end: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Blast {
+    pub id: u32,
+    pub taxid: u32,
+    pub query: String,
+    pub target: u32,
+    pub score: f64,
+    pub evalue: String,
+    pub log_evalue: f64,
+    pub start: u32,
+    pub end: u32,
+    pub hmmsearch_id: u32,
+}
@@ -0,0 +1,367 @@
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryInto;
+
+/**
+ * Layout of the RocksDB.  Each record type is kept within its own column
+ * family, keyed by its SQLite id# in big-endian so iteration follows numeric
+ * order.  Blast results are keyed by hmm search id# followed by their own id#,
+ * so all results of a hmm search sit next to each other.
+ *
+ * The index column families have empty values.  Header index keys are the
+ * base header, a null byte, and the hmm search id#.  Gene score index keys are
+ * the gene, a null byte, the score encoded so iteration returns the highest
+ * score first, and the hmm search id#.
+ *
+ * Version 0 is the original layout, with everything in the default column
+ * family under "hmmsearch:<n>" and header keys.  Version 1 stored values as
+ * JSON, version 2 encodes them with the binary codec below, and version 3
+ * adds the indexes.  upgrade-db migrates all of them.
+ */
+pub const SCHEMA_VERSION: u32 = 3;
+
+pub const CF_META: &str = "meta";
+pub const CF_ESTS: &str = "ests";
+pub const CF_EST_HEADERS: &str = "est_headers";
+pub const CF_EST_DIGESTS: &str = "est_digests";
+pub const CF_HMMSEARCH: &str = "hmmsearch";
+pub const CF_BLAST: &str = "blast";
+pub const CF_HEADER_INDEX: &str = "header_index";
+pub const CF_GENE_SCORE_INDEX: &str = "gene_score_index";
+
+pub const COLUMN_FAMILIES: [&str; 8] = [
+    CF_META,
+    CF_ESTS,
+    CF_EST_HEADERS,
+    CF_EST_DIGESTS,
+    CF_HMMSEARCH,
+    CF_BLAST,
+    CF_HEADER_INDEX,
+    CF_GENE_SCORE_INDEX,
+];
+
+pub const INDEX_COLUMN_FAMILIES: [&str; 2] = [CF_HEADER_INDEX, CF_GENE_SCORE_INDEX];
+
+/**
+ * The k-mer index is only built when asked for, so its column family may not
+ * exist and readers must not require it.  Keys are the sequence type ("n" or
+ * "a"), the k-mer, and the est id#, with empty values.  Nt k-mers are of the
+ * forward strand only, as a query is also looked up by its reverse complement,
+ * and aa k-mers are of the translation in all six frames.
+ */
+pub const CF_KMER_INDEX: &str = "kmer_index";
+pub const OPTIONAL_COLUMN_FAMILIES: [&str; 1] = [CF_KMER_INDEX];
+
+pub const NT_KMER_SIZE: usize = 12;
+pub const AA_KMER_SIZE: usize = 5;
+
+// Keys within the meta column family
+pub const META_SCHEMA_VERSION: &str = "schema_version";
+pub const META_UPGRADE_PROGRESS: &str = "upgrade_progress:";
+pub const META_KMER_INDEX: &str = "kmer_index";
+
+/// Compression the database was created with, which later opens keep to.
+pub const META_COMPRESSION: &str = "compression";
+
+/**
+ * A shared store holds many species within one RocksDB.  Every key of a species
+ * is prefixed by its number within the store, so a species reads the same as
+ * its own database would.  The store keeps its own schema version, and the
+ * number of each species under "store_species:<name>", without any prefix.
+ */
+pub const META_STORE: &str = "store";
+
+pub fn store_prefix(num: u32) -> [u8; 4] {
+    num.to_be_bytes()
+}
+
+pub fn store_species_key(name: &str) -> Vec<u8> {
+    format!("store_species:{}", name).into_bytes()
+}
+
+/// Last id# of the SQLite table transferred, kept only while upgrade-db is in progress.
+pub fn progress_key(table: &str) -> Vec<u8> {
+    format!("{}{}", META_UPGRADE_PROGRESS, table).into_bytes()
+}
+
+/// Id# of the species within orthograph_species_info, keyed by its name.
+pub fn species_key(name: &str) -> Vec<u8> {
+    format!("species:{}", name).into_bytes()
+}
+
+pub fn id_key(id: u32) -> [u8; 4] {
+    id.to_be_bytes()
+}
+
+pub fn blast_key(hmmsearch_id: u32, blast_id: u32) -> [u8; 8] {
+    let mut key = [0u8; 8];
+    key[..4].copy_from_slice(&hmmsearch_id.to_be_bytes());
+    key[4..].copy_from_slice(&blast_id.to_be_bytes());
+    key
+}
+
+pub fn decode_id(key: &[u8]) -> u32 {
+    u32::from_be_bytes(key[..4].try_into().expect("Invalid id key length"))
+}
+
+/// Headers are keyed with spaces replaced by underscores, same as version 0.
+pub fn header_key(header: &str) -> Vec<u8> {
+    header.trim().replace(" ", "_").into_bytes()
+}
+
+/// Header without any frame info, eg. "NODE_1 [revcomp]:[translate(1)]" becomes "NODE_1".
+pub fn base_header(header: &str) -> String {
+    let header = header.trim().replace(" ", "_");
+    match header.find("_[") {
+        Some(pos) => header[..pos].to_string(),
+        None => header,
+    }
+}
+
+// Gene or base header followed by a null byte, the start of all its index keys
+pub fn index_prefix(name: &str) -> Vec<u8> {
+    let mut prefix = name.as_bytes().to_vec();
+    prefix.push(0);
+    prefix
+}
+
+pub fn index_key(name: &str, hmmsearch_id: u32) -> Vec<u8> {
+    let mut key = index_prefix(&name);
+    key.extend_from_slice(&id_key(hmmsearch_id));
+    key
+}
+
+// Sequence type and k-mer, the start of all its k-mer index keys
+pub fn kmer_prefix(seq_type: &str, kmer: &[u8]) -> Vec<u8> {
+    let mut prefix = match seq_type {
+        "aa" => b"a".to_vec(),
+        _ => b"n".to_vec(),
+    };
+    prefix.extend_from_slice(&kmer);
+    prefix
+}
+
+pub fn kmer_key(seq_type: &str, kmer: &[u8], est_id: u32) -> Vec<u8> {
+    let mut key = kmer_prefix(&seq_type, &kmer);
+    key.extend_from_slice(&id_key(est_id));
+    key
+}
+
+/**
+ * Score keys order by score descending.  The sign bit is flipped for positive
+ * floats and all bits for negative ones, which orders them ascending as bytes,
+ * then the whole is inverted.
+ */
+pub fn score_index_key(name: &str, score: f64, hmmsearch_id: u32) -> Vec<u8> {
+    let bits = score.to_bits();
+    let ordered = match bits >> 63 {
+        0 => bits ^ (1 << 63),
+        _ => !bits,
+    };
+
+    let mut key = index_prefix(&name);
+    key.extend_from_slice(&(!ordered).to_be_bytes());
+    key.extend_from_slice(&id_key(hmmsearch_id));
+    key
+}
+
+// The hmm search id# at the end of an index key
+pub fn decode_index_id(key: &[u8]) -> u32 {
+    decode_id(&key[key.len() - 4..])
+}
+
+/**
+ * Values are a single codec version byte, followed by the record encoded with
+ * bincode using variable length integers.  Version 1 databases hold plain JSON
+ * values, which always begin with "{" and are still decoded so upgrade-db is
+ * able to re-encode them.
+ */
+pub const CODEC_VERSION: u8 = 1;
+
+pub fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+    let mut bytes = vec![CODEC_VERSION];
+    bincode::DefaultOptions::new()
+        .serialize_into(&mut bytes, &value)
+        .expect("Unable to encode record");
+    bytes
+}
+
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> T {
+    let res = match bytes.first() {
+        Some(&CODEC_VERSION) => bincode::DefaultOptions::new()
+            .deserialize(&bytes[1..])
+            .map_err(|e| e.to_string()),
+        Some(b'{') => serde_json::from_slice(&bytes).map_err(|e| e.to_string()),
+        _ => Err(String::from("unknown codec version")),
+    };
+
+    match res {
+        Ok(r) => r,
+        Err(e) => panic!("Unable to decode record, error: {}", e),
+    }
+}
+
+/**
+ * Serde helpers for nucleotide sequences, used via #[serde(with)].  Sequences
+ * consisting of only A, C, G and T are packed at 2 bits per base, anything
+ * else (amino acids, N, lowercase) is kept as is.  Human readable formats such
+ * as JSON always get the plain string.
+ */
+pub mod packed {
+    use super::*;
+
+    const BASES: [u8; 4] = [b'A', b'C', b'G', b'T'];
+
+    #[derive(Serialize, Deserialize)]
+    enum PackedSequence {
+        Plain(String),
+        Packed(u32, Vec<u8>),
+    }
+
+    pub fn serialize<S: Serializer>(sequence: &String, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return serializer.serialize_str(&sequence);
+        }
+
+        match pack(&sequence) {
+            Some(bytes) => PackedSequence::Packed(sequence.len() as u32, bytes),
+            None => PackedSequence::Plain(sequence.to_string()),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+        if deserializer.is_human_readable() {
+            return String::deserialize(deserializer);
+        }
+
+        match PackedSequence::deserialize(deserializer)? {
+            PackedSequence::Plain(sequence) => Ok(sequence),
+            PackedSequence::Packed(len, bytes) => Ok(unpack(len as usize, &bytes)),
+        }
+    }
+
+    pub fn pack(sequence: &str) -> Option<Vec<u8>> {
+        let mut bytes = vec![0u8; (sequence.len() + 3) / 4];
+        for (x, base) in sequence.bytes().enumerate() {
+            let code = BASES.iter().position(|b| *b == base)? as u8;
+            bytes[x / 4] |= code << ((x % 4) * 2);
+        }
+        Some(bytes)
+    }
+
+    pub fn unpack(len: usize, bytes: &[u8]) -> String {
+        (0..len)
+            .map(|x| BASES[((bytes[x / 4] >> ((x % 4) * 2)) & 3) as usize] as char)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_keys_order_numerically() {
+        let ids: Vec<u32> = vec![1, 2, 255, 256, 65536, u32::MAX];
+        let mut keys: Vec<[u8; 4]> = ids.iter().map(|id| id_key(*id)).collect();
+        keys.sort();
+        let decoded: Vec<u32> = keys.iter().map(|k| decode_id(k)).collect();
+        assert_eq!(decoded, ids);
+    }
+
+    #[test]
+    fn blast_keys_group_by_hmmsearch() {
+        let mut keys = vec![
+            blast_key(2, 1),
+            blast_key(1, 300),
+            blast_key(256, 0),
+            blast_key(1, 2),
+        ];
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                blast_key(1, 2),
+                blast_key(1, 300),
+                blast_key(2, 1),
+                blast_key(256, 0)
+            ]
+        );
+        assert!(keys[..2].iter().all(|k| k.starts_with(&id_key(1))));
+    }
+
+    #[test]
+    fn score_index_orders_highest_first() {
+        let scores: Vec<f64> = vec![1000.5, 150.2, 2.0, 0.0, -0.5, -3.0, -120.0];
+        let mut keys: Vec<Vec<u8>> = scores
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(x, score)| score_index_key("EOG001", *score, x as u32))
+            .collect();
+        keys.sort();
+        let ids: Vec<u32> = keys.iter().map(|k| decode_index_id(k)).collect();
+        assert_eq!(ids, vec![6, 5, 4, 3, 2, 1, 0]);
+        assert!(keys.iter().all(|k| k.starts_with(&index_prefix("EOG001"))));
+    }
+
+    #[test]
+    fn index_prefix_does_not_match_longer_names() {
+        let key = index_key("EOG0010", 7);
+        assert!(!key.starts_with(&index_prefix("EOG001")));
+        assert!(key.starts_with(&index_prefix("EOG0010")));
+        assert_eq!(decode_index_id(&key), 7);
+        assert_eq!(base_header("NODE 1 [revcomp]:[translate(1)]"), "NODE_1");
+    }
+
+    fn est(sequence: &str) -> crate::db::models::Est {
+        crate::db::models::Est {
+            id: 7,
+            digest: String::from("8b999733ccedafc171dae12464f2f961"),
+            taxid: 1,
+            seq_type: 1,
+            date: Some(1650000000),
+            header: String::from("NODE_1 [revcomp]"),
+            sequence: sequence.to_string(),
+        }
+    }
+
+    #[test]
+    fn codec_round_trip() {
+        for sequence in ["ACGTTGCAAC", "ACGTN", "acgt", "MSTNPKPQRK", ""] {
+            let bytes = encode(&est(sequence));
+            assert_eq!(bytes[0], CODEC_VERSION);
+            let decoded: crate::db::models::Est = decode(&bytes);
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", est(sequence)));
+        }
+
+        // Version 1 values of plain JSON
+        let json = serde_json::to_vec(&est("ACGT")).unwrap();
+        let decoded: crate::db::models::Est = decode(&json);
+        assert_eq!(decoded.sequence, "ACGT");
+    }
+
+    #[test]
+    fn packs_sequences_at_2_bits_per_base() {
+        for sequence in [
+            "",
+            "A",
+            "ACG",
+            "ACGT",
+            "TTTTGGGGCCCCAAAAT",
+            "GATTACAGATTACA",
+        ] {
+            let bytes = packed::pack(sequence).unwrap();
+            assert_eq!(bytes.len(), (sequence.len() + 3) / 4);
+            assert_eq!(packed::unpack(sequence.len(), &bytes), sequence);
+        }
+        assert!(packed::pack("ACGN").is_none());
+        assert!(packed::pack("acgt").is_none());
+
+        // Packed sequences encode smaller than plain ones
+        let long = "ACGT".repeat(100);
+        assert!(encode(&est(&long)).len() < encode(&est(&long.replace("T", "N"))).len() / 2);
+    }
+}
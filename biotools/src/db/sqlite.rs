@@ -1,7 +1,7 @@
 use crate::db::models::BlastResult;
+use crate::formats::hmmer::DomainHit;
 use crate::CONFIG;
 use lazy_static::lazy_static;
-use log::debug;
 use rusqlite::{Connection, Error};
 use std::collections::HashMap;
 use std::path::Path;
@@ -66,6 +66,22 @@ impl Sqlite {
         Self { conn: conn }
     }
 
+    /**
+     * Only the input database of the ortholog set, attached to an in-memory
+     * database.  Used with the rocksdb backend, where the species database may
+     * no longer exist.
+     */
+    pub fn new_set_only() -> Self {
+        let conn = Connection::open_in_memory().expect("Unable to open in-memory SQLite database.");
+
+        // Attach input database
+        let sql = format!("ATTACH '{}' AS input", CONFIG.db.sqlite_file);
+        conn.execute(&sql, [])
+            .expect("Unable to attach input SQLite database");
+
+        Self { conn: conn }
+    }
+
     pub fn get_species_id(&self) -> Result<u32, rusqlite::Error> {
         // Prepare
         let sql = format!("SELECT id,name FROM {} WHERE name = ?", *TBL_SPECIES_INFO);
@@ -181,10 +197,43 @@ impl Sqlite {
         Ok(aaseq)
     }
 
-    pub fn get_hmm_blast_results(&self, hmmsearch_id: u32) -> Result<Vec<BlastResult>, Error> {
+    pub fn get_sequence_pairs_in_set(
+        &self,
+        set_id: &u32,
+    ) -> Result<HashMap<String, (u32, u32)>, Error> {
+        // Format sql
+        let sql = format!(
+            "SELECT l.ortholog_gene_id, p.aa_seq, p.nt_seq 
+            FROM {} l, {} p 
+            WHERE l.sequence_pair = p.id AND l.setid = ? 
+            ORDER BY p.id",
+            *TBL_LOGS, *TBL_SEQUENCE_PAIRS
+        );
+
+        // Prepare
+        let mut stmt = match self.conn.prepare(&sql) {
+            Ok(res) => res,
+            Err(error) => return Err(error),
+        };
+
+        // Execute, keeping the first sequence pair of each gene
+        let mut rows = stmt.query([&set_id])?;
+        let mut pairs: HashMap<String, (u32, u32)> = HashMap::new();
+        while let Some(row) = rows.next()? {
+            pairs
+                .entry(row.get(0)?)
+                .or_insert((row.get(1)?, row.get(2)?));
+        }
+
+        // Return
+        Ok(pairs)
+    }
+
+    pub fn get_all_blast_results(&self) -> Result<HashMap<u32, Vec<BlastResult>>, Error> {
         // Set sql
         let sql = format!(
             "SELECT DISTINCT 
+            b.hmmsearch_id,
             b.target,
             b.score,
             b.evalue,
@@ -194,9 +243,8 @@ impl Sqlite {
             WHERE  
             s.id = b.hmmsearch_id AND 
             e.digest = s.target AND 
-            s.target IS NOT NULL AND 
-            b.hmmsearch_id = ? 
-            ORDER BY b.score DESC",
+            s.target IS NOT NULL 
+            ORDER BY b.hmmsearch_id, b.score DESC",
             *TBL_BLAST, *TBL_HMMSEARCH, *TBL_ESTS
         );
 
@@ -207,63 +255,49 @@ impl Sqlite {
         };
 
         // Execute
-        let rows = stmt.query_map([&hmmsearch_id], |row| {
-            Ok(BlastResult {
-                target: row.get(0)?,
-                score: row.get(1)?,
-                evalue: row.get(2)?,
-                res_start: row.get(3)?,
-                res_end: row.get(4)?,
-            })
-        })?;
-
-        let res: Vec<BlastResult> = rows.into_iter().map(|r| r.unwrap()).collect();
+        let mut rows = stmt.query([])?;
+
+        // Group by hmm search
+        let mut res: HashMap<u32, Vec<BlastResult>> = HashMap::new();
+        while let Some(row) = rows.next()? {
+            res.entry(row.get(0)?)
+                .or_insert(Vec::new())
+                .push(BlastResult {
+                    target: row.get(1)?,
+                    score: row.get(2)?,
+                    evalue: row.get(3)?,
+                    res_start: row.get(4)?,
+                    res_end: row.get(5)?,
+                });
+        }
 
         // Return
         Ok(res)
     }
 
-    pub fn get_ref_taxon_name(&self, aaseq_id: &u32) -> Result<String, Error> {
+    pub fn get_ref_taxon_names(&self) -> Result<HashMap<u32, String>, Error> {
         // Set sql
         let sql = format!(
-            "SELECT t.name FROM {} t, {} a WHERE t.id = a.taxid AND a.id = ?",
+            "SELECT a.id, t.name FROM {} t, {} a WHERE t.id = a.taxid",
             *TBL_TAXA, *TBL_AASEQS
         );
 
         // Prepare
         let mut stmt = match self.conn.prepare(&sql) {
             Ok(res) => res,
-            Err(e) => panic!(
-                "Unable to prepare sql statement to retrieve reference taxon name, error: {}",
-                e
-            ),
+            Err(error) => return Err(error),
         };
 
         // Execute
-        let mut rows = match stmt.query([&aaseq_id]) {
-            Ok(res) => res,
-            Err(e) => panic!(
-                "Unable to retrive ref taxon name of aaseq id# {}, error: {}",
-                &aaseq_id, e
-            ),
-        };
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
 
-        // Get row
-        let row = match rows.next() {
-            Ok(res) => res.unwrap(),
-            Err(e) => panic!(
-                "Unable to retrive ref taxon name of aaseq id# {}, error: {}",
-                &aaseq_id, e
-            ),
-        };
-
-        // Return
-        let name = row.get(0)?;
-        debug!(
-            "Obtained ref taxon name {} for aaseq id# {}",
-            &name, &aaseq_id
-        );
-        Ok(name)
+        // Collect, and return
+        let mut names: HashMap<u32, String> = HashMap::new();
+        for r in rows {
+            let (id, name) = r?;
+            names.insert(id, name);
+        }
+        Ok(names)
     }
 
     pub fn get_blast_count(&self, search_id: &u32) -> u16 {
@@ -287,4 +321,39 @@ impl Sqlite {
         let row = rows.next().unwrap().unwrap();
         row.get(0).unwrap()
     }
+
+    pub fn insert_hmmsearch(&self, taxid: &u32, hit: &DomainHit) {
+        // Prepare sql
+        let sql = format!("INSERT INTO {} (taxid, query, target, score, evalue, log_evalue, env_start, env_end, ali_start, ali_end, hmm_start, hmm_end) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", *TBL_HMMSEARCH);
+        let mut stmt = match self.conn.prepare_cached(&sql) {
+            Ok(res) => res,
+            Err(e) => panic!(
+                "Unable to prepare SQL statement to insert into hmmsearch table, error: {}",
+                e
+            ),
+        };
+
+        // Execute
+        let log_evalue = crate::log_evalue(hit.evalue.parse::<f64>().unwrap());
+        match stmt.execute(rusqlite::params![
+            taxid,
+            &hit.query_name,
+            &hit.target_name,
+            &hit.score,
+            &hit.evalue,
+            &log_evalue,
+            &hit.env_start,
+            &hit.env_end,
+            &hit.ali_start,
+            &hit.ali_end,
+            &hit.hmm_start,
+            &hit.hmm_end,
+        ]) {
+            Ok(_r) => {}
+            Err(e) => panic!(
+                "Unable to insert hmmsearch of gene {} against {}, error: {}",
+                hit.query_name, hit.target_name, e
+            ),
+        };
+    }
 }
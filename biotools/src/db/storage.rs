@@ -0,0 +1,310 @@
+use crate::db::models::{Blast, BlastResult, Est, HmmSearch};
+use crate::db::schema;
+use crate::db::sqlite::{Sqlite, TBL_ESTS, TBL_HMMSEARCH};
+use crate::CONFIG;
+use log::{info, warn};
+use rocksdb::{Direction, IteratorMode, DB};
+use std::collections::HashMap;
+use std::path::Path;
+
+/**
+ * Read access to the hmm searches, blast results and est sequences of the
+ * species, as either the SQLite database generated by the analyzer or the
+ * RocksDB converted by biodb.  Which is used depends on the database-backend
+ * setting.
+ */
+pub trait Storage {
+    fn get_species_id(&self) -> u32;
+
+    /// Hmm searches of the species along with their target est, that meet the
+    /// score threshold and minimum transcript length, highest score first.
+    fn get_hmmsearches(&self, taxid: u32) -> Vec<(HmmSearch, Est)>;
+
+    /// Blast results of all hmm searches keyed by hmm search id#, highest score first.
+    fn get_all_blast_results(&self) -> HashMap<u32, Vec<BlastResult>>;
+
+    fn get_est_sequence(&self, header: &str) -> Option<String>;
+}
+
+pub fn open() -> Box<dyn Storage> {
+    match CONFIG.db.backend.as_str() {
+        "rocksdb" => Box::new(RocksStorage::new()),
+        _ => Box::new(Sqlite::new()),
+    }
+}
+
+impl Storage for Sqlite {
+    fn get_species_id(&self) -> u32 {
+        match Sqlite::get_species_id(self) {
+            Ok(r) => r,
+            Err(e) => panic!(
+                "Unable to determine id# for species {}, error: {}",
+                &CONFIG.report.species_name, e
+            ),
+        }
+    }
+
+    fn get_hmmsearches(&self, taxid: u32) -> Vec<(HmmSearch, Est)> {
+        let sql = format!(
+            "SELECT
+            s.id,s.taxid,s.query,s.target,s.score,s.evalue,s.log_evalue,s.env_start,s.env_end,s.ali_start,s.ali_end,s.hmm_start,s.hmm_end,
+            e.id,e.digest,e.taxid,e.type,e.date,e.header,e.sequence
+            FROM {} s, {} e
+            WHERE
+                s.target = e.digest AND
+                e.digest IS NOT NULL AND
+                s.score >= ? AND
+                s.taxid = ? AND
+                (s.ali_end - s.ali_start) + 1 >= ?
+            GROUP BY s.id ORDER BY s.score DESC, s.id",
+            *TBL_HMMSEARCH, *TBL_ESTS
+        );
+
+        // Prepare
+        let mut stmt = match self.conn.prepare(&sql) {
+            Ok(r) => r,
+            Err(e) => panic!(
+                "Unable to prepare SQL statement to retrieve hmm searches, error: {}",
+                e
+            ),
+        };
+
+        // Execute
+        let rows = stmt.query_map(
+            rusqlite::params![
+                CONFIG.search.hmmsearch_threshold,
+                taxid,
+                CONFIG.search.min_transcript_length
+            ],
+            |row| {
+                Ok((
+                    HmmSearch {
+                        id: row.get(0)?,
+                        taxid: row.get(1)?,
+                        query: row.get(2)?,
+                        target: row.get(3)?,
+                        score: row.get(4)?,
+                        evalue: row.get(5)?,
+                        log_evalue: row.get(6)?,
+                        env_start: row.get(7)?,
+                        env_end: row.get(8)?,
+                        ali_start: row.get(9)?,
+                        ali_end: row.get(10)?,
+                        hmm_start: row.get(11)?,
+                        hmm_end: row.get(12)?,
+                    },
+                    Est {
+                        id: row.get(13)?,
+                        digest: row.get(14)?,
+                        taxid: row.get(15)?,
+                        seq_type: row.get(16)?,
+                        date: row.get(17)?,
+                        header: row.get(18)?,
+                        sequence: row.get(19)?,
+                    },
+                ))
+            },
+        );
+
+        match rows {
+            Ok(r) => r.map(|r| r.unwrap()).collect(),
+            Err(e) => panic!("Unable to retrieve hmm searches, error: {}", e),
+        }
+    }
+
+    fn get_all_blast_results(&self) -> HashMap<u32, Vec<BlastResult>> {
+        match Sqlite::get_all_blast_results(self) {
+            Ok(r) => r,
+            Err(e) => panic!("Unable to obtain blast results, error: {}", e),
+        }
+    }
+
+    fn get_est_sequence(&self, header: &str) -> Option<String> {
+        let sql = format!("SELECT sequence FROM {} WHERE header = ?", *TBL_ESTS);
+        let mut stmt = match self.conn.prepare_cached(&sql) {
+            Ok(r) => r,
+            Err(e) => panic!(
+                "Unable to prepare SQL statement to retrieve est sequence, error: {}",
+                e
+            ),
+        };
+
+        let mut rows = match stmt.query([&header]) {
+            Ok(r) => r,
+            Err(e) => panic!(
+                "Unable to retrieve est sequence of header {}, error: {}",
+                header, e
+            ),
+        };
+
+        match rows.next() {
+            Ok(Some(row)) => row.get(0).ok(),
+            Ok(None) => None,
+            Err(e) => panic!(
+                "Unable to retrieve est sequence of header {}, error: {}",
+                header, e
+            ),
+        }
+    }
+}
+
+pub struct RocksStorage {
+    pub db: DB,
+}
+
+impl RocksStorage {
+    pub fn new() -> Self {
+        // Check directory exists
+        if !Path::new(&CONFIG.db.rocksdb_dir).exists() {
+            panic!(
+                "RocksDB does not exist at {}, please run 'biodb upgrade' first.",
+                CONFIG.db.rocksdb_dir
+            );
+        }
+
+        // Open read-only
+        let opts = rocksdb::Options::default();
+        let db = match DB::open_cf_for_read_only(
+            &opts,
+            &CONFIG.db.rocksdb_dir,
+            schema::COLUMN_FAMILIES,
+            false,
+        ) {
+            Ok(r) => r,
+            Err(e) => panic!(
+                "Unable to open RocksDB at {}, error: {}.  If it was created by an earlier version of biodb, please run 'biodb upgrade' again.",
+                CONFIG.db.rocksdb_dir, e
+            ),
+        };
+
+        // Check schema version
+        let storage = Self { db: db };
+        let version = storage
+            .get(schema::CF_META, schema::META_SCHEMA_VERSION.as_bytes())
+            .map(|r| schema::decode_id(&r));
+        if version != Some(schema::SCHEMA_VERSION) {
+            panic!(
+                "RocksDB at {} is not at schema version {}, please run 'biodb upgrade' first.",
+                CONFIG.db.rocksdb_dir,
+                schema::SCHEMA_VERSION
+            );
+        }
+
+        // Check not a shared store, whose keys are prefixed by the number of each species
+        let store_prefix = schema::store_species_key("");
+        if storage
+            .iter_prefix(schema::CF_META, &store_prefix)
+            .next()
+            .is_some()
+        {
+            panic!(
+                "RocksDB at {} is a shared store of many species, which the reporter can not read.  Please set rocksdb-directory to the RocksDB of the species itself, as created by 'biodb upgrade' within its input directory.",
+                CONFIG.db.rocksdb_dir
+            );
+        }
+        info!("Opened RocksDB at {}", CONFIG.db.rocksdb_dir);
+
+        storage
+    }
+
+    fn get(&self, cf: &str, key: &[u8]) -> Option<Vec<u8>> {
+        let handle = match self.db.cf_handle(cf) {
+            Some(r) => r,
+            None => panic!("Column family {} does not exist within the RocksDB", cf),
+        };
+
+        match self.db.get_cf(handle, key) {
+            Ok(r) => r,
+            Err(e) => panic!(
+                "Received database error when trying to retrieve record from {}, error: {}",
+                cf, e
+            ),
+        }
+    }
+
+    fn get_est_by(&self, cf: &str, key: &[u8]) -> Option<Est> {
+        let id = self.get(cf, key)?;
+        self.get(schema::CF_ESTS, &id)
+            .map(|r| schema::decode::<Est>(&r))
+    }
+
+    // Records of the column family, starting at the prefix and for as long as keys match it
+    fn iter_prefix<'a>(
+        &'a self,
+        cf: &str,
+        prefix: &'a [u8],
+    ) -> impl Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a {
+        let handle = self.db.cf_handle(cf).unwrap();
+        self.db
+            .iterator_cf(handle, IteratorMode::From(prefix, Direction::Forward))
+            .take_while(move |(k, _v)| k.starts_with(prefix))
+    }
+}
+
+impl Storage for RocksStorage {
+    fn get_species_id(&self) -> u32 {
+        let key = schema::species_key(&CONFIG.report.species_name);
+        if let Some(r) = self.get(schema::CF_META, &key) {
+            return schema::decode_id(&r);
+        }
+
+        // Fall back to the taxon of the hmm searches, as a species database holds only one
+        warn!(
+            "No species named {} within the RocksDB, using the taxon id# of its hmm searches instead.",
+            CONFIG.report.species_name
+        );
+        match self.iter_prefix(schema::CF_HMMSEARCH, &[]).next() {
+            Some((_k, v)) => schema::decode::<HmmSearch>(&v).taxid,
+            None => panic!(
+                "Unable to determine id# for species {}, the RocksDB contains no hmm searches.",
+                CONFIG.report.species_name
+            ),
+        }
+    }
+
+    fn get_hmmsearches(&self, taxid: u32) -> Vec<(HmmSearch, Est)> {
+        let mut res: Vec<(HmmSearch, Est)> = self
+            .iter_prefix(schema::CF_HMMSEARCH, &[])
+            .map(|(_k, v)| schema::decode::<HmmSearch>(&v))
+            .filter(|h| {
+                h.taxid == taxid
+                    && h.score >= CONFIG.search.hmmsearch_threshold as f64
+                    && h.ali_end + 1 >= h.ali_start + CONFIG.search.min_transcript_length as u32
+            })
+            .filter_map(|h| {
+                let est = self.get_est_by(schema::CF_EST_DIGESTS, h.target.as_bytes())?;
+                Some((h, est))
+            })
+            .collect();
+
+        res.sort_by(|a, b| b.0.score.partial_cmp(&a.0.score).unwrap());
+        res
+    }
+
+    fn get_all_blast_results(&self) -> HashMap<u32, Vec<BlastResult>> {
+        let mut res: HashMap<u32, Vec<BlastResult>> = HashMap::new();
+        for (_k, v) in self.iter_prefix(schema::CF_BLAST, &[]) {
+            let b = schema::decode::<Blast>(&v);
+            res.entry(b.hmmsearch_id)
+                .or_insert(Vec::new())
+                .push(BlastResult {
+                    target: b.target,
+                    score: b.score as f32,
+                    evalue: b.evalue,
+                    res_start: b.start as u16,
+                    res_end: b.end as u16,
+                });
+        }
+
+        // Keys are in order of id#, so ties keep the same order as the hmm search's blasts
+        for blasts in res.values_mut() {
+            blasts.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+        }
+        res
+    }
+
+    fn get_est_sequence(&self, header: &str) -> Option<String> {
+        self.get_est_by(schema::CF_EST_HEADERS, &schema::header_key(&header))
+            .map(|est| est.sequence)
+    }
+}
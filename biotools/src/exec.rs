@@ -0,0 +1,351 @@
+use crate::CONFIG;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+lazy_static! {
+    // Failed items file of the running program, set by init_failure_log()
+    static ref FAILURE_LOG: Mutex<String> = Mutex::new(String::new());
+}
+static TOTAL_FAILURES: AtomicUsize = AtomicUsize::new(0);
+
+// Type of the resource argument to setrlimit, which differs on glibc
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type RlimitResource = libc::c_int;
+
+/// An external program along with the limits it is run under.
+pub struct Tool {
+    pub name: String,
+    pub program: String,
+    pub timeout: u64,
+    pub retries: u8,
+    pub memory_limit: u64,
+    pub cpu_limit: u64,
+}
+
+#[derive(Debug)]
+pub struct ExecError {
+    pub tool: String,
+    pub item_id: String,
+    pub attempts: u8,
+    pub reason: String,
+    pub stderr: String,
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} failed on item {} after {} attempt(s), {}",
+            self.tool, self.item_id, self.attempts, self.reason
+        )
+    }
+}
+
+impl Tool {
+    pub fn new(name: &str, program: &str, timeout: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            program: program.to_string(),
+            timeout: timeout,
+            retries: CONFIG.limits.retries,
+            memory_limit: CONFIG.limits.memory_limit,
+            cpu_limit: CONFIG.limits.cpu_limit,
+        }
+    }
+
+    pub fn exonerate() -> Self {
+        Tool::new(
+            "exonerate",
+            &CONFIG.programs.exonerate,
+            CONFIG.limits.exonerate_timeout,
+        )
+    }
+
+    pub fn translate() -> Self {
+        Tool::new(
+            "translate",
+            &CONFIG.programs.translate,
+            CONFIG.limits.translate_timeout,
+        )
+    }
+
+    pub fn blast() -> Self {
+        Tool::new("blast", &CONFIG.programs.blast, CONFIG.limits.blast_timeout)
+    }
+
+    /**
+     * Run the program against the given item (hit id, hmm search id, etc.),
+     * retrying as configured.  Once all attempts fail, the failure is recorded
+     * within the failed items file of the output directory.
+     */
+    pub fn run(&self, args: &[&str], item_id: &str) -> Result<Output, ExecError> {
+        let mut attempt: u8 = 0;
+        loop {
+            attempt += 1;
+            let (reason, stderr) = match self.run_once(&args) {
+                Ok(output) => {
+                    if output.status.success() {
+                        return Ok(output);
+                    }
+                    (
+                        format!("exited with {}", output.status),
+                        String::from_utf8_lossy(&output.stderr).to_string(),
+                    )
+                }
+                Err(r) => r,
+            };
+
+            // Check for retry
+            if attempt <= self.retries {
+                warn!(
+                    "{} attempt #{} on item {} failed ({}), retrying.",
+                    self.name, attempt, item_id, reason
+                );
+                thread::sleep(Duration::from_secs(attempt as u64));
+                continue;
+            }
+
+            // Record failure
+            let error = ExecError {
+                tool: self.name.to_string(),
+                item_id: item_id.to_string(),
+                attempts: attempt,
+                reason: reason,
+                stderr: stderr,
+            };
+            record_failure(&error);
+            return Err(error);
+        }
+    }
+
+    fn run_once(&self, args: &[&str]) -> Result<Output, (String, String)> {
+        // Spawn process
+        let mut cmd = Command::new(&self.program);
+        cmd.args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        self.apply_limits(&mut cmd);
+        let mut child = match cmd.spawn() {
+            Ok(r) => r,
+            Err(e) => {
+                return Err((
+                    format!("unable to execute {}, {}", self.program, e),
+                    "".to_string(),
+                ))
+            }
+        };
+
+        // Read output in background, so a full pipe can't block the process
+        let stdout = read_pipe(child.stdout.take());
+        let stderr = read_pipe(child.stderr.take());
+
+        // Wait for exit, killing the process upon timeout
+        let status: Option<ExitStatus> = if self.timeout == 0 {
+            child.wait().ok()
+        } else {
+            let deadline = Instant::now() + Duration::from_secs(self.timeout);
+            loop {
+                match child.try_wait() {
+                    Ok(Some(status)) => break Some(status),
+                    Ok(None) => {}
+                    Err(_e) => break None,
+                }
+                if Instant::now() >= deadline {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    break None;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        };
+
+        // Gather output
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+        match status {
+            Some(s) => Ok(Output {
+                status: s,
+                stdout: stdout,
+                stderr: stderr,
+            }),
+            None => Err((
+                format!("timed out after {} seconds", self.timeout),
+                String::from_utf8_lossy(&stderr).to_string(),
+            )),
+        }
+    }
+
+    fn apply_limits(&self, cmd: &mut Command) {
+        let (memory, cpu) = (self.memory_limit * 1024 * 1024, self.cpu_limit);
+        if memory == 0 && cpu == 0 {
+            return;
+        }
+
+        unsafe {
+            cmd.pre_exec(move || {
+                if memory > 0 {
+                    set_rlimit(libc::RLIMIT_AS, memory)?;
+                }
+                if cpu > 0 {
+                    set_rlimit(libc::RLIMIT_CPU, cpu)?;
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+/**
+ * Truncate the failed items file of the program (eg. reporter, blastpal) ahead
+ * of a new run, and return the items which failed during the previous run,
+ * grouped by tool name.
+ */
+pub fn init_failure_log(program: &str) -> HashMap<String, HashSet<String>> {
+    let filename = format!("{}/{}-failed-items.txt", CONFIG.report.output_dir, program);
+    *FAILURE_LOG.lock().unwrap() = filename.to_string();
+    let mut failed: HashMap<String, HashSet<String>> = HashMap::new();
+
+    // Read previous failures
+    if Path::new(&filename).exists() {
+        let fh = match File::open(&filename) {
+            Ok(r) => r,
+            Err(e) => panic!("Unable to open {}, error: {}", filename, e),
+        };
+        for line in io::BufReader::new(fh).lines().flatten() {
+            let parts = line.split("\t").collect::<Vec<&str>>();
+            if parts.len() < 2 || line.starts_with("#") {
+                continue;
+            }
+            failed
+                .entry(parts[0].to_string())
+                .or_insert(HashSet::new())
+                .insert(parts[1].to_string());
+        }
+    }
+
+    // Start new file
+    let mut fh = crate::io::open_file(filename);
+    fh.write_all("#tool\titem_id\tattempts\treason\tstderr\n".as_bytes())
+        .expect("Unable to write to failed items file");
+
+    failed
+}
+
+pub fn record_failure(error: &ExecError) {
+    warn!("{}", error);
+    TOTAL_FAILURES.fetch_add(1, Ordering::SeqCst);
+    let filename = FAILURE_LOG.lock().unwrap();
+
+    // Open file
+    let mut fh = match OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&*filename)
+    {
+        Ok(r) => r,
+        Err(e) => {
+            warn!(
+                "Unable to open failed items file {}, error: {}",
+                *filename, e
+            );
+            return;
+        }
+    };
+
+    // Write line
+    let line = format!(
+        "{}\t{}\t{}\t{}\t{}\n",
+        error.tool,
+        error.item_id,
+        error.attempts,
+        error.reason,
+        error
+            .stderr
+            .trim_end()
+            .replace("\t", " ")
+            .replace("\n", "\\n")
+    );
+    if let Err(e) = fh.write_all(&line.as_bytes()) {
+        warn!(
+            "Unable to write to failed items file {}, error: {}",
+            *filename, e
+        );
+    }
+}
+
+pub fn total_failures() -> usize {
+    TOTAL_FAILURES.load(Ordering::SeqCst)
+}
+
+pub fn failure_log_file() -> String {
+    FAILURE_LOG.lock().unwrap().to_string()
+}
+
+pub fn log_failure_summary() {
+    let failed = count_failures();
+    if failed.len() == 0 {
+        return;
+    }
+
+    for (tool, total) in failed {
+        warn!("{} item(s) failed with {}.", total, tool);
+    }
+    info!(
+        "Failed items written to {}, set rerun-failed = 1 to process only these.",
+        failure_log_file()
+    );
+}
+
+fn count_failures() -> HashMap<String, usize> {
+    let mut failed: HashMap<String, usize> = HashMap::new();
+    let fh = match File::open(failure_log_file()) {
+        Ok(r) => r,
+        Err(_e) => return failed,
+    };
+
+    for line in io::BufReader::new(fh).lines().flatten() {
+        if line.starts_with("#") {
+            continue;
+        }
+        if let Some(tool) = line.split("\t").next() {
+            *failed.entry(tool.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    failed
+}
+
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf: Vec<u8> = Vec::new();
+        if let Some(mut p) = pipe {
+            let _ = p.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+fn set_rlimit(resource: RlimitResource, value: u64) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: value as libc::rlim_t,
+        rlim_max: value as libc::rlim_t,
+    };
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
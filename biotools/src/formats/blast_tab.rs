@@ -0,0 +1,237 @@
+use crate::formats::ParseError;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+pub const DEFAULT_COLUMNS: &str = "qseqid sseqid evalue bitscore qstart qend";
+const STD_COLUMNS: &str =
+    "qseqid sseqid pident length mismatch gapopen qstart qend sstart send evalue bitscore";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+    Qseqid,
+    Sseqid,
+    Pident,
+    Length,
+    Mismatch,
+    Gapopen,
+    Qstart,
+    Qend,
+    Sstart,
+    Send,
+    Evalue,
+    Bitscore,
+    Score,
+    Qlen,
+    Slen,
+    Qcovs,
+    Other(String),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BlastTabRecord {
+    pub line: usize,
+    pub qseqid: String,
+    pub sseqid: String,
+    pub pident: Option<f32>,
+    pub length: Option<u32>,
+    pub mismatch: Option<u32>,
+    pub gapopen: Option<u32>,
+    pub qstart: Option<u32>,
+    pub qend: Option<u32>,
+    pub sstart: Option<u32>,
+    pub send: Option<u32>,
+    pub evalue: Option<f64>,
+    pub bitscore: Option<f32>,
+    pub score: Option<f32>,
+    pub qlen: Option<u32>,
+    pub slen: Option<u32>,
+    pub qcovs: Option<f32>,
+    pub extra: HashMap<String, String>,
+}
+
+impl Column {
+    /**
+     * Get column from its -outfmt specifier (eg. qseqid, bitscore)
+     */
+    pub fn from_specifier(name: &str) -> Self {
+        match name {
+            "qseqid" | "qacc" | "qaccver" => Column::Qseqid,
+            "sseqid" | "sacc" | "saccver" => Column::Sseqid,
+            "pident" => Column::Pident,
+            "length" => Column::Length,
+            "mismatch" => Column::Mismatch,
+            "gapopen" => Column::Gapopen,
+            "qstart" => Column::Qstart,
+            "qend" => Column::Qend,
+            "sstart" => Column::Sstart,
+            "send" => Column::Send,
+            "evalue" => Column::Evalue,
+            "bitscore" => Column::Bitscore,
+            "score" => Column::Score,
+            "qlen" => Column::Qlen,
+            "slen" => Column::Slen,
+            "qcovs" => Column::Qcovs,
+            _ => Column::Other(name.to_string()),
+        }
+    }
+
+    /**
+     * Get column from its description within the '# Fields:' comment of outfmt 7
+     */
+    pub fn from_description(desc: &str) -> Self {
+        match desc.trim() {
+            "query id" | "query acc." | "query acc.ver" => Column::Qseqid,
+            "subject id" | "subject acc." | "subject acc.ver" => Column::Sseqid,
+            "% identity" => Column::Pident,
+            "alignment length" => Column::Length,
+            "mismatches" => Column::Mismatch,
+            "gap opens" => Column::Gapopen,
+            "q. start" => Column::Qstart,
+            "q. end" => Column::Qend,
+            "s. start" => Column::Sstart,
+            "s. end" => Column::Send,
+            "evalue" => Column::Evalue,
+            "bit score" => Column::Bitscore,
+            "score" => Column::Score,
+            "query length" => Column::Qlen,
+            "subject length" => Column::Slen,
+            "% query coverage per subject" => Column::Qcovs,
+            other => Column::Other(other.to_string()),
+        }
+    }
+}
+
+/**
+ * Parse a column specification such as "7 qseqid sseqid evalue".  Any leading
+ * output format number is skipped, and "std" is expanded to the twelve default columns.
+ */
+pub fn parse_columns(spec: &str) -> Vec<Column> {
+    let mut columns: Vec<Column> = Vec::new();
+    for name in spec.split_whitespace() {
+        if name == "6" || name == "7" {
+            continue;
+        } else if name == "std" {
+            columns.extend(STD_COLUMNS.split(" ").map(Column::from_specifier));
+        } else {
+            columns.push(Column::from_specifier(name));
+        }
+    }
+
+    columns
+}
+
+/**
+ * Read a blast tabular results file (outfmt 6 or 7).  The '# Fields:' comment
+ * of outfmt 7 takes precedence over the columns given.
+ */
+pub fn read_file(filename: &str, columns: &str) -> Result<Vec<BlastTabRecord>, ParseError> {
+    // Open file
+    let fh = match File::open(&filename) {
+        Ok(res) => res,
+        Err(e) => {
+            return Err(ParseError::new(
+                0,
+                format!(
+                    "Unable to open blast result file at {}, error: {}",
+                    filename, e
+                ),
+            ))
+        }
+    };
+
+    parse(io::BufReader::new(fh), columns)
+}
+
+pub fn parse<R: BufRead>(reader: R, columns: &str) -> Result<Vec<BlastTabRecord>, ParseError> {
+    // Initialize
+    let mut cols = parse_columns(columns);
+    let mut records: Vec<BlastTabRecord> = Vec::new();
+
+    // Go through lines
+    for (num, ln) in reader.lines().enumerate() {
+        let line_num = num + 1;
+        let line = match ln {
+            Ok(l) => l,
+            Err(e) => {
+                return Err(ParseError::new(
+                    line_num,
+                    format!("Unable to read line, {}", e),
+                ))
+            }
+        };
+
+        // Check for comments
+        if line.starts_with("# Fields:") {
+            cols = line
+                .trim_start_matches("# Fields:")
+                .split(",")
+                .map(Column::from_description)
+                .collect();
+            continue;
+        } else if line.starts_with("#") || line.trim() == "" {
+            continue;
+        }
+
+        records.push(parse_line(&line, line_num, &cols)?);
+    }
+
+    Ok(records)
+}
+
+fn parse_line(
+    line: &str,
+    line_num: usize,
+    cols: &Vec<Column>,
+) -> Result<BlastTabRecord, ParseError> {
+    // Check number of columns
+    let parts = line.trim_end().split("\t").collect::<Vec<&str>>();
+    if parts.len() != cols.len() {
+        return Err(ParseError::new(
+            line_num,
+            format!("Expected {} columns, found {}", cols.len(), parts.len()),
+        ));
+    }
+
+    // Go through columns
+    let mut rec = BlastTabRecord {
+        line: line_num,
+        ..Default::default()
+    };
+    for (col, value) in cols.iter().zip(parts) {
+        match col {
+            Column::Qseqid => rec.qseqid = value.to_string(),
+            Column::Sseqid => rec.sseqid = value.to_string(),
+            Column::Pident => rec.pident = Some(parse_value(value, "pident", line_num)?),
+            Column::Length => rec.length = Some(parse_value(value, "length", line_num)?),
+            Column::Mismatch => rec.mismatch = Some(parse_value(value, "mismatch", line_num)?),
+            Column::Gapopen => rec.gapopen = Some(parse_value(value, "gapopen", line_num)?),
+            Column::Qstart => rec.qstart = Some(parse_value(value, "qstart", line_num)?),
+            Column::Qend => rec.qend = Some(parse_value(value, "qend", line_num)?),
+            Column::Sstart => rec.sstart = Some(parse_value(value, "sstart", line_num)?),
+            Column::Send => rec.send = Some(parse_value(value, "send", line_num)?),
+            Column::Evalue => rec.evalue = Some(parse_value(value, "evalue", line_num)?),
+            Column::Bitscore => rec.bitscore = Some(parse_value(value, "bitscore", line_num)?),
+            Column::Score => rec.score = Some(parse_value(value, "score", line_num)?),
+            Column::Qlen => rec.qlen = Some(parse_value(value, "qlen", line_num)?),
+            Column::Slen => rec.slen = Some(parse_value(value, "slen", line_num)?),
+            Column::Qcovs => rec.qcovs = Some(parse_value(value, "qcovs", line_num)?),
+            Column::Other(name) => {
+                rec.extra.insert(name.to_string(), value.to_string());
+            }
+        };
+    }
+
+    Ok(rec)
+}
+
+fn parse_value<T: FromStr>(value: &str, column: &str, line_num: usize) -> Result<T, ParseError> {
+    match value.trim().parse::<T>() {
+        Ok(r) => Ok(r),
+        Err(_e) => Err(ParseError::new(
+            line_num,
+            format!("Invalid value '{}' in column {}", value, column),
+        )),
+    }
+}
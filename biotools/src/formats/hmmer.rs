@@ -0,0 +1,333 @@
+use crate::db::sqlite::Sqlite;
+use crate::formats::ParseError;
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+const DOMTBL_FIELDS: usize = 22;
+const TBL_FIELDS: usize = 18;
+
+/// One line of `hmmsearch --domtblout` output.
+#[derive(Clone, Debug, Default)]
+pub struct DomainHit {
+    pub target_name: String,
+    pub target_accession: String,
+    pub target_len: u32,
+    pub query_name: String,
+    pub query_accession: String,
+    pub query_len: u32,
+    pub evalue: String,
+    pub score: f64,
+    pub bias: f64,
+    pub domain_num: u32,
+    pub domain_total: u32,
+    pub c_evalue: String,
+    pub i_evalue: String,
+    pub domain_score: f64,
+    pub domain_bias: f64,
+    pub hmm_start: u32,
+    pub hmm_end: u32,
+    pub ali_start: u32,
+    pub ali_end: u32,
+    pub env_start: u32,
+    pub env_end: u32,
+    pub accuracy: f64,
+    pub description: String,
+}
+
+/// One line of `hmmsearch --tblout` output.
+#[derive(Clone, Debug, Default)]
+pub struct TargetHit {
+    pub target_name: String,
+    pub target_accession: String,
+    pub query_name: String,
+    pub query_accession: String,
+    pub evalue: String,
+    pub score: f64,
+    pub bias: f64,
+    pub best_domain_evalue: String,
+    pub best_domain_score: f64,
+    pub best_domain_bias: f64,
+    pub dom_exp: f64,
+    pub dom_reg: u32,
+    pub dom_clu: u32,
+    pub dom_ov: u32,
+    pub dom_env: u32,
+    pub dom_dom: u32,
+    pub dom_rep: u32,
+    pub dom_inc: u32,
+    pub description: String,
+}
+
+pub fn read_domtblout(filename: &str) -> Result<Vec<DomainHit>, ParseError> {
+    let reader = open_file(&filename)?;
+    parse_domtblout(reader)
+}
+
+pub fn read_tblout(filename: &str) -> Result<Vec<TargetHit>, ParseError> {
+    let reader = open_file(&filename)?;
+    parse_tblout(reader)
+}
+
+pub fn parse_domtblout<R: BufRead>(reader: R) -> Result<Vec<DomainHit>, ParseError> {
+    let mut hits: Vec<DomainHit> = Vec::new();
+    for (line_num, line) in data_lines(reader)? {
+        // Split line
+        let (f, description) = match split_fields(&line, DOMTBL_FIELDS) {
+            Some(r) => r,
+            None => {
+                return Err(ParseError::new(
+                    line_num,
+                    format!("Expected at least {} fields", DOMTBL_FIELDS),
+                ))
+            }
+        };
+
+        // Add hit
+        hits.push(DomainHit {
+            target_name: f[0].to_string(),
+            target_accession: f[1].to_string(),
+            target_len: parse_value(f[2], "tlen", line_num)?,
+            query_name: f[3].to_string(),
+            query_accession: f[4].to_string(),
+            query_len: parse_value(f[5], "qlen", line_num)?,
+            evalue: parse_evalue(f[6], "E-value", line_num)?,
+            score: parse_value(f[7], "score", line_num)?,
+            bias: parse_value(f[8], "bias", line_num)?,
+            domain_num: parse_value(f[9], "#", line_num)?,
+            domain_total: parse_value(f[10], "of", line_num)?,
+            c_evalue: parse_evalue(f[11], "c-Evalue", line_num)?,
+            i_evalue: parse_evalue(f[12], "i-Evalue", line_num)?,
+            domain_score: parse_value(f[13], "domain score", line_num)?,
+            domain_bias: parse_value(f[14], "domain bias", line_num)?,
+            hmm_start: parse_value(f[15], "hmm from", line_num)?,
+            hmm_end: parse_value(f[16], "hmm to", line_num)?,
+            ali_start: parse_value(f[17], "ali from", line_num)?,
+            ali_end: parse_value(f[18], "ali to", line_num)?,
+            env_start: parse_value(f[19], "env from", line_num)?,
+            env_end: parse_value(f[20], "env to", line_num)?,
+            accuracy: parse_value(f[21], "acc", line_num)?,
+            description: description,
+        });
+    }
+
+    Ok(hits)
+}
+
+pub fn parse_tblout<R: BufRead>(reader: R) -> Result<Vec<TargetHit>, ParseError> {
+    let mut hits: Vec<TargetHit> = Vec::new();
+    for (line_num, line) in data_lines(reader)? {
+        // Split line
+        let (f, description) = match split_fields(&line, TBL_FIELDS) {
+            Some(r) => r,
+            None => {
+                return Err(ParseError::new(
+                    line_num,
+                    format!("Expected at least {} fields", TBL_FIELDS),
+                ))
+            }
+        };
+
+        // Add hit
+        hits.push(TargetHit {
+            target_name: f[0].to_string(),
+            target_accession: f[1].to_string(),
+            query_name: f[2].to_string(),
+            query_accession: f[3].to_string(),
+            evalue: parse_evalue(f[4], "E-value", line_num)?,
+            score: parse_value(f[5], "score", line_num)?,
+            bias: parse_value(f[6], "bias", line_num)?,
+            best_domain_evalue: parse_evalue(f[7], "best domain E-value", line_num)?,
+            best_domain_score: parse_value(f[8], "best domain score", line_num)?,
+            best_domain_bias: parse_value(f[9], "best domain bias", line_num)?,
+            dom_exp: parse_value(f[10], "exp", line_num)?,
+            dom_reg: parse_value(f[11], "reg", line_num)?,
+            dom_clu: parse_value(f[12], "clu", line_num)?,
+            dom_ov: parse_value(f[13], "ov", line_num)?,
+            dom_env: parse_value(f[14], "env", line_num)?,
+            dom_dom: parse_value(f[15], "dom", line_num)?,
+            dom_rep: parse_value(f[16], "rep", line_num)?,
+            dom_inc: parse_value(f[17], "inc", line_num)?,
+            description: description,
+        });
+    }
+
+    Ok(hits)
+}
+
+pub fn write_domtblout<W: Write>(fh: &mut W, hits: &Vec<DomainHit>) -> io::Result<()> {
+    writeln!(fh, "#                                                                            --- full sequence --- -------------- this domain -------------   hmm coord   ali coord   env coord")?;
+    writeln!(fh, "# target name        accession   tlen query name           accession   qlen   E-value  score  bias   #  of  c-Evalue  i-Evalue  score  bias  from    to  from    to  from    to  acc description of target")?;
+    writeln!(fh, "#------------------- ---------- ----- -------------------- ---------- ----- --------- ------ ----- --- --- --------- --------- ------ ----- ----- ----- ----- ----- ----- ----- ---- ---------------------")?;
+
+    for h in hits {
+        writeln!(
+            fh,
+            "{:<20} {:<10} {:>5} {:<20} {:<10} {:>5} {:>9} {:>6.1} {:>5.1} {:>3} {:>3} {:>9} {:>9} {:>6.1} {:>5.1} {:>5} {:>5} {:>5} {:>5} {:>5} {:>5} {:>4.2} {}",
+            h.target_name,
+            h.target_accession,
+            h.target_len,
+            h.query_name,
+            h.query_accession,
+            h.query_len,
+            h.evalue,
+            h.score,
+            h.bias,
+            h.domain_num,
+            h.domain_total,
+            h.c_evalue,
+            h.i_evalue,
+            h.domain_score,
+            h.domain_bias,
+            h.hmm_start,
+            h.hmm_end,
+            h.ali_start,
+            h.ali_end,
+            h.env_start,
+            h.env_end,
+            h.accuracy,
+            format_description(&h.description)
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn write_tblout<W: Write>(fh: &mut W, hits: &Vec<TargetHit>) -> io::Result<()> {
+    writeln!(fh, "#                                                               --- full sequence ---- --- best 1 domain ---- --- domain number estimation ----")?;
+    writeln!(fh, "# target name        accession  query name           accession    E-value  score  bias   E-value  score  bias   exp reg clu  ov env dom rep inc description of target")?;
+    writeln!(fh, "#------------------- ---------- -------------------- ---------- --------- ------ ----- --------- ------ -----   --- --- --- --- --- --- --- --- ---------------------")?;
+
+    for h in hits {
+        writeln!(
+            fh,
+            "{:<20} {:<10} {:<20} {:<10} {:>9} {:>6.1} {:>5.1} {:>9} {:>6.1} {:>5.1} {:>5.1} {:>3} {:>3} {:>3} {:>3} {:>3} {:>3} {:>3} {}",
+            h.target_name,
+            h.target_accession,
+            h.query_name,
+            h.query_accession,
+            h.evalue,
+            h.score,
+            h.bias,
+            h.best_domain_evalue,
+            h.best_domain_score,
+            h.best_domain_bias,
+            h.dom_exp,
+            h.dom_reg,
+            h.dom_clu,
+            h.dom_ov,
+            h.dom_env,
+            h.dom_dom,
+            h.dom_rep,
+            h.dom_inc,
+            format_description(&h.description)
+        )?;
+    }
+
+    Ok(())
+}
+
+/**
+ * Load a domtblout file into the hmmsearch table of the species database.  The
+ * query name is taken as the ortholog gene id and the target name as the est digest,
+ * same as the analyzer.  Returns the number of rows inserted.
+ */
+pub fn load_domtblout(db: &Sqlite, filename: &str, taxid: &u32) -> Result<usize, ParseError> {
+    // Read file
+    let hits = read_domtblout(&filename)?;
+
+    // Insert hits
+    db.conn
+        .execute_batch("BEGIN")
+        .expect("Unable to begin transaction to load hmmsearch results");
+    for hit in &hits {
+        db.insert_hmmsearch(&taxid, &hit);
+    }
+    db.conn
+        .execute_batch("COMMIT")
+        .expect("Unable to commit transaction to load hmmsearch results");
+
+    Ok(hits.len())
+}
+
+fn open_file(filename: &str) -> Result<io::BufReader<File>, ParseError> {
+    match File::open(&filename) {
+        Ok(r) => Ok(io::BufReader::new(r)),
+        Err(e) => Err(ParseError::new(
+            0,
+            format!(
+                "Unable to open hmmer results file at {}, error: {}",
+                filename, e
+            ),
+        )),
+    }
+}
+
+fn data_lines<R: BufRead>(reader: R) -> Result<Vec<(usize, String)>, ParseError> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (num, ln) in reader.lines().enumerate() {
+        let line = match ln {
+            Ok(l) => l,
+            Err(e) => {
+                return Err(ParseError::new(
+                    num + 1,
+                    format!("Unable to read line, {}", e),
+                ))
+            }
+        };
+
+        // Skip comments and blank lines
+        if line.starts_with("#") || line.trim() == "" {
+            continue;
+        }
+        lines.push((num + 1, line));
+    }
+
+    Ok(lines)
+}
+
+/**
+ * Split the first num whitespace delimited fields from the line, and return
+ * the remainder as the description, which may itself contain spaces.
+ */
+fn split_fields(line: &str, num: usize) -> Option<(Vec<&str>, String)> {
+    let mut fields: Vec<&str> = Vec::new();
+    let mut rest = line.trim_start();
+
+    while fields.len() < num {
+        if rest == "" {
+            return None;
+        }
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+
+    // Get description
+    let description = rest.trim_end();
+    let description = if description == "-" { "" } else { description };
+
+    Some((fields, description.to_string()))
+}
+
+fn format_description(description: &String) -> String {
+    if description == "" {
+        return "-".to_string();
+    }
+    description.to_string()
+}
+
+fn parse_value<T: FromStr>(value: &str, column: &str, line_num: usize) -> Result<T, ParseError> {
+    match value.parse::<T>() {
+        Ok(r) => Ok(r),
+        Err(_e) => Err(ParseError::new(
+            line_num,
+            format!("Invalid value '{}' in column {}", value, column),
+        )),
+    }
+}
+
+fn parse_evalue(value: &str, column: &str, line_num: usize) -> Result<String, ParseError> {
+    parse_value::<f64>(value, column, line_num)?;
+    Ok(value.to_string())
+}
@@ -0,0 +1,34 @@
+use std::error::Error;
+use std::fmt;
+
+pub mod blast_tab;
+pub mod hmmer;
+
+/// Error returned by the result file parsers.  The line number is 1-based, and
+/// zero when the error concerns the file as a whole (eg. unable to open it).
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(line: usize, message: String) -> Self {
+        Self {
+            line: line,
+            message: message,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl Error for ParseError {}
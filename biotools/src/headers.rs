@@ -0,0 +1,118 @@
+use crate::CONFIG;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref CONVENTION: Box<dyn HeaderConvention> = from_name(
+        &CONFIG.search.header_convention,
+        &CONFIG.search.header_locus_regex
+    );
+    static ref TRINITY_REGEX: Regex =
+        Regex::new(r"^(.+_c\d+_g\d+)_i\d+$|^(.+_c\d+)_seq\d+$").unwrap();
+    static ref SPADES_REGEX: Regex =
+        Regex::new(r"^NODE_\d+_length_\d+_cov_[\d.]+_g(\d+)_i\d+$").unwrap();
+    static ref OASES_REGEX: Regex = Regex::new(r"^(.*Locus_\d+)_Transcript_\d+/\d+").unwrap();
+}
+
+/**
+ * Naming convention of the transcript headers of an assembler, from which the
+ * locus of a transcript is derived.  All isoforms of a gene share its locus.
+ */
+pub trait HeaderConvention: Send + Sync {
+    /// Locus of the base header, or None if it doesn't follow the convention.
+    fn locus(&self, header: &str) -> Option<String>;
+}
+
+/// Trinity, such as TRINITY_DN10_c0_g1_i2 or comp10_c0_seq2 of older versions.
+pub struct Trinity;
+
+impl HeaderConvention for Trinity {
+    fn locus(&self, header: &str) -> Option<String> {
+        let caps = TRINITY_REGEX.captures(header)?;
+        caps.get(1).or(caps.get(2)).map(|m| m.as_str().to_string())
+    }
+}
+
+/// rnaSPAdes, such as NODE_12_length_1450_cov_8.2_g3_i1, where g3 is the gene.
+pub struct Spades;
+
+impl HeaderConvention for Spades {
+    fn locus(&self, header: &str) -> Option<String> {
+        let caps = SPADES_REGEX.captures(header)?;
+        Some(format!("NODE_g{}", &caps[1]))
+    }
+}
+
+/// Oases, such as Locus_4_Transcript_2/3_Confidence_0.750_Length_981.
+pub struct Oases;
+
+impl HeaderConvention for Oases {
+    fn locus(&self, header: &str) -> Option<String> {
+        let caps = OASES_REGEX.captures(header)?;
+        Some(caps[1].to_string())
+    }
+}
+
+/// Locus given by the capture group named locus, or otherwise the first capture group.
+pub struct RegexConvention {
+    regex: Regex,
+}
+
+impl RegexConvention {
+    pub fn new(pattern: &str) -> Self {
+        match Regex::new(pattern) {
+            Ok(r) => Self { regex: r },
+            Err(e) => panic!("Invalid header-locus-regex '{}', error: {}", pattern, e),
+        }
+    }
+}
+
+impl HeaderConvention for RegexConvention {
+    fn locus(&self, header: &str) -> Option<String> {
+        let caps = self.regex.captures(header)?;
+        caps.name("locus")
+            .or(caps.get(1))
+            .map(|m| m.as_str().to_string())
+    }
+}
+
+/// The first of the conventions that the header follows.
+pub struct Auto(Vec<Box<dyn HeaderConvention>>);
+
+impl HeaderConvention for Auto {
+    fn locus(&self, header: &str) -> Option<String> {
+        self.0.iter().find_map(|c| c.locus(header))
+    }
+}
+
+/// Every transcript is its own locus.
+pub struct Plain;
+
+impl HeaderConvention for Plain {
+    fn locus(&self, _header: &str) -> Option<String> {
+        None
+    }
+}
+
+pub fn from_name(name: &str, pattern: &str) -> Box<dyn HeaderConvention> {
+    match name {
+        "none" => Box::new(Plain),
+        "trinity" => Box::new(Trinity),
+        "spades" => Box::new(Spades),
+        "oases" => Box::new(Oases),
+        "regex" => Box::new(RegexConvention::new(pattern)),
+        _ => Box::new(Auto(vec![
+            Box::new(Trinity),
+            Box::new(Spades),
+            Box::new(Oases),
+        ])),
+    }
+}
+
+/// Locus of the base header under the header-convention setting, or the header itself.
+pub fn locus(header: &str) -> String {
+    match CONVENTION.locus(header) {
+        Some(r) => r,
+        None => header.to_string(),
+    }
+}
@@ -1,4 +1,4 @@
-use std::env::temp_dir;
+use log::warn;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
@@ -56,13 +56,17 @@ pub fn create_tmp_file(contents: &String) -> String {
 
 pub fn gen_tmp_filename() -> String {
     // Get filename
-    let dir = temp_dir();
-    let filename = format!("{}/{}", dir.to_str().unwrap(), Uuid::new_v4());
-    //let filename = format!("/dev/shm/{}", Uuid::new_v4());
+    let filename = format!("{}/{}", crate::workspace::dir(), Uuid::new_v4());
 
     filename
 }
 
+pub fn remove_tmp_file(filename: &String) {
+    if let Err(e) = fs::remove_file(&filename) {
+        warn!("Unable to remove temporary file {}, error: {}", filename, e);
+    }
+}
+
 pub fn open_file(filename: String) -> File {
     let path = Path::new(&filename);
     let fh = match File::create(&path) {
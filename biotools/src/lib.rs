@@ -5,8 +5,12 @@ use std::collections::HashMap;
 use std::ops::Range;
 
 pub mod db;
+pub mod exec;
+pub mod formats;
+pub mod headers;
 pub mod io;
 pub mod settings;
+pub mod workspace;
 
 lazy_static! {
     pub static ref CONFIG: Settings = Settings::new();
@@ -55,6 +59,32 @@ pub fn format_header(header: &String, revcomp: &u8, translate: &u8) -> String {
 
     header.to_string()
 }
+
+// Reverse complement of a nt sequence
+pub fn reverse_seq(old_seq: &String) -> String {
+    let seq: String = old_seq
+        .chars()
+        .rev()
+        .map(|c| match c {
+            'A' => 'T',
+            'G' => 'C',
+            'C' => 'G',
+            'T' => 'A',
+            'Y' => 'R',
+            'R' => 'Y',
+            'K' => 'M',
+            'M' => 'K',
+            'a' => 'T',
+            'g' => 'C',
+            'c' => 'G',
+            't' => 'A',
+            _ => c,
+        })
+        .collect();
+
+    seq
+}
+
 pub fn read_fasta_string(contents: &String) -> HashMap<String, FastaResult> {
     // Initialize
     let mut hdr: Vec<&str> = Vec::new();
@@ -122,6 +152,14 @@ pub fn read_fasta_string(contents: &String) -> HashMap<String, FastaResult> {
     result
 }
 
+pub fn log_evalue(evalue: f64) -> f32 {
+    if evalue == 0.00 {
+        return -999.00;
+    }
+    // In f64, as e-values of strong hits underflow f32
+    evalue.log2().floor() as f32
+}
+
 pub fn get_overlap_percent(source: Range<u16>, dest: Range<u16>, is_rev: bool) -> Option<f32> {
     // Check for valid ranges
     if source.start > source.end || dest.start > dest.end {
@@ -9,8 +9,10 @@ use std::string::String;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Database {
+    pub backend: String,
     pub sqlite_file: String,
     pub reporter_sqlite_file: String,
+    pub rocksdb_dir: String,
     pub table_prefix: String,
 }
 
@@ -25,13 +27,24 @@ pub struct Search {
     pub hmm_score_discard_threshold: f32,
     pub max_blast_searches: u16,
     pub max_blast_hits: u16,
+    pub blast_columns: String,
     pub num_threads: u8,
     pub min_transcript_length: u16,
     pub min_overlap: f32,
     pub fill_with_x: bool,
     pub substitute_u_with: String,
     pub header_seperator: String,
+    pub header_convention: String,
+    pub header_locus_regex: String,
     pub max_mismatches: u16,
+    pub tie_policy: String,
+    pub soft_threshold: u16,
+    pub min_reference_taxa: u16,
+    pub chimera_mode: String,
+    pub max_internal_stops: u16,
+    pub min_orf_length_ratio: f32,
+    pub max_orf_length_ratio: f32,
+    pub min_reference_identity: f32,
 }
 
 pub struct Switch {
@@ -43,6 +56,18 @@ pub struct Switch {
     pub clear_files: bool,
     pub enable_env_overlap: bool,
     pub enable_hmm_overlap: bool,
+    pub rerun_failed: bool,
+    pub keep_tmp_files: bool,
+    pub paralog_mode: bool,
+}
+
+pub struct Limits {
+    pub blast_timeout: u64,
+    pub exonerate_timeout: u64,
+    pub translate_timeout: u64,
+    pub retries: u8,
+    pub memory_limit: u64,
+    pub cpu_limit: u64,
 }
 
 pub struct Log {
@@ -58,9 +83,12 @@ pub struct Report {
     pub set_name: String,
     pub sets_dir: String,
     pub output_dir: String,
+    pub tmp_dir: String,
     pub reference_taxa: String,
+    pub reference_taxa_weights: String,
     pub cog_list_file: String,
     pub wanted_genes: Vec<String>,
+    pub hmmsearch_domtblout: Vec<String>,
 }
 
 pub struct Programs {
@@ -78,6 +106,7 @@ pub struct Settings {
     pub db: Database,
     pub search: Search,
     pub switch: Switch,
+    pub limits: Limits,
     pub log: Log,
     pub report: Report,
     pub programs: Programs,
@@ -89,12 +118,18 @@ impl Settings {
         let config = Settings::load_config_file();
 
         // Database
+        let rocksdb_dir = match Settings::get_var(&config, "rocksdb-directory").as_str() {
+            "" => format!("{}/rocksdb", config["output-directory"]),
+            r => r.to_string(),
+        };
         let database = Database {
+            backend: Settings::get_var(&config, "database-backend"),
             sqlite_file: Settings::get_var(&config, &"sqlite-database"),
             reporter_sqlite_file: format!(
                 "{}/{}.sqlite",
                 config["output-directory"], config["species-name"]
             ),
+            rocksdb_dir: rocksdb_dir,
             table_prefix: Settings::get_var(&config, "dbtable-prefix"),
         };
 
@@ -130,6 +165,7 @@ impl Settings {
             max_blast_hits: Settings::get_var(&config, "max-blast-hits")
                 .parse::<u16>()
                 .unwrap(),
+            blast_columns: Settings::get_var(&config, "blast-output-columns"),
             num_threads: Settings::get_var(&config, "num-threads")
                 .parse::<u8>()
                 .unwrap(),
@@ -144,9 +180,31 @@ impl Settings {
                 .unwrap(),
             substitute_u_with: Settings::get_var(&config, "substitute-u-with"),
             header_seperator: Settings::get_var(&config, "header-separator"),
+            header_convention: Settings::get_var(&config, "header-convention"),
+            header_locus_regex: Settings::get_var(&config, "header-locus-regex"),
             max_mismatches: Settings::get_var(&config, "max-reciprocal-mismatches")
                 .parse::<u16>()
                 .unwrap(),
+            tie_policy: Settings::get_var(&config, "reciprocal-tie-policy"),
+            soft_threshold: Settings::get_var(&config, "soft-threshold")
+                .parse::<u16>()
+                .unwrap(),
+            min_reference_taxa: Settings::get_var(&config, "min-reference-taxa")
+                .parse::<u16>()
+                .unwrap(),
+            chimera_mode: Settings::get_var(&config, "chimera-mode"),
+            max_internal_stops: Settings::get_var(&config, "max-internal-stops")
+                .parse::<u16>()
+                .unwrap(),
+            min_orf_length_ratio: Settings::get_var(&config, "min-orf-length-ratio")
+                .parse::<f32>()
+                .unwrap(),
+            max_orf_length_ratio: Settings::get_var(&config, "max-orf-length-ratio")
+                .parse::<f32>()
+                .unwrap(),
+            min_reference_identity: Settings::get_var(&config, "min-reference-identity")
+                .parse::<f32>()
+                .unwrap(),
         };
 
         // Switch
@@ -159,6 +217,31 @@ impl Settings {
             clear_files: Settings::get_bool(&config, "clear-files"),
             enable_env_overlap: Settings::get_bool(&config, "enable-env-overlap"),
             enable_hmm_overlap: Settings::get_bool(&config, "enable-hmm-overlap"),
+            rerun_failed: Settings::get_bool(&config, "rerun-failed"),
+            keep_tmp_files: Settings::get_bool(&config, "keep-tmp-files"),
+            paralog_mode: Settings::get_bool(&config, "paralog-mode"),
+        };
+
+        // Limits
+        let limits = Limits {
+            blast_timeout: Settings::get_var(&config, "blast-timeout")
+                .parse::<u64>()
+                .unwrap(),
+            exonerate_timeout: Settings::get_var(&config, "exonerate-timeout")
+                .parse::<u64>()
+                .unwrap(),
+            translate_timeout: Settings::get_var(&config, "translate-timeout")
+                .parse::<u64>()
+                .unwrap(),
+            retries: Settings::get_var(&config, "tool-retries")
+                .parse::<u8>()
+                .unwrap(),
+            memory_limit: Settings::get_var(&config, "tool-memory-limit")
+                .parse::<u64>()
+                .unwrap(),
+            cpu_limit: Settings::get_var(&config, "tool-cpu-limit")
+                .parse::<u64>()
+                .unwrap(),
         };
 
         // Log
@@ -177,12 +260,21 @@ impl Settings {
             output_dir: Settings::get_var(&config, "output-directory")
                 .trim_end_matches("/")
                 .to_string(),
+            tmp_dir: Settings::get_var(&config, "tmp-dir")
+                .trim_end_matches("/")
+                .to_string(),
             reference_taxa: Settings::get_var(&config, "reference-taxa"),
+            reference_taxa_weights: Settings::get_var(&config, "reference-taxa-weights"),
             sets_dir: Settings::get_var(&config, "sets-dir")
                 .trim_end_matches("/")
                 .to_string(),
             cog_list_file: Settings::get_var(&config, "cog-list-file"),
             wanted_genes: Settings::get_wanted_genes(&Settings::get_var(&config, "cog-list-file")),
+            hmmsearch_domtblout: Settings::get_var(&config, "hmmsearch-domtblout")
+                .split(",")
+                .map(|f| f.trim().to_string())
+                .filter(|f| f != "")
+                .collect(),
         };
 
         // Programs
@@ -202,6 +294,7 @@ impl Settings {
             db: database,
             search: search,
             switch: switch,
+            limits: limits,
             log: log,
             report: report,
             programs: programs,
@@ -318,6 +411,8 @@ impl Settings {
 
         let config = HashMap::from([
             (String::from("blastdb"), String::from("")),
+            (String::from("database-backend"), String::from("sqlite")),
+            (String::from("rocksdb-directory"), String::from("")),
             (String::from("dbtable-prefix"), String::from("orthograph")),
             (String::from("sqlite-program"), String::from("sqlite3")),
             (String::from("ortholog-set"), String::from("test_set")),
@@ -362,6 +457,10 @@ impl Settings {
             ),
             (String::from("max-blast-searches"), String::from("100")),
             (String::from("max-blast-hits"), String::from("100")),
+            (
+                String::from("blast-output-columns"),
+                String::from(crate::formats::blast_tab::DEFAULT_COLUMNS),
+            ),
             (String::from("num-threads"), String::from("1")),
             (
                 String::from("minimum-transcript-length"),
@@ -376,15 +475,40 @@ impl Settings {
             (String::from("clear-files"), String::from("false")),
             (String::from("enable-env-overlap"), String::from("true")),
             (String::from("enable-hmm-overlap"), String::from("true")),
+            (String::from("rerun-failed"), String::from("false")),
+            (String::from("tmp-dir"), String::from("")),
+            (String::from("keep-tmp-files"), String::from("false")),
+            (String::from("paralog-mode"), String::from("false")),
+            (String::from("blast-timeout"), String::from("3600")),
+            (String::from("exonerate-timeout"), String::from("300")),
+            (String::from("translate-timeout"), String::from("60")),
+            (String::from("tool-retries"), String::from("0")),
+            (String::from("tool-memory-limit"), String::from("0")),
+            (String::from("tool-cpu-limit"), String::from("0")),
             (String::from("verbose"), String::from("false")),
             (String::from("quiet"), String::from("false")),
             (String::from("extend-orf"), String::from("false")),
             (String::from("substitute-u-with"), String::from("X")),
             (String::from("header-separator"), String::from("|")),
+            (String::from("header-convention"), String::from("none")),
+            (String::from("header-locus-regex"), String::from("")),
             (String::from("logfile"), logfile),
             (String::from("reference-taxa"), String::from("")),
             (String::from("cog-list-file"), String::from("")),
+            (String::from("hmmsearch-domtblout"), String::from("")),
             (String::from("max-reciprocal-mismatches"), String::from("0")),
+            (
+                String::from("reciprocal-tie-policy"),
+                String::from("reject"),
+            ),
+            (String::from("soft-threshold"), String::from("0")),
+            (String::from("min-reference-taxa"), String::from("0")),
+            (String::from("reference-taxa-weights"), String::from("")),
+            (String::from("chimera-mode"), String::from("off")),
+            (String::from("max-internal-stops"), String::from("0")),
+            (String::from("min-orf-length-ratio"), String::from("0")),
+            (String::from("max-orf-length-ratio"), String::from("0")),
+            (String::from("min-reference-identity"), String::from("0")),
         ]);
 
         // return
@@ -406,6 +530,54 @@ impl Settings {
             }
         }
 
+        // Check database backend
+        if config.contains_key("database-backend")
+            && !["sqlite", "rocksdb"].contains(&config["database-backend"].as_str())
+        {
+            panic!(
+                "Invalid database-backend '{}', must be either 'sqlite' or 'rocksdb'.",
+                config["database-backend"]
+            );
+        }
+
+        // Check tie policy
+        if !["reject", "accept"].contains(&config["reciprocal-tie-policy"].as_str()) {
+            panic!(
+                "Invalid reciprocal-tie-policy '{}', must be either 'reject' or 'accept'.",
+                config["reciprocal-tie-policy"]
+            );
+        }
+
+        // Check chimera mode
+        if !["off", "split", "discard"].contains(&config["chimera-mode"].as_str()) {
+            panic!(
+                "Invalid chimera-mode '{}', must be one of: off, split, discard.",
+                config["chimera-mode"]
+            );
+        }
+
+        // Check header convention
+        let conventions = ["auto", "none", "trinity", "spades", "oases", "regex"];
+        if !conventions.contains(&config["header-convention"].as_str()) {
+            panic!(
+                "Invalid header-convention '{}', must be one of: {}.",
+                config["header-convention"],
+                conventions.join(", ")
+            );
+        }
+        if config["header-convention"] == "regex" {
+            match regex::Regex::new(&config["header-locus-regex"]) {
+                Ok(r) if r.captures_len() > 1 => {}
+                Ok(_r) => panic!(
+                    "The header-locus-regex setting must contain a capture group of the locus."
+                ),
+                Err(e) => panic!(
+                    "Invalid header-locus-regex '{}', error: {}",
+                    config["header-locus-regex"], e
+                ),
+            };
+        }
+
         // Ensure input file exists
         if !Path::new(&config["input-file"]).exists() {
             panic!(
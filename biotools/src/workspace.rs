@@ -0,0 +1,105 @@
+use crate::CONFIG;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use std::env::temp_dir;
+use std::fs;
+use std::panic;
+use std::path::Path;
+use uuid::Uuid;
+
+lazy_static! {
+    static ref WORKSPACE: Workspace = Workspace::new();
+}
+
+/// Temporary directory holding all scratch files of a single run.
+pub struct Workspace {
+    pub dir: String,
+}
+
+impl Workspace {
+    fn new() -> Self {
+        // Get base directory
+        let base = if CONFIG.report.tmp_dir == "" {
+            temp_dir()
+                .to_str()
+                .unwrap()
+                .trim_end_matches("/")
+                .to_string()
+        } else {
+            format!("{}", CONFIG.report.tmp_dir)
+        };
+
+        // Create directory
+        let dir = format!(
+            "{}/orthograph-{}-{}",
+            base,
+            CONFIG.report.species_name,
+            Uuid::new_v4()
+        );
+        crate::io::create_dir(&dir);
+        info!("Created temporary workspace at {}", dir);
+
+        // Remove directory when interrupted
+        let sigint_dir = format!("{}", dir);
+        if let Err(e) = ctrlc::set_handler(move || {
+            warn!(
+                "Interrupted, removing temporary workspace at {}",
+                sigint_dir
+            );
+            let _ = fs::remove_dir_all(&sigint_dir);
+            std::process::exit(130);
+        }) {
+            warn!("Unable to install interrupt handler, error: {}", e);
+        }
+
+        // Keep directory upon panic
+        let panic_dir = format!("{}", dir);
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |p| {
+            default_hook(p);
+            eprintln!(
+                "Keeping temporary workspace at {} for debugging.",
+                panic_dir
+            );
+        }));
+
+        Self { dir: dir }
+    }
+}
+
+/**
+ * Create the workspace, if not already done.  Should be called at startup so
+ * the interrupt handler is in place before any external programs are run.
+ */
+pub fn init() {
+    lazy_static::initialize(&WORKSPACE);
+}
+
+pub fn dir() -> &'static str {
+    &WORKSPACE.dir
+}
+
+/**
+ * Remove the workspace at the end of a successful run.  It is kept when
+ * keep-tmp-files is set, or when any external program failed, so the inputs of
+ * the failed invocations can be inspected.
+ */
+pub fn finish() {
+    if !Path::new(dir()).exists() {
+        return;
+    }
+
+    let failures = crate::exec::total_failures();
+    if CONFIG.switch.keep_tmp_files {
+        info!("Keeping temporary workspace at {}", dir());
+    } else if failures > 0 {
+        warn!(
+            "Keeping temporary workspace at {} as {} external program invocation(s) failed.",
+            dir(),
+            failures
+        );
+    } else {
+        crate::io::remove_dir(&dir().to_string());
+        info!("Removed temporary workspace at {}", dir());
+    }
+}
@@ -0,0 +1,83 @@
+use biotools::formats::blast_tab::{self, Column, DEFAULT_COLUMNS};
+
+#[test]
+fn parses_default_columns() {
+    let contents = "seq1\tref1\t1e-30\t120.5\t3\t98\nseq1\tref2\t2.5e-10\t60.1\t10\t80\n";
+    let records = blast_tab::parse(contents.as_bytes(), DEFAULT_COLUMNS).unwrap();
+
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1].line, 2);
+    assert_eq!(records[1].qseqid, "seq1");
+    assert_eq!(records[1].sseqid, "ref2");
+    assert_eq!(records[1].evalue, Some(2.5e-10));
+    assert_eq!(records[1].bitscore, Some(60.1));
+    assert_eq!(records[1].qstart, Some(10));
+    assert_eq!(records[1].qend, Some(80));
+    assert_eq!(records[1].pident, None);
+}
+
+#[test]
+fn expands_std_columns() {
+    let columns = blast_tab::parse_columns("6 std qlen");
+    assert_eq!(columns.len(), 13);
+    assert_eq!(columns[0], Column::Qseqid);
+    assert_eq!(columns[2], Column::Pident);
+    assert_eq!(columns[11], Column::Bitscore);
+    assert_eq!(columns[12], Column::Qlen);
+
+    let contents = "q1\ts1\t98.5\t100\t1\t0\t1\t100\t5\t104\t1e-50\t190.2\t320\n";
+    let records = blast_tab::parse(contents.as_bytes(), "std qlen").unwrap();
+    assert_eq!(records[0].pident, Some(98.5));
+    assert_eq!(records[0].sstart, Some(5));
+    assert_eq!(records[0].send, Some(104));
+    assert_eq!(records[0].qlen, Some(320));
+}
+
+#[test]
+fn fields_comment_overrides_columns() {
+    let contents = "# BLASTP 2.12.0+\n\
+        # Fields: subject id, query id, bit score, evalue, subject length\n\
+        # 1 hits found\n\
+        ref1\tseq1\t88.0\t3e-20\t250\n";
+    let records = blast_tab::parse(contents.as_bytes(), DEFAULT_COLUMNS).unwrap();
+
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].line, 4);
+    assert_eq!(records[0].qseqid, "seq1");
+    assert_eq!(records[0].sseqid, "ref1");
+    assert_eq!(records[0].bitscore, Some(88.0));
+    assert_eq!(records[0].evalue, Some(3e-20));
+    assert_eq!(records[0].slen, Some(250));
+}
+
+#[test]
+fn unknown_columns_kept_as_extra() {
+    let contents = "seq1\tref1\tTaxA\n";
+    let records = blast_tab::parse(contents.as_bytes(), "qseqid sseqid sscinames").unwrap();
+    assert_eq!(records[0].extra.get("sscinames").unwrap(), "TaxA");
+}
+
+#[test]
+fn column_count_error_gives_line_number() {
+    let contents = "# comment\nseq1\tref1\t1e-30\t120.5\t3\t98\n\nseq2\tref1\t1e-30\n";
+    let err = blast_tab::parse(contents.as_bytes(), DEFAULT_COLUMNS).unwrap_err();
+    assert_eq!(err.line, 4);
+    assert_eq!(err.to_string(), "line 4: Expected 6 columns, found 3");
+}
+
+#[test]
+fn invalid_value_error_gives_line_number() {
+    let contents = "seq1\tref1\tabc\t120.5\t3\t98\n";
+    let err = blast_tab::parse(contents.as_bytes(), DEFAULT_COLUMNS).unwrap_err();
+    assert_eq!(err.line, 1);
+    assert_eq!(err.message, "Invalid value 'abc' in column evalue");
+}
+
+#[test]
+fn missing_file_error() {
+    let err = blast_tab::read_file("/nonexistent/blast.txt", DEFAULT_COLUMNS).unwrap_err();
+    assert_eq!(err.line, 0);
+    assert!(err
+        .to_string()
+        .starts_with("Unable to open blast result file"));
+}
@@ -0,0 +1,91 @@
+use biotools::formats::hmmer::{self, DomainHit, TargetHit};
+
+const DOMTBLOUT: &str = "\
+#                                                                            --- full sequence --- -------------- this domain -------------   hmm coord   ali coord   env coord
+# target name        accession   tlen query name           accession   qlen   E-value  score  bias   #  of  c-Evalue  i-Evalue  score  bias  from    to  from    to  from    to  acc description of target
+#------------------- ---------- ----- -------------------- ---------- ----- --------- ------ ----- --- --- --------- --------- ------ ----- ----- ----- ----- ----- ----- ----- ---- ---------------------
+8b999733ccedafc171dae12464f2f961 -             62 EOG001               -             50   2.1e-45  150.2   0.1   1   1   1.1e-48   2.2e-45  150.0   0.1     1    50     5    54     4    55 0.99 TRANS_1 [translate(1)]
+8489be3498999eda808104582b238e00 -             61 EOG002               -             50   3.4e-42  140.7   0.0   1   2   4.4e-45   3.4e-42  139.9   0.0     2    49     6    53     6    54 0.98 -
+";
+
+const TBLOUT: &str = "\
+#                                                               --- full sequence ---- --- best 1 domain ---- --- domain number estimation ----
+# target name        accession  query name           accession    E-value  score  bias   E-value  score  bias   exp reg clu  ov env dom rep inc description of target
+#------------------- ---------- -------------------- ---------- --------- ------ ----- --------- ------ -----   --- --- --- --- --- --- --- --- ---------------------
+8b999733ccedafc171dae12464f2f961 -          EOG001               -            2.1e-45  150.2   0.1   2.2e-45  150.0   0.1   1.0   1   0   0   1   1   1   1 TRANS_1 [translate(1)]
+";
+
+#[test]
+fn log_evalue_of_small_evalues() {
+    assert_eq!(biotools::log_evalue(0.0), -999.0);
+    assert_eq!(biotools::log_evalue(1e-10), -34.0);
+    assert_eq!(biotools::log_evalue(1e-50), -167.0);
+    assert_eq!(biotools::log_evalue(1e-200), -665.0);
+}
+
+#[test]
+fn parses_domtblout() {
+    let hits = hmmer::parse_domtblout(DOMTBLOUT.as_bytes()).unwrap();
+    assert_eq!(hits.len(), 2);
+
+    let hit = &hits[0];
+    assert_eq!(hit.target_name, "8b999733ccedafc171dae12464f2f961");
+    assert_eq!(hit.query_name, "EOG001");
+    assert_eq!(hit.evalue, "2.1e-45");
+    assert_eq!(hit.score, 150.2);
+    assert_eq!(hit.domain_total, 1);
+    assert_eq!((hit.hmm_start, hit.hmm_end), (1, 50));
+    assert_eq!((hit.ali_start, hit.ali_end), (5, 54));
+    assert_eq!((hit.env_start, hit.env_end), (4, 55));
+    assert_eq!(hit.description, "TRANS_1 [translate(1)]");
+    assert_eq!(hits[1].description, "");
+}
+
+#[test]
+fn domtblout_round_trip() {
+    let hits = hmmer::parse_domtblout(DOMTBLOUT.as_bytes()).unwrap();
+    let mut written: Vec<u8> = Vec::new();
+    hmmer::write_domtblout(&mut written, &hits).unwrap();
+
+    let reparsed = hmmer::parse_domtblout(written.as_slice()).unwrap();
+    assert_eq!(format_domain_hits(&reparsed), format_domain_hits(&hits));
+}
+
+#[test]
+fn tblout_round_trip() {
+    let hits = hmmer::parse_tblout(TBLOUT.as_bytes()).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].best_domain_score, 150.0);
+    assert_eq!(hits[0].dom_exp, 1.0);
+    assert_eq!(hits[0].description, "TRANS_1 [translate(1)]");
+
+    let mut written: Vec<u8> = Vec::new();
+    hmmer::write_tblout(&mut written, &hits).unwrap();
+    let reparsed = hmmer::parse_tblout(written.as_slice()).unwrap();
+    assert_eq!(format_target_hits(&reparsed), format_target_hits(&hits));
+}
+
+#[test]
+fn domtblout_errors_give_line_number() {
+    let err = hmmer::parse_domtblout("# comment\nseq1 - 62 EOG001\n".as_bytes()).unwrap_err();
+    assert_eq!(err.to_string(), "line 2: Expected at least 22 fields");
+
+    let line = DOMTBLOUT
+        .lines()
+        .nth(3)
+        .unwrap()
+        .replacen(" 62 ", " x62 ", 1);
+    let err = hmmer::parse_domtblout(line.as_bytes()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "line 1: Invalid value 'x62' in column tlen"
+    );
+}
+
+fn format_domain_hits(hits: &Vec<DomainHit>) -> Vec<String> {
+    hits.iter().map(|h| format!("{:?}", h)).collect()
+}
+
+fn format_target_hits(hits: &Vec<TargetHit>) -> Vec<String> {
+    hits.iter().map(|h| format!("{:?}", h)).collect()
+}
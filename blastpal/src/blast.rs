@@ -1,13 +1,13 @@
 use crate::models::{Blast, HmmSearch};
+use biotools::exec::Tool;
+use biotools::formats::blast_tab;
 use biotools::settings::Settings;
 use lazy_static::lazy_static;
-use log::info;
+use log::{info, warn};
 use std::fs;
 use std::fs::File;
 use std::io::Write;
-use std::io::{self, BufRead};
 use std::path::Path;
-use std::process::Command;
 
 lazy_static! {
     pub static ref CONFIG: Settings = Settings::new();
@@ -40,27 +40,34 @@ pub fn run(search: &HmmSearch) -> Vec<Blast> {
     };
 
     // Run blastp command
-    let output = Command::new(&CONFIG.programs.blast)
-        .args([
-            "-outfmt",
-            "7 qseqid sseqid evalue bitscore qstart qend",
-            "-evalue",
-            &CONFIG.search.blast_evalue_threshold.to_string(),
-            "-threshold",
-            &CONFIG.search.blast_threshold.to_string(),
-            "-max_target_seqs",
-            &CONFIG.search.max_blast_searches.to_string(),
-            "-num_threads",
-            &CONFIG.search.num_threads.to_string(),
-            "-db",
-            &blastdb,
-            "-query",
-            &tmpfile,
-            "-out",
-            &outfile,
-        ])
-        .output()
-        .expect("Unable to run blastp program");
+    let args = [
+        "-outfmt",
+        &format!("7 {}", CONFIG.search.blast_columns),
+        "-evalue",
+        &CONFIG.search.blast_evalue_threshold.to_string(),
+        "-threshold",
+        &CONFIG.search.blast_threshold.to_string(),
+        "-max_target_seqs",
+        &CONFIG.search.max_blast_searches.to_string(),
+        "-num_threads",
+        &CONFIG.search.num_threads.to_string(),
+        "-db",
+        &blastdb,
+        "-query",
+        &tmpfile,
+        "-out",
+        &outfile,
+    ];
+    let output = match Tool::blast().run(&args, &search.id.to_string()) {
+        Ok(r) => r,
+        Err(_e) => {
+            warn!(
+                "Blast of hmm search id# {} failed, query file kept at {}",
+                search.id, tmpfile
+            );
+            return Vec::new();
+        }
+    };
 
     // Log
     info!(
@@ -80,7 +87,7 @@ pub fn run(search: &HmmSearch) -> Vec<Blast> {
     );
 
     // Delete tmpfile
-    fs::remove_file(&tmpfile).expect("Unable to delete temporary query file");
+    biotools::io::remove_tmp_file(&tmpfile);
 
     // Return
     blasts
@@ -89,8 +96,10 @@ pub fn run(search: &HmmSearch) -> Vec<Blast> {
 fn generate_tmpfile(search: &HmmSearch) -> String {
     // Get filename
     let tmpfile = format!(
-        "{}/tmp/{}.hmm-{}",
-        CONFIG.report.output_dir, search.gene_id, search.id
+        "{}/{}.hmm-{}",
+        biotools::workspace::dir(),
+        search.gene_id,
+        search.id
     );
 
     // Save to tmpfile
@@ -113,48 +122,62 @@ fn generate_tmpfile(search: &HmmSearch) -> String {
 }
 
 fn gather_blast_results(outfile: &String, search: &HmmSearch) -> Vec<Blast> {
-    // Initialize
-    let mut blast_res: Vec<Blast> = Vec::new();
-
-    // Open file
-    let fh = match File::open(&outfile) {
-        Ok(res) => res,
-        Err(e) => panic!(
-            "Unable to open blast result file at {}, error: {}",
-            outfile, e
-        ),
+    // Parse results file
+    let records = match blast_tab::read_file(&outfile, &CONFIG.search.blast_columns) {
+        Ok(r) => r,
+        Err(e) => {
+            warn!(
+                "Unable to parse blast result file {} of hmm search id# {}, {}",
+                outfile, search.id, e
+            );
+            return Vec::new();
+        }
     };
-    let lines = io::BufReader::new(fh).lines();
 
-    // Go through lines
-    for ln in lines {
-        let line = match ln {
-            Ok(l) => l,
-            Err(_e) => continue,
+    // Go through records
+    let mut blast_res: Vec<Blast> = Vec::new();
+    for rec in records {
+        // Check required columns
+        let (evalue, bitscore, qstart, qend) = match (
+            rec.evalue,
+            rec.bitscore,
+            rec.qstart,
+            rec.qend,
+        ) {
+            (Some(e), Some(b), Some(s), Some(q)) => (e, b, s, q),
+            _ => {
+                warn!("Skipping line {} of blast result file {}, missing one of the evalue, bitscore, qstart or qend columns.", rec.line, outfile);
+                continue;
+            }
         };
 
-        if line.starts_with("#") {
-            continue;
-        }
+        // Get target
+        let target = match rec.sseqid.parse::<u32>() {
+            Ok(r) => r,
+            Err(_e) => {
+                warn!("Skipping line {} of blast result file {}, subject id '{}' is not a numeric aa sequence id.", rec.line, outfile, rec.sseqid);
+                continue;
+            }
+        };
 
-        // Set variables
-        let parts = line.trim_end().split("\t").collect::<Vec<&str>>();
-        let tmp_evalue = parts[3].parse::<f32>().unwrap();
-        let log_evalue = if tmp_evalue == 0.00 {
-            -999.00
-        } else {
-            tmp_evalue.log2().floor()
+        // Get coords, which must fit within the u16 columns
+        let (res_start, res_end) = match (u16::try_from(qstart), u16::try_from(qend)) {
+            (Ok(s), Ok(e)) => (s, e),
+            _ => {
+                warn!("Skipping line {} of blast result file {}, query coords {}-{} exceed the maximum of {}.", rec.line, outfile, qstart, qend, u16::MAX);
+                continue;
+            }
         };
 
         // Add to blast
         blast_res.push(Blast {
-            query: parts[0].to_string(),
-            target: parts[1].parse::<u16>().unwrap(),
-            score: parts[3].parse::<f32>().unwrap(),
-            evalue: parts[2].parse::<f32>().unwrap(),
-            log_evalue: log_evalue,
-            res_start: parts[4].parse::<u16>().unwrap(),
-            res_end: parts[5].parse::<u16>().unwrap(),
+            query: rec.qseqid,
+            target: target,
+            score: bitscore,
+            evalue: evalue as f32,
+            log_evalue: biotools::log_evalue(evalue),
+            res_start: res_start,
+            res_end: res_end,
             hmmsearch_id: search.id,
         });
     }
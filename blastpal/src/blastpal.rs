@@ -3,14 +3,17 @@ use crate::models::{Blast, HmmSearch};
 use crate::CONFIG;
 use biotools::db::sqlite::Sqlite;
 use biotools::db::sqlite::{TBL_BLAST, TBL_ESTS, TBL_HMMSEARCH};
+use biotools::formats::hmmer;
 use log::{info, warn};
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
 use rusqlite::{Error, ToSql};
+use std::collections::HashSet;
 
 pub struct Blastpal {
     db: Sqlite,
     species_id: u32,
+    rerun_ids: Option<HashSet<String>>,
 }
 
 impl Blastpal {
@@ -32,9 +35,31 @@ impl Blastpal {
             species_id, CONFIG.report.species_name
         );
 
+        // Load hmm searches from domtblout files, if given
+        for filename in &CONFIG.report.hmmsearch_domtblout {
+            match hmmer::load_domtblout(&db, &filename, &species_id) {
+                Ok(r) => info!("Loaded {} hmm searches from {}", r, filename),
+                Err(e) => panic!("Unable to load hmm searches from {}, {}", filename, e),
+            };
+        }
+
+        // Get previously failed hmm searches
+        let mut failed = biotools::exec::init_failure_log("blastpal");
+        let rerun_ids = if CONFIG.switch.rerun_failed {
+            let ids = failed.remove("blast").unwrap_or(HashSet::new());
+            info!(
+                "Only processing the {} hmm searches which previously failed",
+                ids.len()
+            );
+            Some(ids)
+        } else {
+            None
+        };
+
         return Self {
             db: db,
             species_id: species_id,
+            rerun_ids: rerun_ids,
         };
     }
 
@@ -105,6 +130,13 @@ impl Blastpal {
                 continue;
             }
 
+            // Skip, if only re-running failed searches
+            if let Some(ids) = &self.rerun_ids {
+                if !ids.contains(&search.id.to_string()) {
+                    continue;
+                }
+            }
+
             // Delete previous blast results
             stmt_delete
                 .execute([&search.id])
@@ -122,6 +154,7 @@ impl Blastpal {
 
         // Catch any remaining pending blasts
         self.run_blasts(&pending_blasts, true);
+        biotools::exec::log_failure_summary();
 
         // Return
         Ok(true)
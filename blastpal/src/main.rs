@@ -20,11 +20,13 @@ fn main() {
     let start_time = Instant::now();
 
     // Process
+    biotools::workspace::init();
     let blastpal = Blastpal::new();
     match blastpal.process() {
         Ok(res) => res,
         Err(e) => panic!("An error occured while processing: {}", e),
     };
+    biotools::workspace::finish();
 
     // Give processing time
     let elapsed = start_time.elapsed();
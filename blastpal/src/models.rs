@@ -9,7 +9,7 @@ pub struct HmmSearch {
 
 pub struct Blast {
     pub query: String,
-    pub target: u16,
+    pub target: u32,
     pub score: f32,
     pub evalue: f32,
     pub log_evalue: f32,
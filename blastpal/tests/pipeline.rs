@@ -0,0 +1,147 @@
+use biotools::formats::hmmer::{self, DomainHit};
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use testkit::fixtures::{digest, TABLE_PREFIX, TRANSCRIPTS};
+use testkit::{golden, TestEnv};
+
+fn main() {
+    testkit::mock::run_if_invoked();
+    testkit::run_tests(&[
+        ("blastpal_matches_golden_file", blastpal_matches_golden_file),
+        ("blastpal_rerun_failed", blastpal_rerun_failed),
+        ("blastpal_loads_domtblout", blastpal_loads_domtblout),
+        (
+            "blastpal_skips_coords_beyond_u16",
+            blastpal_skips_coords_beyond_u16,
+        ),
+    ]);
+}
+
+fn golden_file(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name)
+}
+
+fn dump_blast_table(env: &TestEnv) -> String {
+    let conn = env.species_db();
+    let mut stmt = conn
+        .prepare("SELECT hmmsearch_id, query, target, score, evalue, log_evalue, start, end FROM orthograph_blast ORDER BY hmmsearch_id, score DESC, target")
+        .unwrap();
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                row.get::<_, u32>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, u32>(2)?,
+                row.get::<_, f64>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, f64>(5)?,
+                row.get::<_, u32>(6)?,
+                row.get::<_, u32>(7)?
+            ))
+        })
+        .unwrap();
+    rows.map(|r| r.unwrap()).collect()
+}
+
+fn blastpal_matches_golden_file() {
+    let env = TestEnv::new("blastpal", false);
+    env.run(env!("CARGO_BIN_EXE_blastpal"));
+    golden::assert_matches(&golden_file("blast.tsv"), &dump_blast_table(&env));
+
+    // Running again replaces the previous results
+    env.run(env!("CARGO_BIN_EXE_blastpal"));
+    golden::assert_matches(&golden_file("blast.tsv"), &dump_blast_table(&env));
+}
+
+fn blastpal_rerun_failed() {
+    let mut env = TestEnv::new("blastpal-rerun", false);
+
+    // Fail every blast
+    env.set("blast-program", "/nonexistent/blastp");
+    env.run(env!("CARGO_BIN_EXE_blastpal"));
+    assert_eq!(dump_blast_table(&env), "");
+    let failed = std::fs::read_to_string(env.output_file("blastpal-failed-items.txt")).unwrap();
+    assert_eq!(failed.lines().count(), 5, "failed items: {}", failed);
+
+    // Re-run only the failures
+    let blast = env.tools.blast.display().to_string();
+    env.set("blast-program", &blast);
+    env.set("rerun-failed", "1");
+    env.run(env!("CARGO_BIN_EXE_blastpal"));
+    golden::assert_matches(&golden_file("blast.tsv"), &dump_blast_table(&env));
+}
+
+// Hmm searches loaded from domtblout give the same blast results as the fixtures
+fn blastpal_loads_domtblout() {
+    let mut env = TestEnv::new("blastpal-domtblout", false);
+    env.species_db()
+        .execute(&format!("DELETE FROM {}_hmmsearch", TABLE_PREFIX), [])
+        .unwrap();
+
+    let hits: Vec<DomainHit> = TRANSCRIPTS
+        .iter()
+        .map(|t| {
+            let (ali_start, ali_end) = t.ali_coords();
+            DomainHit {
+                target_name: digest(&t.aa_sequence()),
+                target_accession: "-".to_string(),
+                query_name: t.gene_id.to_string(),
+                query_accession: "-".to_string(),
+                evalue: t.evalue.to_string(),
+                score: t.score,
+                domain_num: 1,
+                domain_total: 1,
+                c_evalue: t.evalue.to_string(),
+                i_evalue: t.evalue.to_string(),
+                domain_score: t.score,
+                hmm_start: 1,
+                hmm_end: t.protein.len() as u32,
+                ali_start: ali_start,
+                ali_end: ali_end,
+                env_start: ali_start,
+                env_end: ali_end,
+                ..Default::default()
+            }
+        })
+        .collect();
+    let filename = env.dir.join("hits.domtblout");
+    let mut fh = std::fs::File::create(&filename).unwrap();
+    hmmer::write_domtblout(&mut fh, &hits).unwrap();
+
+    env.set("hmmsearch-domtblout", &filename.display().to_string());
+    env.run(env!("CARGO_BIN_EXE_blastpal"));
+    golden::assert_matches(&golden_file("blast.tsv"), &dump_blast_table(&env));
+}
+
+// Blast results whose query coords don't fit the table are skipped, not wrapped
+fn blastpal_skips_coords_beyond_u16() {
+    let mut env = TestEnv::new("blastpal-u16-coords", false);
+
+    // Blast, then give the first result of each search a qend of 70000
+    let wrapper = env.dir.join("blastp-wrapper");
+    let script = r#"#!/bin/sh
+BLAST "$@" || exit 1
+while [ $# -gt 0 ]; do [ "$1" = "-out" ] && out="$2"; shift; done
+awk -F '\t' 'BEGIN { OFS = "\t" } !/^#/ && !done { $6 = 70000; done = 1 } { print }' "$out" > "$out.tmp"
+mv "$out.tmp" "$out"
+"#;
+    std::fs::write(
+        &wrapper,
+        script.replace("BLAST", &env.tools.blast.display().to_string()),
+    )
+    .unwrap();
+    std::fs::set_permissions(&wrapper, std::fs::Permissions::from_mode(0o755)).unwrap();
+    env.set("blast-program", &wrapper.display().to_string());
+    let output = env.run(env!("CARGO_BIN_EXE_blastpal"));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let skipped = stdout.matches("exceed the maximum of 65535").count();
+    assert!(skipped > 0, "{}", stdout);
+    let golden = std::fs::read_to_string(golden_file("blast.tsv")).unwrap();
+    let blast = dump_blast_table(&env);
+    assert_eq!(blast.lines().count(), golden.lines().count() - skipped);
+    assert!(blast.lines().all(|line| golden.contains(line)), "{}", blast);
+}
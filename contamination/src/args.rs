@@ -0,0 +1,112 @@
+use clap::{value_parser, App, Arg, ArgMatches};
+use std::any::Any;
+use std::path::Path;
+
+pub struct Args {
+    pub outputs: Vec<String>,
+    pub report: String,
+    pub read_counts: String,
+    pub min_identity: f32,
+    pub min_overlap: usize,
+    pub related_identity: f32,
+    pub min_shared_genes: usize,
+    pub min_ratio: f32,
+    pub header_separator: String,
+    pub quiet: bool,
+}
+
+impl Args {
+    pub fn new() -> Self {
+        let matches = build_cli().get_matches();
+
+        Self {
+            outputs: matches
+                .get_many::<String>("outputs")
+                .unwrap()
+                .map(|dir| dir.trim_end_matches("/").to_string())
+                .collect(),
+            report: value::<String>(&matches, "report").unwrap(),
+            read_counts: value::<String>(&matches, "read-counts").unwrap_or_default(),
+            min_identity: value::<f32>(&matches, "min-identity").unwrap(),
+            min_overlap: value::<usize>(&matches, "min-overlap").unwrap(),
+            related_identity: value::<f32>(&matches, "related-identity").unwrap(),
+            min_shared_genes: value::<usize>(&matches, "min-shared-genes").unwrap(),
+            min_ratio: value::<f32>(&matches, "min-ratio").unwrap(),
+            header_separator: value::<String>(&matches, "header-separator").unwrap(),
+            quiet: matches.is_present("quiet"),
+        }
+    }
+}
+
+pub fn build_cli() -> App<'static> {
+    App::new("contamination")
+        .version("0.1")
+        .author("Matt Dizak <matt@apexpl.io>")
+        .about("Find transcripts shared between species processed together, which are likely cross-contamination.")
+        .arg(Arg::new("outputs")
+            .required(true)
+            .multiple_values(true)
+            .min_values(2)
+            .value_parser(existing_dir)
+            .help("Output directories of the reporter, one per species"))
+        .arg(Arg::new("report")
+            .short('o')
+            .long("report")
+            .takes_value(true)
+            .default_value("contamination-report.txt")
+            .help("File to write the contamination report to"))
+        .arg(Arg::new("read-counts")
+            .long("read-counts")
+            .takes_value(true)
+            .help("Tab delimited file of species, transcript header and read count, used to find the source species"))
+        .arg(Arg::new("min-identity")
+            .long("min-identity")
+            .takes_value(true)
+            .default_value("0.98")
+            .value_parser(value_parser!(f32))
+            .help("Minimum identity of two transcripts to be flagged, from 0 to 1"))
+        .arg(Arg::new("min-overlap")
+            .long("min-overlap")
+            .takes_value(true)
+            .default_value("100")
+            .value_parser(value_parser!(usize))
+            .help("Minimum length in nt the two transcripts must overlap by"))
+        .arg(Arg::new("related-identity")
+            .long("related-identity")
+            .takes_value(true)
+            .default_value("0.95")
+            .value_parser(value_parser!(f32))
+            .help("Species whose median identity over their shared genes is at least this are close relatives, and not flagged"))
+        .arg(Arg::new("min-shared-genes")
+            .long("min-shared-genes")
+            .takes_value(true)
+            .default_value("10")
+            .value_parser(value_parser!(usize))
+            .help("Minimum shared genes to determine whether two species are close relatives, otherwise they're taken as distant"))
+        .arg(Arg::new("min-ratio")
+            .long("min-ratio")
+            .takes_value(true)
+            .default_value("2")
+            .value_parser(value_parser!(f32))
+            .help("Minimum ratio of read counts, coverage or scores between the two transcripts to name the source species"))
+        .arg(Arg::new("header-separator")
+            .long("header-separator")
+            .takes_value(true)
+            .default_value("|")
+            .help("Separator of the fields within the sequence headers, as given by header-separator within config.ini of the reporter"))
+        .arg(Arg::new("quiet")
+            .short('q')
+            .long("quiet")
+            .help("Only log warnings"))
+}
+
+fn value<T: Any + Clone + Send + Sync + 'static>(m: &ArgMatches, name: &str) -> Option<T> {
+    m.try_get_one::<T>(name).ok().flatten().cloned()
+}
+
+fn existing_dir(dir: &str) -> Result<String, String> {
+    match Path::new(dir).is_dir() {
+        true => Ok(dir.to_string()),
+        false => Err(format!("Directory does not exist at {}", dir)),
+    }
+}
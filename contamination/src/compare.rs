@@ -0,0 +1,242 @@
+use crate::outputs::{SpeciesOutput, Transcript};
+use crate::ARGS;
+use log::info;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+// Size of the k-mers seeding the comparison of two transcripts
+const KMER_SIZE: usize = 11;
+
+/// Pair of near identical transcripts of the same gene within two species.
+pub struct Flag {
+    pub gene_id: String,
+    pub species_a: usize,
+    pub header_a: String,
+    pub species_b: usize,
+    pub header_b: String,
+    pub identity: f32,
+    pub overlap: usize,
+    pub background: Option<f32>,
+    pub source: Option<usize>,
+    pub evidence: &'static str,
+}
+
+/**
+ * Compare the transcripts of each gene between every pair of species.  Pairs
+ * of transcripts at or above the minimum identity are flagged, unless the two
+ * species are close relatives, going by the median identity of their best
+ * transcripts over all shared genes.
+ */
+pub fn run(species: &[SpeciesOutput]) -> Vec<Flag> {
+    let pairs: Vec<(usize, usize)> = (0..species.len())
+        .flat_map(|a| (a + 1..species.len()).map(move |b| (a, b)))
+        .collect();
+
+    let mut flags: Vec<Flag> = pairs
+        .par_iter()
+        .flat_map_iter(|(a, b)| compare_species(&species, *a, *b))
+        .collect();
+    flags.sort_by(|x, y| {
+        (
+            &x.gene_id,
+            x.species_a,
+            x.species_b,
+            &x.header_a,
+            &x.header_b,
+        )
+            .cmp(&(
+                &y.gene_id,
+                y.species_a,
+                y.species_b,
+                &y.header_a,
+                &y.header_b,
+            ))
+    });
+    flags
+}
+
+fn compare_species(species: &[SpeciesOutput], a: usize, b: usize) -> Vec<Flag> {
+    let mut best_by_gene: Vec<f32> = Vec::new();
+    let mut candidates: Vec<Flag> = Vec::new();
+
+    // Go through shared genes
+    for (gene_id, transcripts_a) in &species[a].genes {
+        let transcripts_b = match species[b].genes.get(gene_id) {
+            Some(r) => r,
+            None => continue,
+        };
+
+        let mut best: Option<f32> = None;
+        for ta in transcripts_a {
+            for tb in transcripts_b {
+                let (identity, overlap) =
+                    match identity(ta.sequence.as_bytes(), tb.sequence.as_bytes()) {
+                        Some(r) if r.1 >= ARGS.min_overlap => r,
+                        _ => continue,
+                    };
+                best = Some(best.map_or(identity, |r| r.max(identity)));
+                if identity < ARGS.min_identity {
+                    continue;
+                }
+
+                let (source, evidence) = source_species(&ta, &tb);
+                candidates.push(Flag {
+                    gene_id: gene_id.to_string(),
+                    species_a: a,
+                    header_a: ta.header.to_string(),
+                    species_b: b,
+                    header_b: tb.header.to_string(),
+                    identity: identity,
+                    overlap: overlap,
+                    background: None,
+                    source: source.map(|x| if x == 0 { a } else { b }),
+                    evidence: evidence,
+                });
+            }
+        }
+
+        // Only genes with a compared pair of transcripts count towards the background
+        if let Some(r) = best {
+            best_by_gene.push(r);
+        }
+    }
+
+    // Check whether close relatives
+    let background = median(&mut best_by_gene, ARGS.min_shared_genes);
+    info!(
+        "Species {} and {} share {} compared genes, median identity {}, {} near identical transcripts",
+        species[a].name,
+        species[b].name,
+        best_by_gene.len(),
+        match background {
+            Some(r) => format!("{:.3}", r),
+            None => String::from("unknown"),
+        },
+        candidates.len()
+    );
+    if let Some(r) = background {
+        if r >= ARGS.related_identity {
+            return Vec::new();
+        }
+    }
+
+    for flag in candidates.iter_mut() {
+        flag.background = background;
+    }
+    candidates
+}
+
+/**
+ * Identity of the two transcripts along their best diagonal, being the one
+ * sharing the most k-mers, plus the length of their overlap on it.  Without
+ * gaps, as contamination gives near identical transcripts.
+ */
+fn identity(a: &[u8], b: &[u8]) -> Option<(f32, usize)> {
+    if a.len() < KMER_SIZE || b.len() < KMER_SIZE {
+        return None;
+    }
+
+    // Index k-mers of a
+    let mut index: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (i, kmer) in a.windows(KMER_SIZE).enumerate() {
+        index.entry(kmer).or_insert(Vec::new()).push(i);
+    }
+
+    // Count shared k-mers per diagonal
+    let mut diagonals: HashMap<isize, usize> = HashMap::new();
+    for (j, kmer) in b.windows(KMER_SIZE).enumerate() {
+        if let Some(positions) = index.get(kmer) {
+            for i in positions {
+                *diagonals.entry(*i as isize - j as isize).or_insert(0) += 1;
+            }
+        }
+    }
+    let (diagonal, _count) = diagonals
+        .into_iter()
+        .max_by_key(|(d, count)| (*count, -d.abs(), *d))?;
+
+    // Compare along diagonal
+    let start_b = (-diagonal).max(0) as usize;
+    let start_a = (start_b as isize + diagonal) as usize;
+    let overlap = (a.len() - start_a).min(b.len() - start_b);
+    let matches = (0..overlap)
+        .filter(|x| a[start_a + x] == b[start_b + x])
+        .count();
+    Some((matches as f32 / overlap as f32, overlap))
+}
+
+/**
+ * The transcript with the higher abundance is the likely source, going by
+ * read counts, then coverage within the headers, then hmm scores, whichever
+ * is first known for both.  Returns 0 for a, 1 for b, or None if the ratio
+ * between them is below the minimum, along with the evidence used.
+ */
+fn source_species(a: &Transcript, b: &Transcript) -> (Option<usize>, &'static str) {
+    let evidence = [
+        ("read-count", a.read_count, b.read_count),
+        ("coverage", a.coverage, b.coverage),
+        ("score", a.score, b.score),
+    ];
+
+    for (name, x, y) in evidence {
+        let (x, y) = match (x, y) {
+            (Some(x), Some(y)) => (x, y),
+            _ => continue,
+        };
+        if x > 0.0 && y > 0.0 && x.max(y) / x.min(y) >= ARGS.min_ratio {
+            return (Some(if x > y { 0 } else { 1 }), name);
+        }
+        return (None, name);
+    }
+    (None, "-")
+}
+
+// Median, if there are enough values
+fn median(values: &mut Vec<f32>, min_values: usize) -> Option<f32> {
+    if values.len() == 0 || values.len() < min_values {
+        return None;
+    }
+    values.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    let mid = values.len() / 2;
+    match values.len() % 2 {
+        0 => Some((values[mid - 1] + values[mid]) / 2.0),
+        _ => Some(values[mid]),
+    }
+}
+
+pub fn write_report(filename: &str, species: &[SpeciesOutput], flags: &[Flag]) {
+    let mut fh = match File::create(&filename) {
+        Ok(r) => r,
+        Err(e) => panic!("Unable to create report at {}, error: {}", filename, e),
+    };
+
+    let mut lines = vec![String::from("gene\tspecies_a\theader_a\tspecies_b\theader_b\tidentity\toverlap\tspecies_identity\tsuspected_source\tevidence\n")];
+    for flag in flags {
+        lines.push(format!(
+            "{}\t{}\t{}\t{}\t{}\t{:.3}\t{}\t{}\t{}\t{}\n",
+            flag.gene_id,
+            species[flag.species_a].name,
+            flag.header_a,
+            species[flag.species_b].name,
+            flag.header_b,
+            flag.identity,
+            flag.overlap,
+            match flag.background {
+                Some(r) => format!("{:.3}", r),
+                None => String::from("-"),
+            },
+            match flag.source {
+                Some(r) => species[r].name.to_string(),
+                None => String::from("-"),
+            },
+            flag.evidence
+        ));
+    }
+
+    for line in lines {
+        fh.write_all(&line.as_bytes())
+            .expect("Unable to write to contamination report");
+    }
+}
@@ -0,0 +1,54 @@
+use crate::args::Args;
+use env_logger::{Builder, Target};
+use lazy_static::lazy_static;
+use log::{info, LevelFilter};
+use std::io::Write;
+use std::time::Instant;
+
+mod args;
+mod compare;
+mod outputs;
+
+lazy_static! {
+    pub static ref ARGS: Args = Args::new();
+}
+
+fn main() {
+    // Init logger
+    init_logger();
+    let start_time = Instant::now();
+
+    // Load reporter outputs
+    let read_counts = outputs::load_read_counts(&ARGS.read_counts);
+    let species: Vec<outputs::SpeciesOutput> = ARGS
+        .outputs
+        .iter()
+        .map(|dir| outputs::load(&dir, &read_counts))
+        .collect();
+
+    // Compare, and write report
+    let flags = compare::run(&species);
+    compare::write_report(&ARGS.report, &species, &flags);
+    info!(
+        "Flagged {} suspected contaminations, report written to {}",
+        flags.len(),
+        ARGS.report
+    );
+
+    // Give processing time
+    let elapsed = start_time.elapsed();
+    info!("Completed processing in {:?} seconds.", elapsed.as_secs());
+}
+
+fn init_logger() {
+    let log_level = match ARGS.quiet {
+        true => LevelFilter::Warn,
+        false => LevelFilter::Info,
+    };
+
+    Builder::new()
+        .format(|buf, record| writeln!(buf, "{}: {}", record.level(), record.args()))
+        .filter(None, log_level)
+        .target(Target::Stderr)
+        .init();
+}
@@ -0,0 +1,173 @@
+use crate::ARGS;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+lazy_static! {
+    // Coverage within SPAdes and Velvet style headers, such as NODE_12_length_1450_cov_8.2
+    static ref COVERAGE_REGEX: Regex = Regex::new(r"_cov_([0-9]+(?:\.[0-9]+)?)").unwrap();
+}
+
+/// Final transcript of a gene within the nt/ files, along with its abundance if known.
+pub struct Transcript {
+    pub header: String,
+    pub sequence: String,
+    pub read_count: Option<f32>,
+    pub coverage: Option<f32>,
+    pub score: Option<f32>,
+}
+
+pub struct SpeciesOutput {
+    pub name: String,
+    pub genes: HashMap<String, Vec<Transcript>>,
+}
+
+/**
+ * Load the final nt sequences of the species from the output directory of
+ * the reporter, skipping those of the reference taxa.  The species name is
+ * taken from the sequence headers, or the name of the directory if there are
+ * none.
+ */
+pub fn load(dir: &str, read_counts: &HashMap<(String, String), f32>) -> SpeciesOutput {
+    let nt_dir = format!("{}/nt", dir);
+    let mut files: Vec<String> = match fs::read_dir(&nt_dir) {
+        Ok(r) => r
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.ends_with(".nt.fa"))
+            .collect(),
+        Err(e) => panic!("Unable to read nt directory at {}, error: {}", nt_dir, e),
+    };
+    files.sort();
+
+    // Go through files
+    let scores = load_scores(&dir);
+    let mut name = String::new();
+    let mut genes: HashMap<String, Vec<Transcript>> = HashMap::new();
+    for filename in files {
+        let gene_id = filename.trim_end_matches(".nt.fa").to_string();
+        let contents = match fs::read_to_string(format!("{}/{}", nt_dir, filename)) {
+            Ok(r) => r,
+            Err(e) => panic!("Unable to read {}/{}, error: {}", nt_dir, filename, e),
+        };
+
+        for (header, sequence) in read_fasta(&contents) {
+            // Skip reference taxa, which have no reciprocal reference taxon
+            let fields: Vec<&str> = header.split(ARGS.header_separator.as_str()).collect();
+            if fields.len() < 6 || fields[5] == "." {
+                continue;
+            }
+            if name == "" {
+                name = fields[1].to_string();
+            }
+
+            let transcript = fields[2].to_string();
+            genes
+                .entry(gene_id.to_string())
+                .or_insert(Vec::new())
+                .push(Transcript {
+                    read_count: read_counts
+                        .get(&(fields[1].to_string(), transcript.to_string()))
+                        .copied(),
+                    coverage: COVERAGE_REGEX
+                        .captures(&transcript)
+                        .and_then(|caps| caps[1].parse::<f32>().ok()),
+                    score: scores
+                        .get(&(gene_id.to_string(), transcript.to_string()))
+                        .copied(),
+                    header: transcript,
+                    sequence: sequence.to_uppercase(),
+                });
+        }
+    }
+
+    // Default name to directory
+    if name == "" {
+        name = match Path::new(dir).file_name() {
+            Some(r) => r.to_string_lossy().to_string(),
+            None => dir.to_string(),
+        };
+        warn!(
+            "No sequences found within {}, using species name {}",
+            dir, name
+        );
+    }
+    info!(
+        "Loaded {} genes of species {} from {}",
+        genes.len(),
+        name,
+        dir
+    );
+
+    SpeciesOutput {
+        name: name,
+        genes: genes,
+    }
+}
+
+// Best hmm score of each gene and base header, from best-reciprocal-hits.txt
+fn load_scores(dir: &str) -> HashMap<(String, String), f32> {
+    let mut scores: HashMap<(String, String), f32> = HashMap::new();
+    let contents = match fs::read_to_string(format!("{}/best-reciprocal-hits.txt", dir)) {
+        Ok(r) => r,
+        Err(_e) => return scores,
+    };
+
+    for line in contents.lines() {
+        let cols: Vec<&str> = line.split("\t").collect();
+        if cols.len() < 5 {
+            continue;
+        }
+        let score = match cols[4].parse::<f32>() {
+            Ok(r) => r,
+            Err(_e) => continue,
+        };
+        let header = cols[1].split(" ").next().unwrap_or("").to_string();
+        let best = scores.entry((cols[0].to_string(), header)).or_insert(score);
+        *best = best.max(score);
+    }
+    scores
+}
+
+/**
+ * Load read counts keyed by species and transcript header, from a tab
+ * delimited file of species, header and count.  Lines starting with # are
+ * skipped.
+ */
+pub fn load_read_counts(filename: &str) -> HashMap<(String, String), f32> {
+    let mut counts: HashMap<(String, String), f32> = HashMap::new();
+    if filename == "" {
+        return counts;
+    }
+    let contents = match fs::read_to_string(&filename) {
+        Ok(r) => r,
+        Err(e) => panic!("Unable to read read counts at {}, error: {}", filename, e),
+    };
+
+    for line in contents.lines() {
+        let cols: Vec<&str> = line.trim_end().split("\t").collect();
+        if line.starts_with("#") || cols.len() < 3 {
+            continue;
+        }
+        match cols[2].parse::<f32>() {
+            Ok(r) => counts.insert((cols[0].to_string(), cols[1].to_string()), r),
+            Err(_e) => panic!("Invalid read count '{}' within {}", cols[2], filename),
+        };
+    }
+    counts
+}
+
+fn read_fasta(contents: &str) -> Vec<(String, String)> {
+    let mut records: Vec<(String, String)> = Vec::new();
+    for line in contents.lines() {
+        if let Some(header) = line.strip_prefix(">") {
+            records.push((header.trim_end().to_string(), String::new()));
+        } else if let Some((_header, sequence)) = records.last_mut() {
+            sequence.push_str(line.trim());
+        }
+    }
+    records
+}
@@ -0,0 +1,243 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const GENES: [&str; 4] = ["EOG001", "EOG002", "EOG003", "EOG004"];
+
+// Deterministic nt sequence of the given length
+fn random_seq(seed: u64, len: usize) -> String {
+    let mut state = seed
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ['A', 'C', 'G', 'T'][(state >> 33) as usize % 4]
+        })
+        .collect()
+}
+
+// Change every nth base, giving an identity of 1 - 1/n
+fn mutate(seq: &str, n: usize) -> String {
+    seq.chars()
+        .enumerate()
+        .map(|(x, c)| match (x % n == n - 1, c) {
+            (false, c) => c,
+            (true, 'A') => 'C',
+            (true, _) => 'A',
+        })
+        .collect()
+}
+
+/**
+ * Write a reporter output directory holding the nt file of each gene, with
+ * the reference taxa first as written by the reporter.
+ */
+fn write_output(dir: &Path, species: &str, transcripts: &[(&str, String, String)]) {
+    fs::create_dir_all(dir.join("nt")).unwrap();
+    for (gene, header, seq) in transcripts {
+        let contents = format!(
+            ">{gene}|TaxA|TaxA_{gene}|1-{len}|.|.|.\n{ref_seq}\n>{gene}|{species}|{header}|1-{len}|.|TaxA|0.900\n{seq}\n",
+            gene = gene,
+            len = seq.len(),
+            ref_seq = random_seq(99, seq.len()),
+            species = species,
+            header = header,
+            seq = seq
+        );
+        fs::write(dir.join(format!("nt/{}.nt.fa", gene)), contents).unwrap();
+    }
+}
+
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "orthograph-contamination-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn flags_shared_transcripts_between_distant_species() {
+    let dir = test_dir("flags");
+
+    // Species B is distant from A, but carries A's transcript of EOG001
+    let seqs_a: Vec<String> = (0..GENES.len())
+        .map(|x| random_seq(x as u64, 300))
+        .collect();
+    let header = |x: usize, cov: &str| format!("NODE_{}_length_300_cov_{}_g{}_i1", x, cov, x);
+    let species_a: Vec<(&str, String, String)> = GENES
+        .iter()
+        .enumerate()
+        .map(|(x, g)| (*g, header(x, "50.0"), seqs_a[x].to_string()))
+        .collect();
+    let species_b: Vec<(&str, String, String)> = GENES
+        .iter()
+        .enumerate()
+        .map(|(x, g)| match x {
+            0 => (*g, header(x, "2.0"), seqs_a[x].to_string()),
+            _ => (*g, header(x, "30.0"), random_seq(100 + x as u64, 300)),
+        })
+        .collect();
+
+    // Species C is a close relative of A
+    let species_c: Vec<(&str, String, String)> = GENES
+        .iter()
+        .enumerate()
+        .map(|(x, g)| (*g, header(x, "40.0"), mutate(&seqs_a[x], 40)))
+        .collect();
+
+    write_output(&dir.join("spA"), "spA", &species_a);
+    write_output(&dir.join("spB"), "spB", &species_b);
+    write_output(&dir.join("spC"), "spC", &species_c);
+
+    let report = dir.join("report.txt");
+    let output = Command::new(env!("CARGO_BIN_EXE_contamination"))
+        .args([
+            dir.join("spA").to_str().unwrap(),
+            dir.join("spB").to_str().unwrap(),
+            dir.join("spC").to_str().unwrap(),
+            "--report",
+            report.to_str().unwrap(),
+            "--min-identity",
+            "0.97",
+            "--min-shared-genes",
+            "3",
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Unrelated transcripts of the other genes aren't compared, leaving B too few genes
+    let report = fs::read_to_string(&report).unwrap();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", report);
+    assert_eq!(
+        lines[1],
+        format!(
+            "EOG001\tspA\t{}\tspB\t{}\t1.000\t300\t-\tspA\tcoverage",
+            header(0, "50.0"),
+            header(0, "2.0")
+        )
+    );
+    assert_eq!(
+        lines[2],
+        format!(
+            "EOG001\tspB\t{}\tspC\t{}\t0.977\t300\t-\tspC\tcoverage",
+            header(0, "2.0"),
+            header(0, "40.0")
+        )
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn source_from_read_counts() {
+    let dir = test_dir("read-counts");
+    let seq = random_seq(7, 300);
+    write_output(
+        &dir.join("spA"),
+        "spA",
+        &[(
+            "EOG001",
+            String::from("TRINITY_DN1_c0_g1_i1"),
+            seq.to_string(),
+        )],
+    );
+    write_output(
+        &dir.join("spB"),
+        "spB",
+        &[(
+            "EOG001",
+            String::from("TRINITY_DN5_c0_g1_i1"),
+            seq.to_string(),
+        )],
+    );
+    let counts = dir.join("counts.tsv");
+    fs::write(
+        &counts,
+        "# species\theader\tcount\nspA\tTRINITY_DN1_c0_g1_i1\t3\nspB\tTRINITY_DN5_c0_g1_i1\t480\n",
+    )
+    .unwrap();
+
+    let report = dir.join("report.txt");
+    let output = Command::new(env!("CARGO_BIN_EXE_contamination"))
+        .args([
+            dir.join("spA").to_str().unwrap(),
+            dir.join("spB").to_str().unwrap(),
+            "--report",
+            report.to_str().unwrap(),
+            "--read-counts",
+            counts.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Too few shared genes to tell whether close relatives
+    let report = fs::read_to_string(&report).unwrap();
+    assert!(
+        report.ends_with("EOG001\tspA\tTRINITY_DN1_c0_g1_i1\tspB\tTRINITY_DN5_c0_g1_i1\t1.000\t300\t-\tspB\tread-count\n"),
+        "{}",
+        report
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn custom_header_separator() {
+    let dir = test_dir("header-separator");
+    let seq = random_seq(11, 300);
+    for (species, header) in [
+        ("spA", "NODE_1_length_300_cov_60.0_g1_i1"),
+        ("spB", "NODE_9_length_300_cov_3.0_g9_i1"),
+    ] {
+        write_output(
+            &dir.join(species),
+            species,
+            &[("EOG001", String::from(header), seq.to_string())],
+        );
+        let file = dir.join(species).join("nt/EOG001.nt.fa");
+        let contents = fs::read_to_string(&file).unwrap();
+        fs::write(&file, contents.replace("|", ";")).unwrap();
+    }
+
+    let report = dir.join("report.txt");
+    let output = Command::new(env!("CARGO_BIN_EXE_contamination"))
+        .args([
+            dir.join("spA").to_str().unwrap(),
+            dir.join("spB").to_str().unwrap(),
+            "--report",
+            report.to_str().unwrap(),
+            "--header-separator",
+            ";",
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let report = fs::read_to_string(&report).unwrap();
+    assert!(
+        report.ends_with("EOG001\tspA\tNODE_1_length_300_cov_60.0_g1_i1\tspB\tNODE_9_length_300_cov_3.0_g9_i1\t1.000\t300\t-\tspA\tcoverage\n"),
+        "{}",
+        report
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
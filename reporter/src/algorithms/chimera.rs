@@ -0,0 +1,181 @@
+use crate::reporter::ReporterKit;
+use crate::stats::Stats;
+use biotools::db::sqlite::TBL_HITS;
+use biotools::CONFIG;
+use log::{info, warn};
+use rusqlite::Error;
+use std::ops::Range;
+
+pub struct ChimeraCandidate {
+    pub id: u32,
+    pub gene_id: String,
+    env_start: u16,
+    env_end: u16,
+    pub header_base: String,
+    pub header_revcomp: u8,
+    pub header_translate: u8,
+}
+
+// Region of a chimeric transcript taken by one gene, in nt coords of the forward strand
+struct Segment<'a> {
+    hit: &'a ChimeraCandidate,
+    range: Range<u32>,
+    bounds: Range<u32>,
+}
+
+/**
+ * Find chimeric transcripts, which carry reciprocal hits to different genes on
+ * non-overlapping regions.  Going by score, a hit becomes a segment of the
+ * transcript unless its gene already has one, or it overlaps one.  Under the
+ * split chimera mode each segment is bounded by the midpoints to its
+ * neighbouring segments, and every hit of the transcript is given the bounds
+ * of the segment it falls within.  The later checks then treat each segment
+ * as its own transcript, and no orf can extend into another segment.  Under
+ * the discard mode all hits of the transcript are discarded.
+ */
+pub fn check(kit: &ReporterKit, stats: &mut Stats) {
+    let candidates = match gather_candidates(&kit) {
+        Ok(r) => r,
+        Err(e) => panic!("Unable to gather hits for chimera check, error: {}", e),
+    };
+
+    // Go through transcripts
+    let mut start = 0;
+    while start < candidates.len() {
+        let end = start
+            + candidates[start..]
+                .iter()
+                .take_while(|c| c.header_base == candidates[start].header_base)
+                .count();
+        let transcript = &candidates[start..end];
+        start = end;
+
+        // Skip, if only one gene
+        if transcript
+            .iter()
+            .all(|c| c.gene_id == transcript[0].gene_id)
+        {
+            continue;
+        }
+        let est_len: u32 = match kit.storage.get_est_sequence(&transcript[0].header_base) {
+            Some(r) => r.len() as u32,
+            None => continue,
+        };
+
+        // Get segments
+        let mut segments: Vec<Segment> = Vec::new();
+        for cand in transcript {
+            let range = forward_range(&cand, est_len);
+            if segments
+                .iter()
+                .any(|s| s.hit.gene_id == cand.gene_id || overlaps(&s.range, &range))
+            {
+                continue;
+            }
+            segments.push(Segment {
+                hit: cand,
+                range: range,
+                bounds: 0..0,
+            });
+        }
+        if segments.len() < 2 {
+            continue;
+        }
+        let genes: Vec<&str> = segments.iter().map(|s| s.hit.gene_id.as_str()).collect();
+        warn!(
+            "Transcript {} is chimeric with non-overlapping hits to genes {}",
+            transcript[0].header_base,
+            genes.join(", ")
+        );
+
+        // Discard, if needed
+        if CONFIG.search.chimera_mode == "discard" {
+            for cand in transcript {
+                stats.discard_chimera(&kit, &cand);
+            }
+            continue;
+        }
+
+        // Get bounds of segments
+        segments.sort_by_key(|s| s.range.start);
+        for x in 0..segments.len() {
+            let lower = match x {
+                0 => 0,
+                _ => (segments[x - 1].range.end + segments[x].range.start) / 2,
+            };
+            let upper = match segments.get(x + 1) {
+                Some(next) => (segments[x].range.end + next.range.start) / 2,
+                None => est_len,
+            };
+            segments[x].bounds = lower..upper;
+            info!(
+                "Splitting gene {} of chimeric transcript {} to segment {}-{}",
+                segments[x].hit.gene_id, segments[x].hit.header_base, lower, upper
+            );
+            stats.split_chimera(&segments[x].hit);
+        }
+        stats.add_split_chimera();
+
+        // Give each hit the bounds of the segment its midpoint falls within
+        let sql = format!(
+            "UPDATE {} SET segment_start = ?, segment_end = ? WHERE id = ?",
+            *TBL_HITS
+        );
+        for cand in transcript {
+            let range = forward_range(&cand, est_len);
+            let midpoint = (range.start + range.end) / 2;
+            let bounds = match segments.iter().find(|s| s.bounds.contains(&midpoint)) {
+                Some(r) => &r.bounds,
+                None => &segments.last().unwrap().bounds,
+            };
+            if let Err(e) = kit
+                .memdb
+                .execute(&sql, [&bounds.start, &bounds.end, &cand.id])
+            {
+                panic!(
+                    "Unable to update segment of chimeric hit id# {}, error: {}",
+                    cand.id, e
+                );
+            }
+        }
+    }
+}
+
+// Env coords of the hit as nt coords of the forward strand, shifted by the reading frame
+fn forward_range(cand: &ChimeraCandidate, est_len: u32) -> Range<u32> {
+    let frame = (cand.header_translate as u32).saturating_sub(1);
+    let start = ((cand.env_start as u32 - 1) * 3 + frame).min(est_len);
+    let end = (cand.env_end as u32 * 3 + frame).min(est_len);
+    match cand.header_revcomp {
+        0 => start..end,
+        _ => est_len.saturating_sub(end)..est_len.saturating_sub(start),
+    }
+}
+
+fn overlaps(a: &Range<u32>, b: &Range<u32>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+fn gather_candidates(kit: &ReporterKit) -> Result<Vec<ChimeraCandidate>, Error> {
+    let sql = format!("SELECT id,gene_id,env_start,env_end,header_base,header_revcomp,header_translate FROM {} ORDER BY header_base,score DESC,id", *TBL_HITS);
+    let mut stmt = match kit.memdb.prepare(&sql) {
+        Ok(res) => res,
+        Err(e) => panic!(
+            "Unable to prepare SQL statement to retrieve hits for chimera check, error: {}",
+            e
+        ),
+    };
+
+    let rows = stmt.query_map([], |row| {
+        Ok(ChimeraCandidate {
+            id: row.get(0)?,
+            gene_id: row.get(1)?,
+            env_start: row.get(2)?,
+            env_end: row.get(3)?,
+            header_base: row.get(4)?,
+            header_revcomp: row.get(5)?,
+            header_translate: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
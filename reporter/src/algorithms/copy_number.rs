@@ -0,0 +1,118 @@
+use crate::reporter::ReporterKit;
+use crate::stats::Stats;
+use biotools::db::sqlite::TBL_HITS;
+use biotools::CONFIG;
+use log::info;
+use rusqlite::Error;
+use std::ops::Range;
+
+struct CopyCandidate {
+    id: u32,
+    gene_id: String,
+    hmm_start: u16,
+    hmm_end: u16,
+}
+
+/**
+ * Number the copies of each gene under paralog mode.  Going by score, each hit
+ * is given the lowest copy number not already taken by a better hit it
+ * overlaps within the hmm, so fragments of the same copy share its number.
+ */
+pub fn assign(kit: &ReporterKit, stats: &mut Stats) {
+    let candidates = match gather_candidates(&kit) {
+        Ok(r) => r,
+        Err(e) => panic!("Unable to gather hits for copy numbers, error: {}", e),
+    };
+
+    // Update within one transaction
+    let tx = match kit.memdb.unchecked_transaction() {
+        Ok(res) => res,
+        Err(e) => panic!(
+            "Unable to begin transaction to update copy numbers, error: {}",
+            e
+        ),
+    };
+    let sql = format!("UPDATE {} SET copy_num = ? WHERE id = ?", *TBL_HITS);
+    let mut stmt = match tx.prepare(&sql) {
+        Ok(res) => res,
+        Err(e) => panic!(
+            "Unable to prepare SQL statement to update copy numbers, error: {}",
+            e
+        ),
+    };
+
+    // Go through genes
+    let mut start = 0;
+    while start < candidates.len() {
+        let end = start
+            + candidates[start..]
+                .iter()
+                .take_while(|c| c.gene_id == candidates[start].gene_id)
+                .count();
+        let gene = &candidates[start..end];
+        start = end;
+
+        let mut numbered: Vec<(Range<u16>, u16)> = Vec::new();
+        for cand in gene {
+            let range = cand.hmm_start..cand.hmm_end + 1;
+            let taken: Vec<u16> = numbered
+                .iter()
+                .filter(|(r, _copy)| {
+                    match biotools::get_overlap_percent(r.clone(), range.clone(), false) {
+                        Some(percent) => percent >= CONFIG.search.hmm_overlap_threshold,
+                        None => false,
+                    }
+                })
+                .map(|(_r, copy)| *copy)
+                .collect();
+            let copy = (1..).find(|c| !taken.contains(c)).unwrap();
+            numbered.push((range, copy));
+
+            if let Err(e) = stmt.execute([&copy as &dyn rusqlite::ToSql, &cand.id]) {
+                panic!(
+                    "Unable to update copy number of hit id# {}, error: {}",
+                    cand.id, e
+                );
+            }
+        }
+
+        let copies = numbered.iter().map(|(_r, copy)| *copy).max().unwrap();
+        info!(
+            "Gene {} has {} copies within {} transcripts",
+            gene[0].gene_id,
+            copies,
+            gene.len()
+        );
+        stats.add_gene_copies(&gene[0].gene_id, copies, gene.len());
+    }
+
+    // Commit
+    drop(stmt);
+    if let Err(e) = tx.commit() {
+        panic!("Unable to commit copy numbers, error: {}", e);
+    }
+}
+
+fn gather_candidates(kit: &ReporterKit) -> Result<Vec<CopyCandidate>, Error> {
+    let sql = format!(
+        "SELECT id,gene_id,hmm_start,hmm_end FROM {} ORDER BY gene_id,score DESC,id",
+        *TBL_HITS
+    );
+    let mut stmt = match kit.memdb.prepare(&sql) {
+        Ok(res) => res,
+        Err(e) => panic!(
+            "Unable to prepare SQL statement to retrieve hits for copy numbers, error: {}",
+            e
+        ),
+    };
+
+    let rows = stmt.query_map([], |row| {
+        Ok(CopyCandidate {
+            id: row.get(0)?,
+            gene_id: row.get(1)?,
+            hmm_start: row.get(2)?,
+            hmm_end: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
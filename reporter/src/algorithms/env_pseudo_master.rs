@@ -1,6 +1,7 @@
 use crate::reporter::ReporterKit;
 use crate::stats::Stats;
 use biotools::db::sqlite::TBL_HITS;
+use biotools::headers;
 use biotools::CONFIG;
 use log::{info, warn};
 use rusqlite::{Error, Statement};
@@ -17,6 +18,8 @@ pub struct EnvCandidate {
     pub hdr_revcomp: u8,
     pub hdr_translate: u8,
     rank: u8,
+    segment_start: u32,
+    segment_end: u32,
 }
 
 pub fn check(kit: &ReporterKit, mut stats: &mut Stats) -> Result<bool, Error> {
@@ -51,13 +54,24 @@ pub fn check(kit: &ReporterKit, mut stats: &mut Stats) -> Result<bool, Error> {
             hdr_revcomp: row.get(7)?,
             hdr_translate: row.get(8)?,
             rank: row.get(9)?,
+            segment_start: row.get(10)?,
+            segment_end: row.get(11)?,
         };
 
-        // Add to hits
-        let header = format!("{}", hit.header_base);
+        // Add to hits, with each segment of a split chimera as its own transcript
+        let header = match hit.segment_end {
+            0 => format!("{}", hit.header_base),
+            _ => format!(
+                "{}:{}-{}",
+                hit.header_base, hit.segment_start, hit.segment_end
+            ),
+        };
         hits.entry(header).or_insert(Vec::new()).push(hit);
     }
 
+    // Keep one transcript per locus of each gene
+    choose_locus_representatives(&kit, &mut hits, &mut stats);
+
     // Process
     let results: Vec<bool> = hits
         .iter()
@@ -70,6 +84,56 @@ pub fn check(kit: &ReporterKit, mut stats: &mut Stats) -> Result<bool, Error> {
     Ok(true)
 }
 
+/**
+ * Isoforms of the same locus, as derived from the header convention, are
+ * redundant hits of a gene.  Only the transcript with the best scoring hit
+ * of the gene is kept as representative of the locus, and the hits of its
+ * other isoforms for that gene are discarded.
+ */
+fn choose_locus_representatives(
+    kit: &ReporterKit,
+    hits: &mut HashMap<String, Vec<EnvCandidate>>,
+    stats: &mut Stats,
+) {
+    // Get best transcript of each locus and gene
+    let mut best: HashMap<(String, String), (f32, String)> = HashMap::new();
+    for candidates in hits.values() {
+        let header_base = &candidates[0].header_base;
+        let locus = headers::locus(&header_base);
+        for cand in candidates {
+            let key = (locus.to_string(), cand.gene_id.to_string());
+            let is_better = match best.get(&key) {
+                Some((score, header)) => {
+                    cand.score > *score || (cand.score == *score && header_base < header)
+                }
+                None => true,
+            };
+            if is_better {
+                best.insert(key, (cand.score, header_base.to_string()));
+            }
+        }
+    }
+
+    // Discard hits of the other isoforms
+    for candidates in hits.values_mut() {
+        let header_base = candidates[0].header_base.to_string();
+        let locus = headers::locus(&header_base);
+        candidates.retain(|cand| {
+            let (_score, header) = &best[&(locus.to_string(), cand.gene_id.to_string())];
+            if *header == header_base {
+                return true;
+            }
+            info!(
+                "Transcript {} is an isoform of {} within locus {} for gene {}, discarding transcript.",
+                header_base, header, locus, cand.gene_id
+            );
+            stats.discard_isoform(&kit, &cand);
+            false
+        });
+    }
+    hits.retain(|_header_base, candidates| candidates.len() > 0);
+}
+
 fn process_candidates(
     kit: &ReporterKit,
     candidates: &Vec<EnvCandidate>,
@@ -202,8 +266,8 @@ fn prepare_sql(kit: &ReporterKit) -> Statement {
     // Set sql
     let sql = format!("
         WITH top_headers AS (
-        SELECT id,hmmsearch_id,gene_id,score,env_start,env_end,header_base,header_revcomp,header_translate, RANK() OVER(PARTITION BY header_base ORDER BY score DESC) rank FROM {}) 
-        SELECT id,hmmsearch_id,gene_id,score,env_start,env_end,header_base,header_revcomp,header_translate,rank FROM top_headers ORDER BY header_base,rank
+        SELECT id,hmmsearch_id,gene_id,score,env_start,env_end,header_base,header_revcomp,header_translate,segment_start,segment_end, RANK() OVER(PARTITION BY header_base,segment_start ORDER BY score DESC) rank FROM {}) 
+        SELECT id,hmmsearch_id,gene_id,score,env_start,env_end,header_base,header_revcomp,header_translate,rank,segment_start,segment_end FROM top_headers ORDER BY header_base,segment_start,rank
     ", *TBL_HITS);
 
     // Prepare sql
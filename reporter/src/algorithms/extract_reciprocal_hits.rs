@@ -1,63 +1,56 @@
 use crate::algorithms::is_reciprocal_hit;
-use crate::models::HmmSearch;
+use crate::models::{HmmSearch, Reciprocity};
 use crate::reporter::ReporterKit;
 use crate::stats::Stats;
-use biotools::db::sqlite::{TBL_ESTS, TBL_HITS, TBL_HMMSEARCH, TBL_LOGS, TBL_SEQUENCE_PAIRS};
+use biotools::db::sqlite::TBL_HITS;
 use biotools::CONFIG;
 use log::{info, warn};
+use rayon::prelude::*;
+use rusqlite::Error;
 use rusqlite::ToSql;
-use rusqlite::{Error, Statement};
 
 pub fn run(kit: &ReporterKit) -> Result<Stats, Error> {
-    // Prepare and execute sql
-    let mut stmt = prepare_sql(&kit);
-    let mut rows = match stmt.query([&kit.species_id, &kit.set_id]) {
+    // Get sequence pairs of genes within the set
+    let pairs = match kit.db.get_sequence_pairs_in_set(&kit.set_id) {
         Ok(res) => res,
-        Err(e) => panic!(
-            "Unable to execute sql while fetching filtered scores from database, error: {}",
-            e
-        ),
-    };
-
-    // Prepare insert sql statement
-    let insert_sql = format!("INSERT INTO {} (hmmsearch_id, taxid, aaseq_id, ntseq_id, blast_target, gene_id, score, digest, evalue, hmm_start, hmm_end, ali_start, ali_end, env_start, env_end, blast_start, blast_end, header_base, header_full, header_revcomp, header_translate, non_orf_sequence) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", *TBL_HITS);
-    let mut insert_stmt = match kit.memdb.prepare(&insert_sql) {
-        Ok(res) => res,
-        Err(e) => panic!(
-            "Unable to prepare SQL statement for insert into hits table, error: {}",
-            e
-        ),
+        Err(e) => panic!("Unable to retrieve sequence pairs within set, error: {}", e),
     };
 
     // Instantiate stats
     let mut stats = Stats::new();
 
-    // Go through rows
-    loop {
-        // Get next row
-        let row = match rows.next()? {
-            Some(r) => r,
-            None => break,
+    // Go through hmm searches, highest score first
+    let mut candidates: Vec<HmmSearch> = Vec::new();
+    for (search, est) in kit.storage.get_hmmsearches(kit.species_id) {
+        // Skip, if gene not within set
+        let (aaseq_id, ntseq_id) = match pairs.get(&search.query) {
+            Some(r) => *r,
+            None => continue,
         };
 
         // Define search
         let cand = HmmSearch {
-            gene_id: row.get(0)?,
-            aaseq_id: row.get(1)?,
-            ntseq_id: row.get(2)?,
-            taxid: row.get(3)?,
-            hmm_id: row.get(4)?,
-            score: row.get(5)?,
-            digest: row.get(6)?,
-            evalue: row.get(7)?,
-            hmm_start: row.get(8)?,
-            hmm_end: row.get(9)?,
-            ali_start: row.get(11)?,
-            ali_end: row.get(12)?,
-            env_start: row.get(13)?,
-            env_end: row.get(14)?,
-            header: row.get(15)?,
-            non_orf_sequence: row.get(16)?,
+            gene_id: search.query.to_string(),
+            aaseq_id: aaseq_id,
+            ntseq_id: ntseq_id,
+            taxid: search.taxid as u16,
+            hmm_id: search.id,
+            score: search.score as f32,
+            digest: search.target.to_string(),
+            evalue: search.evalue.to_string(),
+            hmm_start: search.hmm_start as u16,
+            hmm_end: search.hmm_end as u16,
+            ali_start: search.ali_start as u16,
+            ali_end: search.ali_end as u16,
+            env_start: search.env_start as u16,
+            env_end: search.env_end as u16,
+            non_orf_sequence: est
+                .sequence
+                .chars()
+                .skip((search.ali_start as usize).saturating_sub(1))
+                .take((search.ali_end + 1).saturating_sub(search.ali_start) as usize)
+                .collect(),
+            header: est.header,
         };
 
         // Skip, if not in list of wanted genes
@@ -68,8 +61,42 @@ pub fn run(kit: &ReporterKit) -> Result<Stats, Error> {
             continue;
         }
 
-        // Check if reciprocal hit
-        let blast = match is_reciprocal_hit::check(&kit, &cand) {
+        candidates.push(cand);
+    }
+
+    // Check if reciprocal hits, in parallel
+    let refs = &kit.refs;
+    let results: Vec<(Option<Reciprocity>, Vec<String>)> = kit.pool.install(|| {
+        candidates
+            .par_iter()
+            .map(|cand| is_reciprocal_hit::check(&refs, &cand))
+            .collect()
+    });
+
+    // Insert all hits within one transaction
+    let tx = match kit.memdb.unchecked_transaction() {
+        Ok(res) => res,
+        Err(e) => panic!(
+            "Unable to begin transaction to insert into hits table, error: {}",
+            e
+        ),
+    };
+
+    // Prepare insert sql statement
+    let insert_sql = format!("INSERT INTO {} (hmmsearch_id, taxid, aaseq_id, ntseq_id, blast_target, gene_id, score, digest, evalue, hmm_start, hmm_end, ali_start, ali_end, env_start, env_end, blast_start, blast_end, header_base, header_full, header_revcomp, header_translate, non_orf_sequence, confidence) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", *TBL_HITS);
+    let mut insert_stmt = match tx.prepare(&insert_sql) {
+        Ok(res) => res,
+        Err(e) => panic!(
+            "Unable to prepare SQL statement for insert into hits table, error: {}",
+            e
+        ),
+    };
+
+    // Go through results
+    for (cand, (recip, trace)) in candidates.iter().zip(results) {
+        // Write decision trace, and skip if not reciprocal
+        stats.write_trace(&cand, &trace);
+        let recip = match recip {
             Some(r) => r,
             None => {
                 warn!("No orthology detected for {}.", cand.gene_id);
@@ -80,8 +107,8 @@ pub fn run(kit: &ReporterKit) -> Result<Stats, Error> {
 
         // Success message
         info!(
-            "Orthology detected for {}! Queueing for further checks: {}[{}:{}] to {}.",
-            cand.gene_id, cand.header, cand.hmm_start, cand.hmm_end, cand.gene_id
+            "Orthology detected for {}! Queueing for further checks: {}[{}:{}] to {}, confidence {}.",
+            cand.gene_id, cand.header, cand.hmm_start, cand.hmm_end, cand.gene_id, recip.confidence
         );
 
         // Translate header
@@ -93,7 +120,7 @@ pub fn run(kit: &ReporterKit) -> Result<Stats, Error> {
             &cand.taxid,
             &cand.aaseq_id,
             &cand.ntseq_id,
-            &blast.0,
+            &recip.blast_target,
             &cand.gene_id,
             &cand.score,
             &cand.digest,
@@ -104,13 +131,14 @@ pub fn run(kit: &ReporterKit) -> Result<Stats, Error> {
             &cand.ali_end,
             &cand.env_start,
             &cand.ali_end,
-            &blast.1,
-            &blast.2,
+            &recip.blast_start,
+            &recip.blast_end,
             &hdr_base.trim_end(),
             &cand.header.trim_end_matches(" "),
             &hdr_revcomp,
             &hdr_translate,
             &cand.non_orf_sequence,
+            &recip.confidence,
         ]) {
             Ok(res) => res,
             Err(e) => panic!(
@@ -120,58 +148,13 @@ pub fn run(kit: &ReporterKit) -> Result<Stats, Error> {
         };
     }
 
-    // Return
-    Ok(stats)
-}
-
-fn prepare_sql(kit: &ReporterKit) -> Statement {
-    let sql = format!(
-        "SELECT DISTINCT 
-        l.ortholog_gene_id,
-        p.aa_seq,
-        p.nt_seq,
-        s.taxid,
-        s.id,
-        s.score,
-        s.target,
-        s.evalue,
-        s.hmm_start,
-        s.hmm_end,
-        (s.ali_end - s.ali_start) ali_length,
-        s.ali_start,
-        s.ali_end,
-        s.env_start,
-        s.env_end,
-        e.header,
-        substr(e.sequence, s.ali_start, (s.ali_end - s.ali_start + 1))
-        FROM {} s, {} l, {} e, {} p  
-        WHERE 
-            s.target = e.digest AND 
-            s.query = l.ortholog_gene_id AND  
-            l.sequence_pair = p.id AND 
-            e.digest IS NOT NULL AND 
-            s.score >= {} AND 
-            s.taxid = ? AND 
-            l.setid = ? AND 
-            (s.ali_end - s.ali_start) + 1 >= {} 
-        GROUP BY s.id ORDER BY s.score DESC",
-        *TBL_HMMSEARCH,
-        *TBL_LOGS,
-        *TBL_ESTS,
-        *TBL_SEQUENCE_PAIRS,
-        &CONFIG.search.hmmsearch_threshold,
-        &CONFIG.search.min_transcript_length
-    );
-
-    // Prepare
-    let stmt = match kit.db.conn.prepare(&sql) {
-        Ok(res) => res,
-        Err(e) => panic!(
-            "Unable to prepare SQL statement to extract reciprocal hits, error: {}",
-            e
-        ),
+    // Commit
+    drop(insert_stmt);
+    match tx.commit() {
+        Ok(_r) => {}
+        Err(e) => panic!("Unable to commit inserts into hits table, error: {}", e),
     };
 
     // Return
-    stmt
+    Ok(stats)
 }
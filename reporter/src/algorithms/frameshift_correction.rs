@@ -2,7 +2,7 @@ use crate::algorithms::{orf, orf_extended};
 use crate::models::{Hit, OrfTranscript};
 use crate::reporter::ReporterKit;
 use crate::stats::Stats;
-use biotools::db::sqlite::{TBL_AASEQS, TBL_ESTS, TBL_HITS};
+use biotools::db::sqlite::{TBL_AASEQS, TBL_HITS};
 use biotools::CONFIG;
 use log::warn;
 use rayon::iter::IntoParallelRefIterator;
@@ -38,11 +38,23 @@ pub fn run(kit: &ReporterKit, mut stats: &mut Stats) -> Result<bool, Error> {
             None => break,
         };
 
+        // Skip, if only re-running failed hmm searches
+        let hmmsearch_id: u32 = row.get(2)?;
+        if let Some(ids) = &kit.rerun_ids {
+            if !ids.contains(&hmmsearch_id.to_string()) {
+                continue;
+            }
+        }
+
         // Get est sequence, reverse if necessary
         let is_revcomp: bool = row.get(21)?;
-        let mut est_sequence: String = row.get(26)?;
+        let header_base: String = row.get(19)?;
+        let mut est_sequence: String = match kit.storage.get_est_sequence(&header_base) {
+            Some(r) => r,
+            None => continue,
+        };
         if is_revcomp == true {
-            est_sequence = reverse_seq(&est_sequence);
+            est_sequence = biotools::reverse_seq(&est_sequence);
         }
         let (ali_start, ali_end): (u16, u16) = (row.get(13)?, row.get(14)?);
 
@@ -50,8 +62,8 @@ pub fn run(kit: &ReporterKit, mut stats: &mut Stats) -> Result<bool, Error> {
         let hit = Hit {
             id: row.get(0)?,
             is_overlap: row.get(1)?,
-            hmmsearch_id: row.get(2)?,
-            taxid: row.get(24)?,
+            hmmsearch_id: hmmsearch_id,
+            taxid: row.get(28)?,
             aaseq_id: row.get(4)?,
             ntseq_id: row.get(5)?,
             blast_target: row.get(6)?,
@@ -67,12 +79,16 @@ pub fn run(kit: &ReporterKit, mut stats: &mut Stats) -> Result<bool, Error> {
             env_end: row.get(16)?,
             blast_start: row.get(17)?,
             blast_end: row.get(18)?,
-            header_base: row.get(19)?,
+            header_base: header_base,
             header_full: row.get(20)?,
             header_revcomp: is_revcomp,
             header_translate: row.get(22)?,
             non_orf_sequence: row.get(23)?,
-            aa_sequence: row.get(25)?,
+            confidence: row.get(24)?,
+            copy_num: row.get(25)?,
+            segment_start: row.get(26)?,
+            segment_end: row.get(27)?,
+            aa_sequence: row.get(29)?,
             hmm_sequence: est_to_hmm(&est_sequence, &ali_start, &ali_end),
             est_sequence: est_sequence,
         };
@@ -144,6 +160,10 @@ fn process_hits(hit: &Hit) -> OrfResult {
 
     // Check for extended orf
     let orf = match orf_extended::generate(&hit, &initial_orf) {
+        Some(r) if !within_segment(&hit, &r) => {
+            warn!("Extended orf of hmm search id# {}, gene {} runs outside its segment of chimeric transcript {}, reverting to initial orf.", hit.hmmsearch_id, hit.gene_id, hit.header_base);
+            initial_orf
+        }
         Some(r) => r,
         None => {
             warn!("Did not receive extended orf, reverting to initial orf");
@@ -165,21 +185,34 @@ fn process_hits(hit: &Hit) -> OrfResult {
     res
 }
 
+// Whether the orf lies within its segment, if split from a chimeric transcript
+fn within_segment(hit: &Hit, orf: &OrfTranscript) -> bool {
+    if hit.segment_end == 0 {
+        return true;
+    }
+
+    // Segments are on the forward strand
+    let est_len = hit.est_sequence.len() as u32;
+    let (start, end) = match hit.header_revcomp {
+        true => (est_len - hit.segment_end, est_len - hit.segment_start),
+        false => (hit.segment_start, hit.segment_end),
+    };
+    orf.cdna_start as u32 > start && orf.cdna_end as u32 <= end
+}
+
 fn prepare_select_sql(kit: &ReporterKit) -> Statement {
     let sql = format!(
         "SELECT 
         h.*,
         a.taxid,
-        a.sequence aa_sequence, 
-        e.sequence 
+        a.sequence aa_sequence 
     FROM 
-        {} h, {} a, out.{} e 
+        {} h, {} a 
     WHERE 
-        h.blast_target = a.id AND 
-        e.header = h.header_base
+        h.blast_target = a.id 
         ORDER BY h.id 
     ",
-        *TBL_HITS, *TBL_AASEQS, *TBL_ESTS
+        *TBL_HITS, *TBL_AASEQS
     );
     //ORDER BY h.gene_id,h.score,h.header_base
     // Prepare sql
@@ -210,30 +243,6 @@ fn prepare_insert_sql(kit: &ReporterKit) -> Statement {
     stmt
 }
 
-fn reverse_seq(old_seq: &String) -> String {
-    let seq: String = old_seq
-        .chars()
-        .rev()
-        .map(|c| match c {
-            'A' => 'T',
-            'G' => 'C',
-            'C' => 'G',
-            'T' => 'A',
-            'Y' => 'R',
-            'R' => 'Y',
-            'K' => 'M',
-            'M' => 'K',
-            'a' => 'T',
-            'g' => 'C',
-            'c' => 'G',
-            't' => 'A',
-            _ => c,
-        })
-        .collect();
-
-    seq
-}
-
 fn est_to_hmm(est_sequence: &String, ali_start: &u16, ali_end: &u16) -> String {
     // Get start and end
     let start: usize = (*ali_start as usize - 1) * 3;
@@ -1,6 +1,7 @@
 use crate::reporter::ReporterKit;
 use crate::stats::Stats;
 use biotools::db::sqlite::TBL_HITS;
+use biotools::headers;
 use biotools::CONFIG;
 use log::{info, warn};
 use rusqlite::{Error, Statement};
@@ -152,8 +153,18 @@ fn process_candidates(candidates: &Vec<HmmCandidate>) -> Vec<HmmDiscard> {
                 continue;
             }
 
+            // Keep other copies of the gene under paralog mode, only discarding isoforms
+            if CONFIG.switch.paralog_mode {
+                if headers::locus(&hit_a.header_base) != headers::locus(&hit_b.header_base) {
+                    info!(
+                        "Keeping hmm search {}, gene {}, header {} as another copy of master {}",
+                        hit_b.hmmsearch_id, hit_b.gene_id, hit_b.header_base, hit_a.header_base
+                    );
+                    continue;
+                }
+
             // Check score
-            if (hit_a.score / hit_b.score) < CONFIG.search.hmm_score_discard_threshold {
+            } else if (hit_a.score / hit_b.score) < CONFIG.search.hmm_score_discard_threshold {
                 continue;
             }
 
@@ -1,21 +1,25 @@
-use crate::models::HmmSearch;
-use crate::reporter::ReporterKit;
+use crate::models::{HmmSearch, Reciprocity};
+use crate::reporter::ReferenceData;
+use biotools::db::models::BlastResult;
 use biotools::CONFIG;
 use log::{info, warn};
 
-pub fn check(kit: &ReporterKit, candidate: &HmmSearch) -> Option<(u32, u16, u16)> {
+/**
+ * Check whether the best blast hits of the candidate are within its gene.
+ * Returns the accepted blast hit if reciprocal, plus the decision trace of
+ * the blast hits looked at as target, ref taxon, score and reason.
+ */
+pub fn check(refs: &ReferenceData, candidate: &HmmSearch) -> (Option<Reciprocity>, Vec<String>) {
     // Get blast results
     info!(
         "Getting blast results for '{}' (hmm search id# {}, alignment score {})",
         candidate.header, candidate.hmm_id, candidate.score
     );
-    let blasts = match kit.db.get_hmm_blast_results(candidate.hmm_id) {
-        Ok(res) => res,
-        Err(e) => panic!(
-            "Unable to obtain blast results for hmm search id# {}, error: {}",
-            candidate.hmm_id, e
-        ),
+    let blasts: &[BlastResult] = match refs.blasts.get(&candidate.hmm_id) {
+        Some(r) => r,
+        None => &[],
     };
+    let mut trace: Vec<String> = Vec::new();
 
     // Check for zero blasts
     if blasts.len() == 0 {
@@ -23,68 +27,251 @@ pub fn check(kit: &ReporterKit, candidate: &HmmSearch) -> Option<(u32, u16, u16)
             "No blast results found for '{}' (gene '{}', hmm search id# {}), skipping.",
             candidate.header, candidate.gene_id, candidate.hmm_id
         );
-        return None;
+        trace.push(outcome("not reciprocal, no blast results"));
+        return (None, trace);
     }
 
+    // Get ref taxon name, and whether target is used in the gene, of each blast
+    let targets: Vec<(String, bool)> = blasts
+        .iter()
+        .map(|blast| {
+            (
+                match refs.taxon_by_aaseq.get(&blast.target) {
+                    Some(r) => r.to_string(),
+                    None => panic!(
+                        "Unable to retrieve ref taxon name of aaseq id# {}",
+                        blast.target
+                    ),
+                },
+                refs.aaseq_by_gene[&candidate.gene_id].contains(&blast.target),
+            )
+        })
+        .collect();
+
     // Initialize
-    let mut taxa_count = Vec::new();
+    let required = required_weight(&refs);
+    let mut taxa_count: Vec<String> = Vec::new();
+    let mut taxa_weight: f32 = 0.0;
     let mut mismatches: u16 = 0;
+    let mut soft_mismatches: u16 = 0;
 
-    // GO through blast results
-    for num in 0..(blasts.len() - 1) {
-        let blast = &blasts[num];
-
-        // Get ref taxon name
-        let ref_taxon: String = kit.db.get_ref_taxon_name(&blast.target).unwrap();
+    // Go through blast results in groups of equal score, highest first
+    let mut start = 0;
+    while start < blasts.len() {
+        let end = start
+            + blasts[start..]
+                .iter()
+                .take_while(|b| b.score == blasts[start].score)
+                .count();
 
-        // Check if hit occurs in hmm
-        if kit.aaseq_by_gene[&candidate.gene_id].contains(&blast.target) {
+        // Check for tie between in-gene and out-of-gene targets
+        let in_gene = (start..end)
+            .filter(|x| targets[*x].1 && refs.reference_taxa.contains(&targets[*x].0))
+            .count();
+        let out_gene = (start..end).filter(|x| !targets[*x].1).count();
+        if in_gene > 0 && out_gene > 0 {
+            if CONFIG.search.tie_policy == "reject" {
+                warn!(
+                    "    Reciprocal hits tied at score {} in and out of {}, ambiguous so skipping.",
+                    blasts[start].score, candidate.gene_id
+                );
+                trace.push(outcome(&format!(
+                    "not reciprocal, tied at score {} in and out of gene under reject tie policy",
+                    blasts[start].score
+                )));
+                return (None, trace);
+            }
             info!(
-                "    Reciprocal hit {} ({}) used in {}!",
-                blast.target, ref_taxon, candidate.gene_id
+                "    Reciprocal hits tied at score {} in and out of {}, accepting in-gene hit.",
+                blasts[start].score, candidate.gene_id
             );
+        }
+
+        for num in start..end {
+            let blast = &blasts[num];
+            let (ref_taxon, is_used) = &targets[num];
+
+            // Check if hit occurs in hmm
+            if *is_used {
+                info!(
+                    "    Reciprocal hit {} ({}) used in {}!",
+                    blast.target, ref_taxon, candidate.gene_id
+                );
+
+                // Check reference taxa
+                if !&refs.reference_taxa.contains(&ref_taxon) {
+                    info!("'{}' not in reference taxon list, skipping", ref_taxon);
+                    trace.push(step(&blast, &ref_taxon, "in gene, not a reference taxon"));
+                    continue;
+                }
+
+                // Add taxa to count, if not already threre
+                if !taxa_count.contains(ref_taxon) {
+                    taxa_count.push(ref_taxon.to_string());
+                    taxa_weight += weight(&refs, &ref_taxon);
+                }
+                trace.push(step(
+                    &blast,
+                    &ref_taxon,
+                    &format!(
+                        "in gene, reference taxa weight {}, {}",
+                        taxa_weight,
+                        required_label(required)
+                    ),
+                ));
+
+                // Check if we have enough taxa, only one unless under strict search or k-of-n
+                if taxa_weight > 0.0 && taxa_weight >= required {
+                    let recip = accept(&refs, blasts, &targets, blast);
+                    trace.push(outcome(&format!(
+                        "reciprocal, confidence {}",
+                        recip.confidence
+                    )));
+                    return (Some(recip), trace);
+                }
 
-            // Check reference taxa
-            if !&kit.reference_taxa.contains(&ref_taxon) {
-                info!("'{}' not in reference taxon list, skipping", ref_taxon);
+            // Tied with an in-gene hit, hence not a mismatch
+            } else if in_gene > 0 {
+                trace.push(step(
+                    &blast,
+                    &ref_taxon,
+                    "out of gene, tied with in-gene hit",
+                ));
                 continue;
 
-            // Check if not in strict search, hence 1 hit ie enough
-            } else if !CONFIG.switch.strict_search {
-                return Some((blast.target, blast.res_start, blast.res_end));
-            }
+            // Allowed before the first in-gene hit by the soft threshold
+            } else if taxa_count.len() == 0 && soft_mismatches < CONFIG.search.soft_threshold {
+                soft_mismatches += 1;
+                info!(
+                    "    reciprocal hit {} ({}) not used in this HMM, within soft threshold (#{})",
+                    blast.target, ref_taxon, soft_mismatches
+                );
+                trace.push(step(
+                    &blast,
+                    &ref_taxon,
+                    &format!(
+                        "out of gene, within soft threshold ({} of {})",
+                        soft_mismatches, CONFIG.search.soft_threshold
+                    ),
+                ));
 
-            // Add taxa to count, if not already threre
-            if !taxa_count.contains(&ref_taxon) {
-                taxa_count.push(ref_taxon);
-            }
+            // Mismatch
+            } else {
+                mismatches += 1;
+                warn!(
+                    "    reciprocal hit {} ({}) not used in this HMM (mismatch #{})",
+                    blast.target, ref_taxon, mismatches
+                );
+                trace.push(step(
+                    &blast,
+                    &ref_taxon,
+                    &format!(
+                        "out of gene, mismatch {} of {} allowed",
+                        mismatches, CONFIG.search.max_mismatches
+                    ),
+                ));
 
-            // Check if we have all taxa = matches under strict search
-            if taxa_count.len() >= kit.reference_taxa.len() {
-                return Some((blast.target, blast.res_start, blast.res_end));
+                // Check for too many mismatches
+                if mismatches > CONFIG.search.max_mismatches {
+                    warn!("    Too many mismatches, we don't trust this one anymore.");
+                    trace.push(outcome("not reciprocal, too many mismatches"));
+                    return (None, trace);
+                }
             }
+        }
+        start = end;
+    }
 
-        // Check one ahead for same score
-        //} else if blasts.len() >= (num+1) && blasts[num+1].score == blast.score {
-        //info!("    reciprocal hit {} ({}) (gene: {}) not used in this HMM, but next one has same score, skipping this hit", blast.target, ref_taxon, candidate.gene_id);
-        //continue;
-
-        // Mismatch
-        } else {
-            mismatches += 1;
-            warn!(
-                "    reciprocal hit {} ({}) not used in this HMM (mismatch #{})",
-                blast.target, ref_taxon, mismatches
-            );
+    // Not reciprocal
+    trace.push(outcome(&format!(
+        "not reciprocal, reference taxa weight {}, {}",
+        taxa_weight,
+        required_label(required)
+    )));
+    (None, trace)
+}
+
+fn weight(refs: &ReferenceData, ref_taxon: &String) -> f32 {
+    *refs.taxon_weights.get(ref_taxon).unwrap_or(&1.0)
+}
+
+/**
+ * Weight of reference taxa that must have an in-gene hit, where each taxon
+ * counts by its weight.  This is min-reference-taxa if set, all reference
+ * taxa under strict search, otherwise any one taxon.
+ */
+fn required_weight(refs: &ReferenceData) -> f32 {
+    let total: f32 = refs.reference_taxa.iter().map(|t| weight(&refs, &t)).sum();
+    if CONFIG.search.min_reference_taxa > 0 {
+        (CONFIG.search.min_reference_taxa as f32).min(total)
+    } else if CONFIG.switch.strict_search {
+        total
+    } else {
+        0.0
+    }
+}
+
+fn required_label(required: f32) -> String {
+    match required > 0.0 {
+        true => format!("{} required", required),
+        false => String::from("any taxon required"),
+    }
+}
+
+fn step(blast: &BlastResult, ref_taxon: &str, reason: &str) -> String {
+    format!(
+        "{}\t{}\t{}\t{}",
+        blast.target, ref_taxon, blast.score, reason
+    )
+}
 
-            // Check for too many mismatches
-            if mismatches > CONFIG.search.max_mismatches {
-                warn!("    Too many mismatches, we don't trust this one anymore.");
-                return None;
+fn outcome(reason: &str) -> String {
+    format!("-\t-\t-\t{}", reason)
+}
+
+/**
+ * Confidence is the score gap between the best in-gene and best out-of-gene
+ * hits relative to the best in-gene score, times the weighted fraction of
+ * reference taxa with an in-gene hit.
+ */
+fn accept(
+    refs: &ReferenceData,
+    blasts: &[BlastResult],
+    targets: &[(String, bool)],
+    blast: &BlastResult,
+) -> Reciprocity {
+    let mut best_in: f32 = 0.0;
+    let mut best_out: f32 = 0.0;
+    let mut taxa: Vec<&String> = Vec::new();
+    for (blast, (ref_taxon, is_used)) in blasts.iter().zip(targets.iter()) {
+        if !is_used {
+            best_out = best_out.max(blast.score);
+        } else if refs.reference_taxa.contains(&ref_taxon) {
+            best_in = best_in.max(blast.score);
+            if !taxa.contains(&ref_taxon) {
+                taxa.push(ref_taxon);
             }
         }
     }
 
-    // Not reciprocal
-    None
+    let total: f32 = refs.reference_taxa.iter().map(|t| weight(&refs, &t)).sum();
+    let found: f32 = taxa.iter().map(|t| weight(&refs, &t)).sum();
+    let gap = if best_in > 0.0 {
+        ((best_in - best_out) / best_in).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let coverage = if total > 0.0 { found / total } else { 0.0 };
+    let confidence = (gap * coverage * 1000.0).round() / 1000.0;
+    info!(
+        "    Reciprocity confidence {} (score gap {:.3}, reference taxa weight {} of {})",
+        confidence, gap, found, total
+    );
+
+    Reciprocity {
+        blast_target: blast.target,
+        blast_start: blast.res_start,
+        blast_end: blast.res_end,
+        confidence: confidence,
+    }
 }
@@ -1,3 +1,5 @@
+pub mod chimera;
+pub mod copy_number;
 pub mod env_pseudo_master;
 pub mod extract_reciprocal_hits;
 pub mod frameshift_correction;
@@ -6,4 +8,5 @@ mod is_reciprocal_hit;
 mod orf;
 mod orf_extended;
 mod orf_overlap;
+pub mod orf_qc;
 pub mod region_mapped_before;
@@ -1,8 +1,8 @@
 use crate::models::{Hit, OrfTranscript};
+use biotools::exec::Tool;
 use biotools::{FastaResult, CONFIG};
 use log::{info, warn};
 use std::collections::HashMap;
-use std::process::Command;
 use std::string::String;
 
 pub fn generate(hit: &Hit, is_extended: bool) -> Option<OrfTranscript> {
@@ -39,8 +39,8 @@ pub fn generate(hit: &Hit, is_extended: bool) -> Option<OrfTranscript> {
     let target_file = biotools::io::create_tmp_file(&target_contents);
 
     // Run exxonerate command
-    let output = Command::new(&CONFIG.programs.exonerate)
-        .args([
+    let output = match Tool::exonerate().run(
+        &[
             "--bestn",
             "1",
             "--score",
@@ -67,18 +67,17 @@ pub fn generate(hit: &Hit, is_extended: bool) -> Option<OrfTranscript> {
             &query_file,
             "--target",
             &target_file,
-        ])
-        .output()
-        .expect("Unable to execute exonerate program.");
-
-    // Check output status
-    if !output.status.success() {
-        warn!(
-            "Did not receive successful exit code from exonerate, skipping transcript.  Error: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
-        return None;
-    }
+        ],
+        &hit.hmmsearch_id.to_string(),
+    ) {
+        Ok(r) => r,
+        Err(_e) => {
+            warn!("Did not receive successful exit code from exonerate, skipping transcript.  Input files kept at {} and {}", query_file, target_file);
+            return None;
+        }
+    };
+    biotools::io::remove_tmp_file(&query_file);
+    biotools::io::remove_tmp_file(&target_file);
 
     // Log
     info!(
@@ -100,7 +99,7 @@ pub fn generate(hit: &Hit, is_extended: bool) -> Option<OrfTranscript> {
     };
 
     // Translate
-    let translated = match translate(&cdna.sequence) {
+    let translated = match translate(&cdna.sequence, &hit.hmmsearch_id) {
         Some(r) => r,
         None => {
             warn!("Did not receive a valid response from translate program, skipping transcript.");
@@ -164,23 +163,26 @@ fn read_exonerate_response<'a>(
     Some((cdna, aa))
 }
 
-fn translate(cdna_seq: &String) -> Option<String> {
+fn translate(cdna_seq: &String, hmmsearch_id: &u32) -> Option<String> {
     // Save tmp file
     let tmp_contents = format!(">cdna\n{}", cdna_seq);
     let tmpfile = biotools::io::create_tmp_file(&tmp_contents);
 
     // Execute fasta-translate command
-    let output = Command::new(&CONFIG.programs.translate)
-        .args(["--geneticcode", "1", "-F", "1", &tmpfile])
-        .output()
-        .expect("Unable to run translate program");
-
-    // Check status
-    if !output.status.success() {
-        warn!("Did not receive successful exit status from translate program.  Got {} with (stdout: {}), (stderr: {})", 
-            output.status, String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
-        return None;
-    }
+    let output = match Tool::translate().run(
+        &["--geneticcode", "1", "-F", "1", &tmpfile],
+        &hmmsearch_id.to_string(),
+    ) {
+        Ok(r) => r,
+        Err(_e) => {
+            warn!(
+                "Did not receive successful exit status from translate program.  Input file kept at {}",
+                tmpfile
+            );
+            return None;
+        }
+    };
+    biotools::io::remove_tmp_file(&tmpfile);
 
     // Read fasta file
     let output_contents = String::from_utf8_lossy(&output.stdout);
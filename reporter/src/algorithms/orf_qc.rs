@@ -0,0 +1,197 @@
+use crate::reporter::ReporterKit;
+use crate::stats::Stats;
+use biotools::db::sqlite::{TBL_AASEQS, TBL_HITS};
+use biotools::CONFIG;
+use log::{info, warn};
+use rayon::iter::IntoParallelRefIterator;
+use rayon::iter::ParallelIterator;
+use rusqlite::Error;
+use std::collections::HashMap;
+
+pub struct QcCandidate {
+    pub hit_id: u32,
+    pub gene_id: String,
+    pub header_base: String,
+    pub revcomp: u8,
+    pub translate: u8,
+    orf_seq: String,
+    ref_seq: String,
+}
+
+/**
+ * Quality checks of the final orfs, rejecting those with too many internal
+ * stop codons, a length far off the median length of the gene's reference
+ * sequences, or too low an identity to the reciprocal reference sequence.
+ */
+pub fn check(kit: &ReporterKit, stats: &mut Stats) {
+    let candidates = match gather_candidates(&kit) {
+        Ok(r) => r,
+        Err(e) => panic!("Unable to gather orfs for quality checks, error: {}", e),
+    };
+    let ref_lengths = match median_ref_lengths(&kit) {
+        Ok(r) => r,
+        Err(e) => panic!(
+            "Unable to retrieve reference sequence lengths for quality checks, error: {}",
+            e
+        ),
+    };
+
+    // Check in parallel
+    let results: Vec<Option<&str>> = candidates
+        .par_iter()
+        .map(|c| check_orf(&c, &ref_lengths))
+        .collect();
+
+    // Discard
+    for (cand, res) in candidates.iter().zip(results) {
+        if let Some(reason) = res {
+            stats.discard_orf_qc(&kit, &cand, &reason);
+        }
+    }
+}
+
+fn check_orf(cand: &QcCandidate, ref_lengths: &HashMap<String, f32>) -> Option<&'static str> {
+    let orf_seq = cand.orf_seq.trim_end_matches('*');
+
+    // Check internal stops
+    let stops = orf_seq.matches('*').count();
+    if stops > CONFIG.search.max_internal_stops as usize {
+        warn!(
+            "Orf of hit id# {}, gene {}, header {} has {} internal stops, discarding.",
+            cand.hit_id, cand.gene_id, cand.header_base, stops
+        );
+        return Some("internal-stops");
+    }
+
+    // Check length against reference sequences
+    if let Some(median) = ref_lengths.get(&cand.gene_id) {
+        let ratio = orf_seq.len() as f32 / median;
+        if ratio < CONFIG.search.min_orf_length_ratio
+            || (CONFIG.search.max_orf_length_ratio > 0.0
+                && ratio > CONFIG.search.max_orf_length_ratio)
+        {
+            warn!(
+                "Orf of hit id# {}, gene {}, header {} is {:.3} times the median reference length, discarding.",
+                cand.hit_id, cand.gene_id, cand.header_base, ratio
+            );
+            return Some("length-outlier");
+        }
+    }
+
+    // Check identity to reciprocal reference sequence
+    if CONFIG.search.min_reference_identity > 0.0 {
+        let identity = identity(&orf_seq, &cand.ref_seq.trim_end_matches('*'));
+        if identity < CONFIG.search.min_reference_identity {
+            warn!(
+                "Orf of hit id# {}, gene {}, header {} has identity {:.3} to its reciprocal reference, discarding.",
+                cand.hit_id, cand.gene_id, cand.header_base, identity
+            );
+            return Some("reference-divergence");
+        }
+        info!(
+            "Orf of hit id# {} has identity {:.3} to its reciprocal reference",
+            cand.hit_id, identity
+        );
+    }
+
+    None
+}
+
+/**
+ * Fraction of the orf identical to the reference, aligned end to end within
+ * the reference, so fragments of the gene aren't penalized for its missing
+ * parts.  Scores a match 2, mismatch -1 and gap -2.
+ */
+fn identity(orf_seq: &str, ref_seq: &str) -> f32 {
+    let (a, b) = (orf_seq.as_bytes(), ref_seq.as_bytes());
+    if a.len() == 0 {
+        return 0.0;
+    }
+
+    // Score and number of matches, one row per residue of the orf
+    let mut prev: Vec<(i32, u32)> = vec![(0, 0); b.len() + 1];
+    for i in 1..=a.len() {
+        let mut row: Vec<(i32, u32)> = vec![(i as i32 * -2, 0); b.len() + 1];
+        for j in 1..=b.len() {
+            let is_match = a[i - 1].to_ascii_uppercase() == b[j - 1].to_ascii_uppercase();
+            let diag = (
+                prev[j - 1].0 + if is_match { 2 } else { -1 },
+                prev[j - 1].1 + is_match as u32,
+            );
+            let up = (prev[j].0 - 2, prev[j].1);
+            let left = (row[j - 1].0 - 2, row[j - 1].1);
+            row[j] = diag.max(up).max(left);
+        }
+        prev = row;
+    }
+
+    let best = prev.iter().max().unwrap();
+    best.1 as f32 / a.len() as f32
+}
+
+// Median length of the reference sequences of each gene
+fn median_ref_lengths(kit: &ReporterKit) -> Result<HashMap<String, f32>, Error> {
+    let sql = format!(
+        "SELECT id,LENGTH(RTRIM(sequence, '*')) FROM {}",
+        *TBL_AASEQS
+    );
+    let mut stmt = match kit.memdb.prepare(&sql) {
+        Ok(res) => res,
+        Err(e) => panic!(
+            "Unable to prepare SQL statement to retrieve reference sequence lengths, error: {}",
+            e
+        ),
+    };
+    let lengths: HashMap<u32, u32> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+
+    let mut medians: HashMap<String, f32> = HashMap::new();
+    for (gene_id, aaseq_ids) in &kit.refs.aaseq_by_gene {
+        let mut gene_lengths: Vec<u32> = aaseq_ids
+            .iter()
+            .filter_map(|id| lengths.get(id).copied())
+            .collect();
+        if gene_lengths.len() == 0 {
+            continue;
+        }
+        gene_lengths.sort();
+        let mid = gene_lengths.len() / 2;
+        let median = match gene_lengths.len() % 2 {
+            0 => (gene_lengths[mid - 1] + gene_lengths[mid]) as f32 / 2.0,
+            _ => gene_lengths[mid] as f32,
+        };
+        medians.insert(gene_id.to_string(), median);
+    }
+    Ok(medians)
+}
+
+fn gather_candidates(kit: &ReporterKit) -> Result<Vec<QcCandidate>, Error> {
+    let sql = format!(
+        "SELECT o.hit_id,h.gene_id,h.header_base,h.header_revcomp,h.header_translate,o.translated_seq,a.sequence
+        FROM {} h, {}_orf o, {} a
+        WHERE h.id = o.hit_id AND h.blast_target = a.id
+        ORDER BY o.hit_id",
+        *TBL_HITS, CONFIG.db.table_prefix, *TBL_AASEQS
+    );
+    let mut stmt = match kit.memdb.prepare(&sql) {
+        Ok(res) => res,
+        Err(e) => panic!(
+            "Unable to prepare SQL statement to retrieve orfs for quality checks, error: {}",
+            e
+        ),
+    };
+
+    let rows = stmt.query_map([], |row| {
+        Ok(QcCandidate {
+            hit_id: row.get(0)?,
+            gene_id: row.get(1)?,
+            header_base: row.get(2)?,
+            revcomp: row.get(3)?,
+            translate: row.get(4)?,
+            orf_seq: row.get(5)?,
+            ref_seq: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
@@ -17,6 +17,14 @@ pub struct HmmSearch {
     pub non_orf_sequence: String,
 }
 
+// Accepted reciprocal blast hit, and confidence in it from 0 to 1
+pub struct Reciprocity {
+    pub blast_target: u32,
+    pub blast_start: u16,
+    pub blast_end: u16,
+    pub confidence: f32,
+}
+
 pub struct Hit {
     pub id: u32,
     pub is_overlap: bool,
@@ -42,6 +50,10 @@ pub struct Hit {
     pub header_revcomp: bool,
     pub header_translate: u8,
     pub non_orf_sequence: String,
+    pub confidence: f32,
+    pub copy_num: u16,
+    pub segment_start: u32,
+    pub segment_end: u32,
     pub est_sequence: String,
     pub hmm_sequence: String,
     pub aa_sequence: String,
@@ -56,6 +56,10 @@ pub fn save(kit: &ReporterKit, mut stats: &mut Stats) -> Result<bool, Error> {
             header_revcomp: row.get(21)?,
             header_translate: row.get(22)?,
             non_orf_sequence: row.get(23)?,
+            confidence: row.get(24)?,
+            copy_num: row.get(25)?,
+            segment_start: row.get(26)?,
+            segment_end: row.get(27)?,
             est_sequence: "".to_string(),
             hmm_sequence: "".to_string(),
             aa_sequence: "".to_string(),
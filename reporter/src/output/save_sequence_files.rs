@@ -31,6 +31,10 @@ struct Sequence {
     cdna_end: u16,
     aa_seq: String,
     cdna_seq: String,
+    confidence: f32,
+    copy_num: u16,
+    copies: u16,
+    rel_score: f32,
 }
 
 pub fn run(kit: &ReporterKit) -> Result<bool, Error> {
@@ -127,15 +131,20 @@ fn write_core_sequences(
         };
 
         // Format header
-        let header = vec![
+        let mut fields = vec![
             seq.gene_id,
             seq.taxa_name,
             seq.header,
             format!("1-{}", seq.sequence.len().to_string()).to_string(),
             ".".to_string(),
             ".".to_string(),
-        ]
-        .join(&CONFIG.search.header_seperator);
+            ".".to_string(),
+        ];
+        if CONFIG.switch.paralog_mode {
+            fields.push(".".to_string());
+            fields.push(".".to_string());
+        }
+        let header = fields.join(&CONFIG.search.header_seperator);
 
         // Save to aa file
         let line = format!(">{}\n{}\n", header, seq.sequence);
@@ -183,21 +192,30 @@ fn write_sequences(
             cdna_end: row.get(8)?,
             aa_seq: row.get(9)?,
             cdna_seq: row.get(10)?,
+            confidence: row.get(11)?,
+            copy_num: row.get(12)?,
+            copies: row.get(13)?,
+            rel_score: row.get(14)?,
         };
 
         // Get rf
         let rf = get_rf(&seq.is_revcomp, &seq.translate, &seq_type);
 
-        // Get header
-        let header = vec![
+        // Get header, with copy number and score relative to the best copy under paralog mode
+        let mut fields = vec![
             seq.gene_id,
             format!("{}", CONFIG.report.species_name),
             seq.header,
             format!("{}-{}", seq.aa_start, seq.aa_end),
             rf.to_string(),
             seq.taxa_name,
-        ]
-        .join(&CONFIG.search.header_seperator);
+            seq.confidence.to_string(),
+        ];
+        if CONFIG.switch.paralog_mode {
+            fields.push(format!("copy{}/{}", seq.copy_num, seq.copies));
+            fields.push(format!("{:.3}", seq.rel_score));
+        }
+        let header = fields.join(&CONFIG.search.header_seperator);
 
         // Get sequence
         let sequence = if seq_type == "nt".to_string() {
@@ -283,7 +301,11 @@ fn prepare_sequence_sql(kit: &ReporterKit) -> Statement {
             o.cdna_start_transcript,
             o.cdna_end_transcript,
             o.translated_seq,
-            o.cdna_seq 
+            o.cdna_seq,
+            h.confidence,
+            h.copy_num,
+            (SELECT MAX(c.copy_num) FROM {} c WHERE c.gene_id = h.gene_id),
+            h.score / (SELECT MAX(c.score) FROM {} c WHERE c.gene_id = h.gene_id) 
         FROM 
             {} h, {}_orf o, {} t  
         WHERE
@@ -295,6 +317,8 @@ fn prepare_sequence_sql(kit: &ReporterKit) -> Statement {
             GROUP BY o.translated_seq ORDER BY h.id 
         ",
         *TBL_HITS,
+        *TBL_HITS,
+        *TBL_HITS,
         CONFIG.db.table_prefix,
         *TBL_TAXA,
         CONFIG.search.min_transcript_length,
@@ -1,23 +1,39 @@
 use crate::algorithms::{
-    env_pseudo_master, extract_reciprocal_hits, frameshift_correction, hmm_overlap,
+    chimera, copy_number, env_pseudo_master, extract_reciprocal_hits, frameshift_correction,
+    hmm_overlap, orf_qc,
 };
 use crate::output::{save_brh_files, save_sequence_files};
 use crate::temp_tables;
+use biotools::db::models::BlastResult;
 use biotools::db::sqlite::Sqlite;
+use biotools::db::storage::{self, Storage};
 use biotools::CONFIG;
 use log::info;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use rusqlite::Connection;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub struct Reporter {}
 
 pub struct ReporterKit {
     pub db: Sqlite,
+    pub storage: Box<dyn Storage>,
     pub memdb: Connection,
     pub species_id: u32,
     pub set_id: u32,
+    pub refs: ReferenceData,
+    pub rerun_ids: Option<HashSet<String>>,
+    // Threads of the parallel checks, as per num-threads
+    pub pool: ThreadPool,
+}
+
+// Preloaded for the reciprocity checks, which run in parallel without database access
+pub struct ReferenceData {
     pub reference_taxa: Vec<String>,
-    pub aaseq_by_gene: HashMap<String, Vec<u32>>,
+    pub taxon_weights: HashMap<String, f32>,
+    pub aaseq_by_gene: HashMap<String, HashSet<u32>>,
+    pub taxon_by_aaseq: HashMap<u32, String>,
+    pub blasts: HashMap<u32, Vec<BlastResult>>,
 }
 
 impl Reporter {
@@ -27,15 +43,20 @@ impl Reporter {
 
     pub fn process(self) {
         // Prepare environment
-        self.prepare();
+        let failed = self.prepare();
 
         // Initialize
-        let kit = self.initialize();
+        let kit = self.initialize(failed);
 
         // Extract reciprocal hits
         let mut stats = extract_reciprocal_hits::run(&kit)
             .expect("Error occured while trying to extract reciprocal hits.");
 
+        // Chimera checks
+        if CONFIG.search.chimera_mode != "off" {
+            chimera::check(&kit, &mut stats);
+        }
+
         // Env pseudo master checks
         env_pseudo_master::check(&kit, &mut stats);
 
@@ -48,14 +69,24 @@ impl Reporter {
         // Frameshift correction
         frameshift_correction::run(&kit, &mut stats);
 
+        // Quality checks of final orfs
+        orf_qc::check(&kit, &mut stats);
+
+        // Number copies of genes, if paralog mode
+        if CONFIG.switch.paralog_mode {
+            copy_number::assign(&kit, &mut stats);
+        }
+
         // Save sequence files
         save_sequence_files::run(&kit);
 
         // Write report
         stats.write_report();
+        biotools::exec::log_failure_summary();
+        biotools::workspace::finish();
     }
 
-    fn prepare(&self) {
+    fn prepare(&self) -> HashMap<String, HashSet<String>> {
         // Check output directory, and create if necessary
         biotools::io::create_dir(&CONFIG.report.output_dir);
 
@@ -63,6 +94,10 @@ impl Reporter {
         let logdir = format!("{}/log", CONFIG.report.output_dir);
         biotools::io::create_dir(&logdir);
 
+        // Start new failed items file, and temporary workspace
+        let failed = biotools::exec::init_failure_log("reporter");
+        biotools::workspace::init();
+
         // Clear and re-create /aa/ and /nt/ directories, if needed
         if CONFIG.switch.clear_files == true {
             let aa_dir = format!("{}/aa", &CONFIG.report.output_dir);
@@ -70,18 +105,28 @@ impl Reporter {
             biotools::io::recreate_dir(&aa_dir);
             biotools::io::recreate_dir(&nt_dir);
         }
+
+        failed
     }
 
-    fn initialize(&self) -> ReporterKit {
-        // Connect to SQLite, get species id
-        let db = Sqlite::new();
-        let species_id: u32 = match db.get_species_id() {
-            Ok(res) => res,
-            Err(e) => panic!(
-                "Unable to determine id# for species {}, error: {}",
-                &CONFIG.report.species_name, e
-            ),
+    fn build_pool(&self) -> ThreadPool {
+        match ThreadPoolBuilder::new()
+            .num_threads(CONFIG.search.num_threads as usize)
+            .build()
+        {
+            Ok(r) => r,
+            Err(e) => panic!("Unable to create thread pool, error: {}", e),
+        }
+    }
+
+    fn initialize(&self, mut failed: HashMap<String, HashSet<String>>) -> ReporterKit {
+        // Connect to SQLite and the species storage, get species id
+        let db = match CONFIG.db.backend.as_str() {
+            "rocksdb" => Sqlite::new_set_only(),
+            _ => Sqlite::new(),
         };
+        let storage = storage::open();
+        let species_id: u32 = storage.get_species_id();
         info!(
             "Got species id# {} for species name {}",
             species_id, CONFIG.report.species_name
@@ -117,6 +162,28 @@ impl Reporter {
             ref_taxa.len().to_string()
         );
 
+        // Get weights of reference taxa, which default to 1
+        let mut weights: HashMap<String, f32> = HashMap::new();
+        for pair in CONFIG.report.reference_taxa_weights.split(",") {
+            if pair.trim() == "" {
+                continue;
+            }
+            let (taxon, weight) = match pair.rsplit_once(":") {
+                Some((t, w)) => (t.trim().to_string(), w.trim().parse::<f32>()),
+                None => panic!(
+                    "Invalid reference-taxa-weights entry '{}', must be formatted as TAXON:WEIGHT",
+                    pair
+                ),
+            };
+            match weight {
+                Ok(r) if r >= 0.0 => weights.insert(taxon, r),
+                _ => panic!(
+                    "Invalid weight of reference taxon '{}', must be a number of 0 or more",
+                    taxon
+                ),
+            };
+        }
+
         // Get aa sequences in set
         let aaseq = match db.get_aaseq_in_set(&set_id) {
             Ok(res) => res,
@@ -127,14 +194,51 @@ impl Reporter {
             aaseq.len().to_string()
         );
 
+        // Get ref taxon names of aa sequences
+        let taxon_names = match db.get_ref_taxon_names() {
+            Ok(res) => res,
+            Err(e) => panic!("Unable to retrieve reference taxon names, error: {}", e),
+        };
+
+        // Get blast results
+        let blasts = storage.get_all_blast_results();
+        info!(
+            "Loaded blast results of {} hmm searches.",
+            blasts.len().to_string()
+        );
+
+        // Get hmm searches which previously failed with exonerate or translate
+        let rerun_ids = if CONFIG.switch.rerun_failed {
+            let mut ids = failed.remove("exonerate").unwrap_or(HashSet::new());
+            ids.extend(failed.remove("translate").unwrap_or(HashSet::new()));
+            info!(
+                "Only generating orfs of the {} hmm searches which previously failed",
+                ids.len()
+            );
+            Some(ids)
+        } else {
+            None
+        };
+
         // Return
         ReporterKit {
             db: db,
+            storage: storage,
             memdb: self.open_memdb(),
             species_id: species_id,
             set_id: set_id,
-            reference_taxa: ref_taxa,
-            aaseq_by_gene: aaseq,
+            refs: ReferenceData {
+                reference_taxa: ref_taxa,
+                taxon_weights: weights,
+                aaseq_by_gene: aaseq
+                    .into_iter()
+                    .map(|(gene, ids)| (gene, ids.into_iter().collect()))
+                    .collect(),
+                taxon_by_aaseq: taxon_names,
+                blasts: blasts,
+            },
+            rerun_ids: rerun_ids,
+            pool: self.build_pool(),
         }
     }
 
@@ -144,12 +248,6 @@ impl Reporter {
             //let memdb = Connection::open("/home/boxer/devel/clients/kevin/rust/report.db")
             .expect("Unable to open in-memory SQLite database.");
 
-        // Attach database
-        let sql = format!("ATTACH '{}' AS out", CONFIG.db.reporter_sqlite_file);
-        memdb
-            .execute(&sql, [])
-            .expect("Unable to attach output SQLite database to in-memory database");
-
         // Attach input database
         let sql = format!("ATTACH '{}' AS input", CONFIG.db.sqlite_file);
         memdb
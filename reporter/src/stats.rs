@@ -1,6 +1,8 @@
+use crate::algorithms::chimera::ChimeraCandidate;
 use crate::algorithms::env_pseudo_master::EnvCandidate;
 use crate::algorithms::frameshift_correction::OrfResult;
 use crate::algorithms::hmm_overlap::HmmDiscard;
+use crate::algorithms::orf_qc::QcCandidate;
 use crate::models::{Hit, HmmSearch};
 use crate::reporter::ReporterKit;
 use biotools::db::sqlite::TBL_HITS;
@@ -15,11 +17,17 @@ pub struct Stats {
     discard_hmm_overlap: u32,
     discard_env_pseudo_master: u32,
     discard_env_overlap: u32,
+    discard_isoform: u32,
+    discard_chimera: u32,
+    split_chimeras: u32,
+    discard_orf_qc: HashMap<String, u32>,
+    multi_copy_genes: u32,
     pub discards: Vec<u32>,
     brh_fh: File,
     nolap_fh: File,
     sum_fh: File,
     filter_fh: File,
+    trace_fh: File,
     report_fh: File,
 }
 
@@ -38,6 +46,10 @@ impl Stats {
         let report_fh = biotools::io::open_file(format!("{}/report.txt", CONFIG.report.output_dir));
         let filter_fh =
             biotools::io::open_file(format!("{}/filtered-hits.txt", CONFIG.report.output_dir));
+        let trace_fh = biotools::io::open_file(format!(
+            "{}/reciprocity-trace.txt",
+            CONFIG.report.output_dir
+        ));
 
         Self {
             nrh_by_gene: HashMap::new(),
@@ -45,18 +57,24 @@ impl Stats {
             discard_hmm_overlap: 0,
             discard_env_pseudo_master: 0,
             discard_env_overlap: 0,
+            discard_isoform: 0,
+            discard_chimera: 0,
+            split_chimeras: 0,
+            discard_orf_qc: HashMap::new(),
+            multi_copy_genes: 0,
             discards: Vec::new(),
             brh_fh: brh_fh,
             nolap_fh: nolap_fh,
             sum_fh: sum_fh,
             filter_fh: filter_fh,
+            trace_fh: trace_fh,
             report_fh: report_fh,
         }
     }
 
     pub fn write_brh(&mut self, hit: &Hit) {
         let line = format!(
-            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
             hit.gene_id,
             hit.header_full,
             hit.ali_start,
@@ -64,7 +82,8 @@ impl Stats {
             hit.score,
             hit.evalue,
             hit.hmm_start,
-            hit.hmm_end
+            hit.hmm_end,
+            hit.confidence
         );
 
         self.brh_fh
@@ -74,7 +93,7 @@ impl Stats {
 
     pub fn write_nolap(&mut self, hit: &Hit) {
         let line = format!(
-            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
             hit.gene_id,
             hit.header_full,
             hit.ali_start,
@@ -82,7 +101,8 @@ impl Stats {
             hit.score,
             hit.evalue,
             hit.hmm_start,
-            hit.hmm_end
+            hit.hmm_end,
+            hit.confidence
         );
 
         self.nolap_fh
@@ -107,6 +127,22 @@ impl Stats {
             .expect("Unable to write to filtered-hits.txt file");
     }
 
+    // Decisions taken on the blast results of the candidate, as returned by is_reciprocal_hit
+    pub fn write_trace(&mut self, cand: &HmmSearch, trace: &[String]) {
+        for step in trace {
+            let line = format!(
+                "{}\t{}\t{}\t{}\n",
+                cand.gene_id,
+                cand.header.trim_end(),
+                cand.hmm_id,
+                step
+            );
+            self.trace_fh
+                .write_all(&line.as_bytes())
+                .expect("Unable to write to reciprocity-trace.txt file");
+        }
+    }
+
     pub fn add_non_reciprocal_hit(&mut self, cand: &HmmSearch) {
         // Update counter
         let gene = format!("{}", cand.gene_id);
@@ -177,6 +213,70 @@ impl Stats {
         self.discard_env_overlap += 1;
     }
 
+    pub fn discard_isoform(&mut self, kit: &ReporterKit, cand: &EnvCandidate) {
+        self.delete_hit(&kit, &cand.id);
+        self.write_filtered_hit(
+            &cand.gene_id,
+            &cand.header_base,
+            &cand.hdr_revcomp,
+            &cand.hdr_translate,
+            "isoform",
+        );
+        self.discard_isoform += 1;
+    }
+
+    pub fn discard_chimera(&mut self, kit: &ReporterKit, cand: &ChimeraCandidate) {
+        self.delete_hit(&kit, &cand.id);
+        self.write_filtered_hit(
+            &cand.gene_id,
+            &cand.header_base,
+            &cand.header_revcomp,
+            &cand.header_translate,
+            "chimera",
+        );
+        self.discard_chimera += 1;
+    }
+
+    // Hit kept within its segment of a chimeric transcript
+    pub fn split_chimera(&mut self, cand: &ChimeraCandidate) {
+        self.write_filtered_hit(
+            &cand.gene_id,
+            &cand.header_base,
+            &cand.header_revcomp,
+            &cand.header_translate,
+            "chimera-split",
+        );
+    }
+
+    pub fn add_split_chimera(&mut self) {
+        self.split_chimeras += 1;
+    }
+
+    // Orf rejected by the quality checks, counted per reason
+    pub fn discard_orf_qc(&mut self, kit: &ReporterKit, cand: &QcCandidate, reason: &str) {
+        self.delete_hit(&kit, &cand.hit_id);
+        self.write_filtered_hit(
+            &cand.gene_id,
+            &cand.header_base,
+            &cand.revcomp,
+            &cand.translate,
+            &reason,
+        );
+        *self.discard_orf_qc.entry(reason.to_string()).or_insert(0) += 1;
+    }
+
+    // Copies of the gene under paralog mode, written to summary.txt
+    pub fn add_gene_copies(&mut self, gene_id: &String, copies: u16, transcripts: usize) {
+        if copies > 1 {
+            self.multi_copy_genes += 1;
+        }
+
+        let line = format!("{}\t{}\t{}\n", gene_id, copies, transcripts);
+        self.sum_fh
+            .write_all(&line.as_bytes())
+            .expect("Unable to write to summary.txt file");
+    }
+
     pub fn write_report(&mut self) {
         self.report_fh
             .write_all("\n-- Report --\n\n".as_bytes())
@@ -193,11 +293,35 @@ impl Stats {
         self.report_fh
             .write_all(format!("Skipped Env Overlap: {}\n", self.discard_env_overlap).as_bytes())
             .expect("Unable to write to report.txt file");
+        self.report_fh
+            .write_all(format!("Skipped Isoforms: {}\n", self.discard_isoform).as_bytes())
+            .expect("Unable to write to report.txt file");
+        self.report_fh
+            .write_all(format!("Skipped Chimeras: {}\n", self.discard_chimera).as_bytes())
+            .expect("Unable to write to report.txt file");
+        self.report_fh
+            .write_all(format!("Split Chimeras: {}\n", self.split_chimeras).as_bytes())
+            .expect("Unable to write to report.txt file");
         self.report_fh
             .write_all(format!("Skipped Hmm Overlap: {}\n", self.discard_hmm_overlap).as_bytes())
             .expect("Unable to write to report.txt file");
         self.report_fh
             .write_all(format!("Skipped No ORF: {}\n", self.discard_non_orf).as_bytes())
             .expect("Unable to write to report.txt file");
+        for (label, reason) in [
+            ("Internal Stops", "internal-stops"),
+            ("Length Outliers", "length-outlier"),
+            ("Reference Divergence", "reference-divergence"),
+        ] {
+            let count = self.discard_orf_qc.get(reason).unwrap_or(&0);
+            self.report_fh
+                .write_all(format!("Skipped {}: {}\n", label, count).as_bytes())
+                .expect("Unable to write to report.txt file");
+        }
+        if CONFIG.switch.paralog_mode {
+            self.report_fh
+                .write_all(format!("Multi-copy Genes: {}\n", self.multi_copy_genes).as_bytes())
+                .expect("Unable to write to report.txt file");
+        }
     }
 }
@@ -35,7 +35,11 @@ pub fn setup(conn: &Connection) {
         header_full VARCHAR(255) NOT NULL,
         header_revcomp BOOLEAN NOT NULL DEFAULT false,
         header_translate UNSIGNED INTEGER NOT NULL,
-        non_orf_sequence BLOB NOT NULL
+        non_orf_sequence BLOB NOT NULL,
+        confidence DOUBLE NOT NULL DEFAULT 0,
+        copy_num UNSIGNED INTEGER NOT NULL DEFAULT 1,
+        segment_start UNSIGNED INTEGER NOT NULL DEFAULT 0,
+        segment_end UNSIGNED INTEGER NOT NULL DEFAULT 0
     )",
             CONFIG.db.table_prefix
         ),
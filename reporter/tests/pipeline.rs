@@ -0,0 +1,522 @@
+use std::path::PathBuf;
+use std::process::Command;
+use testkit::fixtures::{digest, Transcript, SPECIES_NAME, TABLE_PREFIX, TRANSCRIPTS};
+use testkit::{golden, TestEnv};
+
+fn main() {
+    testkit::mock::run_if_invoked();
+    testkit::run_tests(&[
+        (
+            "reporter_matches_golden_files",
+            reporter_matches_golden_files,
+        ),
+        (
+            "reporter_without_frameshift_correction",
+            reporter_without_frameshift_correction,
+        ),
+        (
+            "reporter_with_rocksdb_backend",
+            reporter_with_rocksdb_backend,
+        ),
+        (
+            "reporter_reciprocal_tie_policy",
+            reporter_reciprocal_tie_policy,
+        ),
+        (
+            "reporter_reciprocal_with_single_blast_result",
+            reporter_reciprocal_with_single_blast_result,
+        ),
+        (
+            "reporter_soft_threshold_and_min_reference_taxa",
+            reporter_soft_threshold_and_min_reference_taxa,
+        ),
+        ("reporter_paralog_mode", reporter_paralog_mode),
+        ("reporter_chimera_mode", reporter_chimera_mode),
+        ("reporter_orf_quality_checks", reporter_orf_quality_checks),
+        ("reporter_rerun_failed", reporter_rerun_failed),
+    ]);
+}
+
+fn golden_file(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name)
+}
+
+fn reporter_matches_golden_files() {
+    let env = TestEnv::new("reporter", true);
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    assert_golden_outputs(&env);
+}
+
+// The RocksDB converted by biodb must give the same results as SQLite
+fn reporter_with_rocksdb_backend() {
+    let mut env = TestEnv::new("reporter-rocksdb", true);
+    env.use_rocksdb();
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    assert_golden_outputs(&env);
+
+    // But not a shared store, whose keys are prefixed by species
+    let store = env.dir.join("store");
+    let status = Command::new(testkit::biodb_program())
+        .arg("import")
+        .arg("-i")
+        .arg(env.dir.join("biodb").join(SPECIES_NAME))
+        .arg("--store")
+        .arg(&store)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    env.set("rocksdb-directory", &store.display().to_string());
+    let output = Command::new(env!("CARGO_BIN_EXE_reporter"))
+        .arg("-c")
+        .arg(&env.config_file)
+        .current_dir(&env.dir)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("is a shared store"), "{}", stderr);
+}
+
+fn assert_golden_outputs(env: &TestEnv) {
+    // Sequence files
+    for gene in ["EOG001", "EOG002"] {
+        for seq_type in ["aa", "nt"] {
+            let name = format!("{}.{}.fa", gene, seq_type);
+            golden::assert_file_matches(
+                &golden_file(&format!("{}/{}", seq_type, name)),
+                &env.output_file(&format!("{}/{}", seq_type, name)),
+                false,
+            );
+        }
+    }
+
+    // Hit lists and report
+    golden::assert_file_matches(
+        &golden_file("best-reciprocal-hits.txt"),
+        &env.output_file("best-reciprocal-hits.txt"),
+        false,
+    );
+    golden::assert_file_matches(
+        &golden_file("filtered-hits.txt"),
+        &env.output_file("filtered-hits.txt"),
+        true,
+    );
+    golden::assert_file_matches(
+        &golden_file("reciprocity-trace.txt"),
+        &env.output_file("reciprocity-trace.txt"),
+        false,
+    );
+    golden::assert_file_matches(
+        &golden_file("report.txt"),
+        &env.output_file("report.txt"),
+        false,
+    );
+
+    // No external program failures
+    let failed = std::fs::read_to_string(env.output_file("reporter-failed-items.txt"))
+        .expect("Unable to read failed items file");
+    assert_eq!(failed.lines().count(), 1, "failed items: {}", failed);
+}
+
+fn reporter_without_frameshift_correction() {
+    let mut env = TestEnv::new("reporter-no-frameshift", true);
+    env.set("frameshift-correction", "false");
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+
+    // Without exonerate, the orf is the aligned region of the translated transcript
+    let aa = std::fs::read_to_string(env.output_file("aa/EOG001.aa.fa")).unwrap();
+    assert!(aa.contains(TRANSCRIPTS[0].protein), "{}", aa);
+    golden::assert_file_matches(
+        &golden_file("best-reciprocal-hits.txt"),
+        &env.output_file("best-reciprocal-hits.txt"),
+        false,
+    );
+}
+
+// Tie the out-of-gene blast hit of TRANS_1 with its best in-gene hit
+fn reporter_reciprocal_tie_policy() {
+    for policy in ["reject", "accept"] {
+        let mut env = TestEnv::new(&format!("reporter-tie-{}", policy), true);
+        env.species_db()
+            .execute(
+                &format!(
+                    "UPDATE {}_blast SET score = 98.5 WHERE hmmsearch_id = 1 AND target = 3",
+                    TABLE_PREFIX
+                ),
+                [],
+            )
+            .expect("Unable to update blast score");
+        env.set("reciprocal-tie-policy", policy);
+        env.run(env!("CARGO_BIN_EXE_reporter"));
+
+        let brh = std::fs::read_to_string(env.output_file("best-reciprocal-hits.txt")).unwrap();
+        let filtered = std::fs::read_to_string(env.output_file("filtered-hits.txt")).unwrap();
+        if policy == "reject" {
+            assert!(!brh.contains("TRANS_1"), "{}", brh);
+            assert!(
+                filtered
+                    .lines()
+                    .any(|l| l.contains("TRANS_1") && l.ends_with("non-reciprocal")),
+                "{}",
+                filtered
+            );
+        } else {
+            // No score gap, hence no confidence
+            let line = brh.lines().find(|l| l.contains("TRANS_1")).unwrap();
+            assert!(line.ends_with("\t0"), "{}", line);
+        }
+    }
+}
+
+// The last, here only, blast result of a hmm search is checked too
+fn reporter_reciprocal_with_single_blast_result() {
+    for score in ["98.5", "0"] {
+        let env = TestEnv::new(&format!("reporter-single-blast-{}", score), true);
+        env.species_db()
+            .execute_batch(&format!(
+                "DELETE FROM {p}_blast WHERE hmmsearch_id = 1 AND target != 1;
+                UPDATE {p}_blast SET score = {s} WHERE hmmsearch_id = 1;",
+                p = TABLE_PREFIX,
+                s = score
+            ))
+            .expect("Unable to update blast results");
+        env.run(env!("CARGO_BIN_EXE_reporter"));
+
+        // Without any score, there is no score gap rather than NaN
+        let brh = std::fs::read_to_string(env.output_file("best-reciprocal-hits.txt")).unwrap();
+        let line = brh.lines().find(|l| l.contains("TRANS_1")).unwrap();
+        if score == "0" {
+            assert!(line.ends_with("\t0"), "{}", line);
+        } else {
+            assert!(!line.contains("NaN"), "{}", line);
+        }
+    }
+}
+
+fn reporter_soft_threshold_and_min_reference_taxa() {
+    // TRANS_3 only hits its gene after two blast hits of EOG002, hence no score gap
+    let mut env = TestEnv::new("reporter-soft-threshold", true);
+    env.set("soft-threshold", "2");
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let trace = std::fs::read_to_string(env.output_file("reciprocity-trace.txt")).unwrap();
+    assert!(
+        trace.contains("within soft threshold (2 of 2)"),
+        "{}",
+        trace
+    );
+    assert!(
+        trace
+            .lines()
+            .any(|l| l.contains("TRANS_3") && l.ends_with("reciprocal, confidence 0")),
+        "{}",
+        trace
+    );
+
+    // Both reference taxa are required, unless TaxA counts twice
+    for (weights, trans_1) in [("", 2), ("TaxA:2", 1)] {
+        let mut env = TestEnv::new("reporter-min-reference-taxa", true);
+        env.set("min-reference-taxa", "2");
+        env.set("reference-taxa-weights", weights);
+        env.run(env!("CARGO_BIN_EXE_reporter"));
+        let trace = std::fs::read_to_string(env.output_file("reciprocity-trace.txt")).unwrap();
+        let steps: Vec<&str> = trace
+            .lines()
+            .filter(|l| l.contains("TRANS_1") && !l.contains("\t-\t"))
+            .collect();
+        assert_eq!(steps.len(), trans_1, "{}", trace);
+        assert!(
+            steps[trans_1 - 1].ends_with("reference taxa weight 2, 2 required"),
+            "{}",
+            trace
+        );
+    }
+}
+
+// TRANS_4 overlaps the better TRANS_2 within EOG002, so is kept as its second copy
+fn reporter_paralog_mode() {
+    let mut env = TestEnv::new("reporter-paralog", true);
+    env.set("paralog-mode", "true");
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let aa = std::fs::read_to_string(env.output_file("aa/EOG002.aa.fa")).unwrap();
+    assert!(
+        aa.contains("|TRANS_2|2-53|[revcomp]:[translate(1)]|TaxA|0.889|copy1/2|1.000\n"),
+        "{}",
+        aa
+    );
+    assert!(
+        aa.contains("|TRANS_4|") && aa.contains("|copy2/2|0.498\n"),
+        "{}",
+        aa
+    );
+    let summary = std::fs::read_to_string(env.output_file("summary.txt")).unwrap();
+    assert_eq!(summary, "EOG001\t1\t1\nEOG002\t2\t2\n");
+    let report = std::fs::read_to_string(env.output_file("report.txt")).unwrap();
+    assert!(report.contains("Skipped Hmm Overlap: 0\n"), "{}", report);
+    assert!(report.contains("Multi-copy Genes: 1\n"), "{}", report);
+
+    // Unless they're isoforms of the same locus
+    let isoforms = [
+        ("TRANS_2", "TRINITY_DN1_c0_g1_i1"),
+        ("TRANS_4", "TRINITY_DN1_c0_g1_i2"),
+    ];
+    let mut env = TestEnv::new("reporter-paralog-isoforms", true);
+    env.set("paralog-mode", "true");
+    env.set("header-convention", "auto");
+    rename_ests(&env, &isoforms);
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let filtered = std::fs::read_to_string(env.output_file("filtered-hits.txt")).unwrap();
+    assert!(
+        filtered.contains("EOG002,TRINITY_DN1_c0_g1_i2 [translate(1)],isoform"),
+        "{}",
+        filtered
+    );
+    let summary = std::fs::read_to_string(env.output_file("summary.txt")).unwrap();
+    assert_eq!(summary, "EOG001\t1\t1\nEOG002\t1\t1\n");
+    let report = std::fs::read_to_string(env.output_file("report.txt")).unwrap();
+    assert!(report.contains("Skipped Isoforms: 1\n"), "{}", report);
+
+    // Without a header convention, the default, isoforms are separate loci
+    let mut env = TestEnv::new("reporter-paralog-no-convention", true);
+    env.set("paralog-mode", "true");
+    rename_ests(&env, &isoforms);
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let summary = std::fs::read_to_string(env.output_file("summary.txt")).unwrap();
+    assert_eq!(summary, "EOG001\t1\t1\nEOG002\t2\t2\n");
+
+    // Or with a regex convention deriving the same locus
+    let mut env = TestEnv::new("reporter-paralog-regex-convention", true);
+    env.set("paralog-mode", "true");
+    env.set("header-convention", "regex");
+    env.set("header-locus-regex", "^(?P<locus>.+)_i[0-9]+$");
+    rename_ests(&env, &isoforms);
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let summary = std::fs::read_to_string(env.output_file("summary.txt")).unwrap();
+    assert_eq!(summary, "EOG001\t1\t1\nEOG002\t1\t1\n");
+}
+
+fn rename_ests(env: &TestEnv, renames: &[(&str, &str)]) {
+    for (header, renamed) in renames {
+        env.species_db()
+            .execute(
+                &format!(
+                    "UPDATE {}_ests SET header = REPLACE(header, ?, ?)",
+                    TABLE_PREFIX
+                ),
+                [header, renamed],
+            )
+            .expect("Unable to update est header");
+    }
+}
+
+fn reporter_chimera_mode() {
+    // Split, keeping both genes of the chimeric transcript
+    let mut env = TestEnv::new("reporter-chimera-split", true);
+    env.set("chimera-mode", "split");
+    fuse_transcripts(&env);
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let filtered = std::fs::read_to_string(env.output_file("filtered-hits.txt")).unwrap();
+    for gene in ["EOG001", "EOG002"] {
+        assert!(
+            filtered.contains(&format!("{},TRANS_1 [translate(1)],chimera-split\n", gene)),
+            "{}",
+            filtered
+        );
+        let aa = std::fs::read_to_string(env.output_file(&format!("aa/{}.aa.fa", gene))).unwrap();
+        assert!(aa.contains("|TRANS_1|"), "{}", aa);
+    }
+    let report = std::fs::read_to_string(env.output_file("report.txt")).unwrap();
+    assert!(report.contains("Split Chimeras: 1\n"), "{}", report);
+
+    // Discard both genes
+    let mut env = TestEnv::new("reporter-chimera-discard", true);
+    env.set("chimera-mode", "discard");
+    fuse_transcripts(&env);
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let filtered = std::fs::read_to_string(env.output_file("filtered-hits.txt")).unwrap();
+    for gene in ["EOG001", "EOG002"] {
+        assert!(
+            filtered.contains(&format!("{},TRANS_1 [translate(1)],chimera\n", gene)),
+            "{}",
+            filtered
+        );
+    }
+    let aa = std::fs::read_to_string(env.output_file("aa/EOG002.aa.fa")).unwrap();
+    assert!(
+        !aa.contains("|TRANS_1|") && aa.contains("|TRANS_4|"),
+        "{}",
+        aa
+    );
+    let report = std::fs::read_to_string(env.output_file("report.txt")).unwrap();
+    assert!(report.contains("Skipped Chimeras: 2\n"), "{}", report);
+
+    // Or not checked at all, the default
+    let env = TestEnv::new("reporter-chimera-off", true);
+    fuse_transcripts(&env);
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let filtered = std::fs::read_to_string(env.output_file("filtered-hits.txt")).unwrap();
+    assert!(!filtered.contains("chimera"), "{}", filtered);
+}
+
+// Join TRANS_2 onto the end of TRANS_1, moving its hmmsearch hit along with it
+fn fuse_transcripts(env: &TestEnv) {
+    let (first, second) = (&TRANSCRIPTS[0], &TRANSCRIPTS[1]);
+    let nt = format!("{}{}", first.coding_strand(), second.coding_strand());
+    let aa = format!("{}{}", first.aa_sequence(), second.aa_sequence());
+    let offset = (first.coding_strand().len() / 3) as u32;
+
+    let db = env.species_db();
+    let sql = format!(
+        "UPDATE {}_ests SET sequence = ?, digest = ? WHERE header = ?",
+        TABLE_PREFIX
+    );
+    db.execute(&sql, [&nt, &digest(&nt), first.header])
+        .expect("Unable to update est");
+    db.execute(&sql, [&aa, &digest(&aa), &first.aa_header()])
+        .expect("Unable to update est");
+    db.execute(
+        &format!("DELETE FROM {}_ests WHERE header LIKE ?", TABLE_PREFIX),
+        [format!("{}%", second.header)],
+    )
+    .expect("Unable to delete est");
+    db.execute(
+        &format!(
+            "UPDATE {}_hmmsearch SET target = ? WHERE id IN (?, ?)",
+            TABLE_PREFIX
+        ),
+        rusqlite::params![digest(&aa), first.hmmsearch_id, second.hmmsearch_id],
+    )
+    .expect("Unable to update hmmsearch target");
+    db.execute(
+        &format!("UPDATE {}_hmmsearch SET env_start = env_start + ?1, env_end = env_end + ?1, ali_start = ali_start + ?1, ali_end = ali_end + ?1 WHERE id = ?2", TABLE_PREFIX),
+        [offset, second.hmmsearch_id],
+    )
+    .expect("Unable to update hmmsearch coords");
+}
+
+fn reporter_orf_quality_checks() {
+    // TRANS_1 differs from its reciprocal reference by one residue
+    let mut env = TestEnv::new("reporter-orf-qc-identity", true);
+    env.set("min-reference-identity", "0.99");
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let filtered = std::fs::read_to_string(env.output_file("filtered-hits.txt")).unwrap();
+    assert!(
+        filtered.contains("EOG001,TRANS_1 [translate(1)],reference-divergence\n"),
+        "{}",
+        filtered
+    );
+    let aa = std::fs::read_to_string(env.output_file("aa/EOG002.aa.fa")).unwrap();
+    assert!(aa.contains("|TRANS_2|"), "{}", aa);
+    let report = std::fs::read_to_string(env.output_file("report.txt")).unwrap();
+    assert!(
+        report.contains("Skipped Reference Divergence: 1\n"),
+        "{}",
+        report
+    );
+
+    // All orfs are about as long as their references
+    let mut env = TestEnv::new("reporter-orf-qc-length", true);
+    env.set("min-orf-length-ratio", "1.5");
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let report = std::fs::read_to_string(env.output_file("report.txt")).unwrap();
+    assert!(
+        report.contains("Skipped Length Outliers: 2\n"),
+        "{}",
+        report
+    );
+
+    // Nor are they rejected as too long, unless a maximum below 1 is set
+    let env = TestEnv::new("reporter-orf-qc-max-length-default", true);
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let report = std::fs::read_to_string(env.output_file("report.txt")).unwrap();
+    assert!(
+        report.contains("Skipped Length Outliers: 0\n"),
+        "{}",
+        report
+    );
+    let mut env = TestEnv::new("reporter-orf-qc-max-length", true);
+    env.set("max-orf-length-ratio", "0.5");
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let report = std::fs::read_to_string(env.output_file("report.txt")).unwrap();
+    assert!(
+        report.contains("Skipped Length Outliers: 2\n"),
+        "{}",
+        report
+    );
+
+    // Stop codon within the hmm alignment, kept as the orf without frameshift correction
+    let mut env = TestEnv::new("reporter-orf-qc-stops", true);
+    env.set("frameshift-correction", "false");
+    let t = &TRANSCRIPTS[0];
+    let mut aa = t.aa_sequence();
+    aa.replace_range(20..21, "*");
+    replace_translation(&env, &t, &aa);
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let filtered = std::fs::read_to_string(env.output_file("filtered-hits.txt")).unwrap();
+    assert!(
+        filtered.contains("EOG001,TRANS_1 [translate(1)],internal-stops\n"),
+        "{}",
+        filtered
+    );
+
+    // Unless allowed
+    let mut env = TestEnv::new("reporter-orf-qc-stops-allowed", true);
+    env.set("frameshift-correction", "false");
+    env.set("max-internal-stops", "1");
+    replace_translation(&env, &t, &aa);
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let filtered = std::fs::read_to_string(env.output_file("filtered-hits.txt")).unwrap();
+    assert!(!filtered.contains("internal-stops"), "{}", filtered);
+}
+
+// Replace the translated sequence of the transcript, along with its hmmsearch hit target
+fn replace_translation(env: &TestEnv, t: &Transcript, aa: &str) {
+    let db = env.species_db();
+    db.execute(
+        &format!(
+            "UPDATE {}_ests SET sequence = ?, digest = ? WHERE header = ?",
+            TABLE_PREFIX
+        ),
+        [aa, &digest(&aa), &t.aa_header()],
+    )
+    .expect("Unable to update est");
+    db.execute(
+        &format!(
+            "UPDATE {}_hmmsearch SET target = ? WHERE id = ?",
+            TABLE_PREFIX
+        ),
+        rusqlite::params![digest(&aa), t.hmmsearch_id],
+    )
+    .expect("Unable to update hmmsearch target");
+}
+
+fn reporter_rerun_failed() {
+    let mut env = TestEnv::new("reporter-rerun", true);
+
+    // Fail every exonerate search
+    env.set("exonerate-program", "/nonexistent/exonerate");
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+    let failed_file = env.output_file("reporter-failed-items.txt");
+    let failed = std::fs::read_to_string(&failed_file).unwrap();
+    assert_eq!(failed.lines().count(), 3, "failed items: {}", failed);
+
+    // Re-run only the failure of TRANS_1
+    let failed: String = failed
+        .lines()
+        .filter(|l| l.starts_with("#") || l.starts_with("exonerate\t1\t"))
+        .map(|l| format!("{}\n", l))
+        .collect();
+    std::fs::write(&failed_file, &failed).unwrap();
+    let exonerate = env.tools.exonerate.display().to_string();
+    env.set("exonerate-program", &exonerate);
+    env.set("rerun-failed", "1");
+    env.run(env!("CARGO_BIN_EXE_reporter"));
+
+    let aa = std::fs::read_to_string(env.output_file("aa/EOG001.aa.fa")).unwrap();
+    assert!(aa.contains("|TRANS_1|"), "{}", aa);
+    let aa = std::fs::read_to_string(env.output_file("aa/EOG002.aa.fa")).unwrap_or_default();
+    assert!(!aa.contains("|TRANS_2|"), "{}", aa);
+    let failed = std::fs::read_to_string(&failed_file).unwrap();
+    assert_eq!(failed.lines().count(), 1, "failed items: {}", failed);
+}
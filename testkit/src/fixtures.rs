@@ -0,0 +1,413 @@
+use crate::mock;
+use rusqlite::{params, Connection};
+use std::fs;
+use std::path::Path;
+
+pub const TABLE_PREFIX: &str = "orthograph";
+pub const SET_NAME: &str = "test_set";
+pub const SPECIES_NAME: &str = "testspecies";
+pub const SET_ID: u32 = 1;
+pub const SPECIES_ID: u32 = 1;
+
+// Sequence types of the ests table
+pub const EST_TYPE_NT: u8 = 1;
+pub const EST_TYPE_AA: u8 = 2;
+
+// Codons leading and trailing the coding region of every transcript
+const LEADER: &str = "CCCAAA";
+const TRAILER: &str = "TAAGGG";
+
+/// Reference protein of the ortholog set.
+pub struct Reference {
+    pub aaseq_id: u32,
+    pub taxon: &'static str,
+    pub gene_id: &'static str,
+    pub sequence: &'static str,
+}
+
+/// Transcript of the species, along with the hmmsearch hit it produced and the
+/// blast hits of that hmmsearch as (aaseq id, bitscore).
+pub struct Transcript {
+    pub header: &'static str,
+    pub protein: &'static str,
+    pub revcomp: bool,
+    pub hmmsearch_id: u32,
+    pub gene_id: &'static str,
+    pub score: f64,
+    pub evalue: &'static str,
+    pub blasts: &'static [(u32, f32)],
+}
+
+pub const TAXA: [(u32, &str); 2] = [(1, "TaxA"), (2, "TaxB")];
+
+pub const REFERENCES: [Reference; 4] = [
+    Reference {
+        aaseq_id: 1,
+        taxon: "TaxA",
+        gene_id: "EOG001",
+        sequence: "MSTNPKPQRKTKRNTNRRPQDVKFPGGGQIVGGVYLLPRRGPRLGVRATRK",
+    },
+    Reference {
+        aaseq_id: 2,
+        taxon: "TaxB",
+        gene_id: "EOG001",
+        sequence: "MSTNPKPQRKTKRNTNRRPQDVKFPGGGQIVGGVYLLPRRGPKLGVRATRK",
+    },
+    Reference {
+        aaseq_id: 3,
+        taxon: "TaxA",
+        gene_id: "EOG002",
+        sequence: "MADQLTEEQIAEFKEAFSLFDKDGDGTITTKELGTVMRSLGQNPTEAELQ",
+    },
+    Reference {
+        aaseq_id: 4,
+        taxon: "TaxB",
+        gene_id: "EOG002",
+        sequence: "MADQLTEEQIAEFKEAFSLFDKDGDGTITTKELGTVMRSLGQNPTEAELL",
+    },
+];
+
+/**
+ * The transcripts cover one plain hit, one hit on the reverse strand, one hit
+ * without a reciprocal blast hit, and one weaker hit of the same gene which is
+ * discarded by the hmm overlap check.
+ */
+pub const TRANSCRIPTS: [Transcript; 4] = [
+    Transcript {
+        header: "TRANS_1",
+        protein: "MSTNPKPQRKTKRNTNRRPQDVKFPGGGQIVGGVYLLPRRGPRLGVRSTRK",
+        revcomp: false,
+        hmmsearch_id: 1,
+        gene_id: "EOG001",
+        score: 150.2,
+        evalue: "2.1e-45",
+        blasts: &[(1, 98.5), (2, 96.1), (3, 12.4)],
+    },
+    Transcript {
+        header: "TRANS_2",
+        protein: "MADQLTEEQIAEFKEAFSLFDKDGDGTITTKELGTVMRSLGQNPTEAELQ",
+        revcomp: true,
+        hmmsearch_id: 2,
+        gene_id: "EOG002",
+        score: 140.7,
+        evalue: "3.4e-42",
+        blasts: &[(3, 101.3), (4, 99.8), (1, 11.2)],
+    },
+    Transcript {
+        header: "TRANS_3",
+        protein: "MADQLTEEQIAEFKEAFSLWDKDGDGTITTKELGTVMRSLGQNPTEAEAQ",
+        revcomp: false,
+        hmmsearch_id: 3,
+        gene_id: "EOG001",
+        score: 60.3,
+        evalue: "1.7e-15",
+        blasts: &[(3, 88.9), (4, 87.0), (1, 14.6)],
+    },
+    Transcript {
+        header: "TRANS_4",
+        protein: "MADQLTEEQIVEFKEAFSLFDKDGDGSITTKELGTVMRSLGQNPTEAELQ",
+        revcomp: false,
+        hmmsearch_id: 4,
+        gene_id: "EOG002",
+        score: 70.1,
+        evalue: "5.5e-19",
+        blasts: &[(3, 80.2), (4, 78.3), (2, 10.9)],
+    },
+];
+
+impl Transcript {
+    /// Coding strand of the transcript, including leading and trailing codons.
+    pub fn coding_strand(&self) -> String {
+        format!("{}{}{}", LEADER, back_translate(&self.protein), TRAILER)
+    }
+
+    /// Nucleotide sequence as found within the input file.
+    pub fn nt_sequence(&self) -> String {
+        if self.revcomp {
+            mock::reverse_complement(&self.coding_strand())
+        } else {
+            self.coding_strand()
+        }
+    }
+
+    /// Translated sequence, as stored by the analyzer.
+    pub fn aa_sequence(&self) -> String {
+        mock::translate(&self.coding_strand())
+    }
+
+    pub fn aa_header(&self) -> String {
+        if self.revcomp {
+            format!("{} [revcomp]:[translate(1)]", self.header)
+        } else {
+            format!("{} [translate(1)]", self.header)
+        }
+    }
+
+    /// Alignment coordinates of the protein within the translated sequence.
+    pub fn ali_coords(&self) -> (u32, u32) {
+        let start = (LEADER.len() / 3) as u32 + 1;
+        (start, start + self.protein.len() as u32 - 1)
+    }
+}
+
+pub fn digest(sequence: &str) -> String {
+    format!("{:x}", md5::compute(sequence.as_bytes()))
+}
+
+/**
+ * Create the ortholog set database holding the reference taxa, their aa / nt
+ * sequences, and the ortholog groups.
+ */
+pub fn create_set_db(filename: &Path) {
+    let conn = open_db(&filename);
+    let sql = "
+        CREATE TABLE {p}_set_details (id INTEGER PRIMARY KEY, name TEXT UNIQUE, description BLOB);
+        CREATE TABLE {p}_taxa (id INTEGER PRIMARY KEY, name TEXT UNIQUE, core INTEGER NOT NULL);
+        CREATE TABLE {p}_aaseqs (id INTEGER PRIMARY KEY, taxid INTEGER NOT NULL, header TEXT, sequence TEXT, date INTEGER);
+        CREATE TABLE {p}_ntseqs (id INTEGER PRIMARY KEY, taxid INTEGER NOT NULL, header TEXT, sequence TEXT, date INTEGER);
+        CREATE TABLE {p}_sequence_pairs (id INTEGER PRIMARY KEY, taxid INTEGER, aa_seq INTEGER UNIQUE, nt_seq INTEGER UNIQUE, date INTEGER);
+        CREATE TABLE {p}_orthologs (id INTEGER PRIMARY KEY, ortholog_gene_id TEXT NOT NULL, sequence_pair INTEGER NOT NULL, setid INTEGER NOT NULL, UNIQUE (ortholog_gene_id, sequence_pair, setid));
+        CREATE TABLE {p}_ogs (id INTEGER PRIMARY KEY, type INTEGER, taxid INTEGER NOT NULL, version TEXT);
+        CREATE TABLE {p}_blastdbs (id INTEGER PRIMARY KEY, setid INTEGER UNIQUE, blastdb_path TEXT);
+        CREATE TABLE {p}_sequence_types (id INTEGER PRIMARY KEY, type TEXT UNIQUE);
+    ";
+    execute_batch(&conn, &sql);
+
+    // Set and taxa
+    conn.execute(
+        &format!(
+            "INSERT INTO {}_set_details (id, name, description) VALUES (?, ?, ?)",
+            TABLE_PREFIX
+        ),
+        params![SET_ID, SET_NAME, "Synthetic set for integration tests"],
+    )
+    .expect("Unable to insert set details");
+    for (id, name) in TAXA.iter() {
+        conn.execute(
+            &format!(
+                "INSERT INTO {}_taxa (id, name, core) VALUES (?, ?, 1)",
+                TABLE_PREFIX
+            ),
+            params![id, name],
+        )
+        .expect("Unable to insert taxon");
+    }
+    for (id, name) in [(1, "nt"), (2, "aa")] {
+        conn.execute(
+            &format!(
+                "INSERT INTO {}_sequence_types (id, type) VALUES (?, ?)",
+                TABLE_PREFIX
+            ),
+            params![id, name],
+        )
+        .expect("Unable to insert sequence type");
+    }
+
+    // Reference sequences, one sequence pair per aa sequence
+    for r in REFERENCES.iter() {
+        let taxid = taxon_id(&r.taxon);
+        let header = format!("{}_{}", r.taxon, r.gene_id);
+        conn.execute(
+            &format!(
+                "INSERT INTO {}_aaseqs (id, taxid, header, sequence, date) VALUES (?, ?, ?, ?, 0)",
+                TABLE_PREFIX
+            ),
+            params![r.aaseq_id, taxid, header, r.sequence],
+        )
+        .expect("Unable to insert aa sequence");
+        conn.execute(
+            &format!(
+                "INSERT INTO {}_ntseqs (id, taxid, header, sequence, date) VALUES (?, ?, ?, ?, 0)",
+                TABLE_PREFIX
+            ),
+            params![r.aaseq_id, taxid, header, back_translate(&r.sequence)],
+        )
+        .expect("Unable to insert nt sequence");
+        conn.execute(
+            &format!("INSERT INTO {}_sequence_pairs (id, taxid, aa_seq, nt_seq, date) VALUES (?, ?, ?, ?, 0)", TABLE_PREFIX),
+            params![r.aaseq_id, taxid, r.aaseq_id, r.aaseq_id],
+        )
+        .expect("Unable to insert sequence pair");
+        conn.execute(
+            &format!("INSERT INTO {}_orthologs (ortholog_gene_id, sequence_pair, setid) VALUES (?, ?, ?)", TABLE_PREFIX),
+            params![r.gene_id, r.aaseq_id, SET_ID],
+        )
+        .expect("Unable to insert ortholog");
+    }
+}
+
+/**
+ * Create the species database as left behind by the analyzer, holding the
+ * transcripts and hmmsearch hits.  The blast results of the transcripts are
+ * only included if with_blast is set, otherwise they're left for blastpal.
+ */
+pub fn create_species_db(filename: &Path, with_blast: bool) {
+    let conn = open_db(&filename);
+    let sql = "
+        CREATE TABLE {p}_species_info (id INTEGER PRIMARY KEY, name TEXT UNIQUE NOT NULL);
+        CREATE TABLE {p}_ests (id INTEGER PRIMARY KEY, digest TEXT(32) NOT NULL, taxid INTEGER NOT NULL, type INTEGER NOT NULL, date INTEGER, header TEXT NOT NULL, sequence TEXT);
+        CREATE TABLE {p}_hmmsearch (id INTEGER PRIMARY KEY, taxid INTEGER NOT NULL, query TEXT NOT NULL, target TEXT(32) NOT NULL, score DOUBLE NOT NULL, evalue TEXT(8) NOT NULL, log_evalue DOUBLE NOT NULL DEFAULT '-999', env_start INTEGER NOT NULL, env_end INTEGER NOT NULL, ali_start INTEGER NOT NULL, ali_end INTEGER NOT NULL, hmm_start INTEGER NOT NULL, hmm_end INTEGER NOT NULL);
+        CREATE TABLE {p}_blast (id INTEGER PRIMARY KEY, taxid INTEGER NOT NULL, query TEXT(32) NOT NULL, target INTEGER NOT NULL, score DOUBLE NOT NULL, evalue TEXT(8) NOT NULL, log_evalue DOUBLE NOT NULL DEFAULT '-999', start INTEGER NOT NULL, end INTEGER NOT NULL, hmmsearch_id INTEGER NOT NULL);
+        CREATE INDEX {p}_ests_digest ON {p}_ests (digest);
+        CREATE INDEX {p}_hmmsearch_query ON {p}_hmmsearch (query);
+        CREATE INDEX {p}_blast_hmmsearch_id ON {p}_blast (hmmsearch_id);
+    ";
+    execute_batch(&conn, &sql);
+
+    // Species
+    conn.execute(
+        &format!(
+            "INSERT INTO {}_species_info (id, name) VALUES (?, ?)",
+            TABLE_PREFIX
+        ),
+        params![SPECIES_ID, SPECIES_NAME],
+    )
+    .expect("Unable to insert species");
+
+    for t in TRANSCRIPTS.iter() {
+        // Untranslated and translated transcript
+        let (nt, aa) = (t.nt_sequence(), t.aa_sequence());
+        insert_est(&conn, &t.header, &nt, EST_TYPE_NT);
+        insert_est(&conn, &t.aa_header(), &aa, EST_TYPE_AA);
+
+        // Hmmsearch hit
+        let (ali_start, ali_end) = t.ali_coords();
+        let hmm_len = t.protein.len() as u32;
+        conn.execute(
+            &format!("INSERT INTO {}_hmmsearch (id, taxid, query, target, score, evalue, log_evalue, env_start, env_end, ali_start, ali_end, hmm_start, hmm_end) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", TABLE_PREFIX),
+            params![
+                t.hmmsearch_id,
+                SPECIES_ID,
+                t.gene_id,
+                digest(&aa),
+                t.score,
+                t.evalue,
+                log_evalue(t.evalue.parse::<f64>().unwrap()),
+                ali_start,
+                ali_end,
+                ali_start,
+                ali_end,
+                1,
+                hmm_len
+            ],
+        )
+        .expect("Unable to insert hmmsearch hit");
+
+        // Blast hits
+        if !with_blast {
+            continue;
+        }
+        for (target, score) in t.blasts.iter() {
+            let evalue = mock::blast_evalue(*score);
+            conn.execute(
+                &format!("INSERT INTO {}_blast (taxid, query, target, score, evalue, log_evalue, start, end, hmmsearch_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)", TABLE_PREFIX),
+                params![
+                    SPECIES_ID,
+                    digest(&aa),
+                    target,
+                    score,
+                    evalue,
+                    log_evalue(evalue),
+                    1,
+                    hmm_len,
+                    t.hmmsearch_id
+                ],
+            )
+            .expect("Unable to insert blast hit");
+        }
+    }
+}
+
+/// Input file of the species, nucleotide transcripts in FASTA format.
+pub fn write_input_file(filename: &Path) {
+    let contents: String = TRANSCRIPTS
+        .iter()
+        .map(|t| format!(">{}\n{}\n", t.header, t.nt_sequence()))
+        .collect();
+    write_file(&filename, &contents);
+}
+
+/// Reference proteins in FASTA format keyed by aa sequence id, used as the blast database.
+pub fn write_blastdb(filename: &Path) {
+    let contents: String = REFERENCES
+        .iter()
+        .map(|r| format!(">{}\n{}\n", r.aaseq_id, r.sequence))
+        .collect();
+    write_file(&filename, &contents);
+}
+
+/// Nucleotide sequence of a protein, using one fixed codon per amino acid.
+pub fn back_translate(protein: &str) -> String {
+    protein
+        .chars()
+        .map(|aa| match aa {
+            'A' => "GCT",
+            'C' => "TGT",
+            'D' => "GAT",
+            'E' => "GAA",
+            'F' => "TTT",
+            'G' => "GGT",
+            'H' => "CAT",
+            'I' => "ATT",
+            'K' => "AAA",
+            'L' => "CTG",
+            'M' => "ATG",
+            'N' => "AAT",
+            'P' => "CCG",
+            'Q' => "CAG",
+            'R' => "CGT",
+            'S' => "TCT",
+            'T' => "ACC",
+            'V' => "GTT",
+            'W' => "TGG",
+            'Y' => "TAT",
+            _ => "TAA",
+        })
+        .collect()
+}
+
+// Same as biotools::log_evalue, duplicated to keep biotools' settings out of the tests
+fn log_evalue(evalue: f64) -> f32 {
+    if evalue == 0.00 {
+        return -999.00;
+    }
+    evalue.log2().floor() as f32
+}
+
+fn taxon_id(name: &str) -> u32 {
+    TAXA.iter().find(|t| t.1 == name).map(|t| t.0).unwrap()
+}
+
+fn insert_est(conn: &Connection, header: &str, sequence: &str, seq_type: u8) {
+    conn.execute(
+        &format!("INSERT INTO {}_ests (digest, taxid, type, date, header, sequence) VALUES (?, ?, ?, 0, ?, ?)", TABLE_PREFIX),
+        params![digest(&sequence), SPECIES_ID, seq_type, header, sequence],
+    )
+    .expect("Unable to insert est");
+}
+
+fn open_db(filename: &Path) -> Connection {
+    if filename.exists() {
+        fs::remove_file(&filename).expect("Unable to remove previous fixture database");
+    }
+    match Connection::open(&filename) {
+        Ok(r) => r,
+        Err(e) => panic!(
+            "Unable to create fixture database at {}, error: {}",
+            filename.display(),
+            e
+        ),
+    }
+}
+
+fn execute_batch(conn: &Connection, sql: &str) {
+    if let Err(e) = conn.execute_batch(&sql.replace("{p}", TABLE_PREFIX)) {
+        panic!("Unable to create fixture database tables, error: {}", e);
+    }
+}
+
+fn write_file(filename: &Path, contents: &str) {
+    if let Err(e) = fs::write(&filename, &contents) {
+        panic!("Unable to write {}, error: {}", filename.display(), e);
+    }
+}
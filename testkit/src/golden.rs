@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::Path;
+
+/**
+ * Compare output against its golden file.  When the UPDATE_GOLDEN environment
+ * variable is set the golden file is written instead, so changes in output can
+ * be reviewed through the diff of the golden files.
+ */
+pub fn assert_matches(golden: &Path, actual: &str) {
+    // Update golden file, if needed
+    if std::env::var("UPDATE_GOLDEN").is_ok() {
+        if let Some(parent) = golden.parent() {
+            fs::create_dir_all(&parent).expect("Unable to create golden file directory");
+        }
+        fs::write(&golden, &actual).expect("Unable to write golden file");
+        return;
+    }
+
+    let expected = match fs::read_to_string(&golden) {
+        Ok(r) => r,
+        Err(e) => panic!(
+            "Unable to read golden file {}, error: {}.  Run with UPDATE_GOLDEN=1 to create it.",
+            golden.display(),
+            e
+        ),
+    };
+    if expected == actual {
+        return;
+    }
+
+    // Report first differing line
+    let (exp_lines, act_lines): (Vec<&str>, Vec<&str>) =
+        (expected.lines().collect(), actual.lines().collect());
+    let line = (0..exp_lines.len().max(act_lines.len()))
+        .find(|i| exp_lines.get(*i) != act_lines.get(*i))
+        .unwrap_or(0);
+    panic!(
+        "Output does not match golden file {} at line {}\n  expected: {}\n  actual:   {}\nRun with UPDATE_GOLDEN=1 to accept the new output.",
+        golden.display(),
+        line + 1,
+        exp_lines.get(line).unwrap_or(&"<end of file>"),
+        act_lines.get(line).unwrap_or(&"<end of file>")
+    );
+}
+
+/// Compare an output file against its golden file.
+pub fn assert_file_matches(golden: &Path, actual: &Path, sort_lines: bool) {
+    let contents = match fs::read_to_string(&actual) {
+        Ok(r) => r,
+        Err(e) => panic!(
+            "Unable to read output file {}, error: {}",
+            actual.display(),
+            e
+        ),
+    };
+
+    // Sort lines of files written in no particular order
+    if sort_lines {
+        assert_matches(&golden, &sorted_lines(&contents));
+    } else {
+        assert_matches(&golden, &contents);
+    }
+}
+
+pub fn sorted_lines(contents: &str) -> String {
+    let mut lines: Vec<&str> = contents.lines().collect();
+    lines.sort();
+    lines.iter().map(|l| format!("{}\n", l)).collect()
+}
@@ -0,0 +1,235 @@
+use crate::mock::MockTools;
+use rusqlite::Connection;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+pub mod fixtures;
+pub mod golden;
+pub mod mock;
+
+/**
+ * Self-contained run environment for the end-to-end tests.  Holds the set and
+ * species databases, input file, blast database, mock programs, and a
+ * config.ini pointing at all of them, within a fresh temporary directory.  The
+ * directory is removed on drop, unless KEEP_TEST_DIR is set or the test failed.
+ */
+pub struct TestEnv {
+    pub dir: PathBuf,
+    pub output_dir: PathBuf,
+    pub config_file: PathBuf,
+    pub tools: MockTools,
+    settings: Vec<(String, String)>,
+}
+
+impl TestEnv {
+    pub fn new(name: &str, with_blast: bool) -> Self {
+        // Create directories
+        let dir = std::env::temp_dir().join(format!(
+            "orthograph-testkit-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let output_dir = dir.join("output");
+        for sub in ["blast", "log"] {
+            fs::create_dir_all(output_dir.join(sub)).expect("Unable to create test directory");
+        }
+        fs::create_dir_all(dir.join("tmp")).expect("Unable to create test directory");
+
+        // Create fixtures
+        fixtures::create_set_db(&dir.join("set.sqlite"));
+        fixtures::create_species_db(
+            &output_dir.join(format!("{}.sqlite", fixtures::SPECIES_NAME)),
+            with_blast,
+        );
+        fixtures::write_input_file(&dir.join("input.fa"));
+        fixtures::write_blastdb(&dir.join("blastdb.fa"));
+        let tools = mock::install(&dir.join("bin"));
+
+        // Default settings
+        let path = |p: &Path| p.display().to_string();
+        let settings: Vec<(String, String)> = vec![
+            ("species-name", fixtures::SPECIES_NAME.to_string()),
+            ("ortholog-set", fixtures::SET_NAME.to_string()),
+            ("sets-dir", path(&dir)),
+            ("sqlite-database", path(&dir.join("set.sqlite"))),
+            ("input-file", path(&dir.join("input.fa"))),
+            ("output-directory", path(&output_dir)),
+            ("blastdb", path(&dir.join("blastdb.fa"))),
+            ("tmp-dir", path(&dir.join("tmp"))),
+            ("exonerate-program", path(&tools.exonerate)),
+            ("translate-program", path(&tools.translate)),
+            ("blast-program", path(&tools.blast)),
+            ("hmmsearch-score-threshold", "10".to_string()),
+            ("minimum-transcript-length", "10".to_string()),
+            ("max-reciprocal-mismatches", "0".to_string()),
+            ("env-overlap-threshold", "0.3".to_string()),
+            ("env-score-discard-threshold", "1.05".to_string()),
+            ("hmm-overlap-threshold", "0.9".to_string()),
+            ("hmm-score-discard-threshold", "1.5".to_string()),
+            ("clear-files", "1".to_string()),
+            ("num-threads", "1".to_string()),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+
+        let env = Self {
+            config_file: dir.join("config.ini"),
+            dir: dir,
+            output_dir: output_dir,
+            tools: tools,
+            settings: settings,
+        };
+        env.write_config();
+        env
+    }
+
+    /// Add or replace a config.ini setting.
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.settings.iter_mut().find(|s| s.0 == key) {
+            Some(s) => s.1 = value.to_string(),
+            None => self.settings.push((key.to_string(), value.to_string())),
+        }
+        self.write_config();
+    }
+
+    fn write_config(&self) {
+        let contents: String = self
+            .settings
+            .iter()
+            .map(|(k, v)| format!("{} = {}\n", k, v))
+            .collect();
+        fs::write(&self.config_file, &contents).expect("Unable to write config.ini");
+    }
+
+    /// Run the program against the config.ini, panicking with its output upon failure.
+    pub fn run(&self, program: &str) -> Output {
+        let output = match Command::new(&program)
+            .arg("-c")
+            .arg(&self.config_file)
+            .current_dir(&self.dir)
+            .output()
+        {
+            Ok(r) => r,
+            Err(e) => panic!("Unable to execute {}, error: {}", program, e),
+        };
+
+        if !output.status.success() {
+            panic!(
+                "{} exited with {}\n--- stdout ---\n{}\n--- stderr ---\n{}",
+                program,
+                output.status,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        output
+    }
+
+    pub fn species_db(&self) -> Connection {
+        let filename = self
+            .output_dir
+            .join(format!("{}.sqlite", fixtures::SPECIES_NAME));
+        Connection::open(&filename).expect("Unable to open species database")
+    }
+
+    /**
+     * Convert the species database to a RocksDB with "biodb upgrade", and
+     * switch the reporter over to it.  The SQLite database is moved into a
+     * species directory first, as laid out by Orthograph.
+     */
+    pub fn use_rocksdb(&mut self) {
+        let species_dir = self.dir.join("biodb").join(fixtures::SPECIES_NAME);
+        fs::create_dir_all(&species_dir).expect("Unable to create species directory");
+        let filename = format!("{}.sqlite", fixtures::SPECIES_NAME);
+        fs::rename(self.output_dir.join(&filename), species_dir.join(&filename))
+            .expect("Unable to move species database");
+
+        let output = match Command::new(biodb_program())
+            .arg("upgrade")
+            .arg("-i")
+            .arg(&species_dir)
+            .output()
+        {
+            Ok(r) => r,
+            Err(e) => panic!("Unable to execute biodb, error: {}", e),
+        };
+        if !output.status.success() {
+            panic!(
+                "biodb upgrade exited with {}\n--- stderr ---\n{}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        let rocksdb_dir = species_dir.join("rocksdb");
+        self.set("rocksdb-directory", &rocksdb_dir.display().to_string());
+        self.set("database-backend", "rocksdb");
+    }
+
+    pub fn output_file(&self, name: &str) -> PathBuf {
+        self.output_dir.join(name)
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        if std::env::var("KEEP_TEST_DIR").is_ok() || std::thread::panicking() {
+            eprintln!("Keeping test directory at {}", self.dir.display());
+            return;
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/**
+ * The biodb binary of the workspace, next to the test executable within the
+ * target directory.  Built first if the tests were run without it.
+ */
+pub fn biodb_program() -> PathBuf {
+    let exe = std::env::current_exe().expect("Unable to get test executable");
+    let program = exe
+        .parent()
+        .and_then(|deps| deps.parent())
+        .expect("Test executable is not within a target directory")
+        .join(format!("biodb{}", std::env::consts::EXE_SUFFIX));
+    if program.exists() {
+        return program;
+    }
+
+    let cargo = std::env::var("CARGO").unwrap_or(String::from("cargo"));
+    let status = Command::new(&cargo)
+        .args(["build", "-p", "biodb", "--bin", "biodb"])
+        .status();
+    match status {
+        Ok(r) if r.success() && program.exists() => program,
+        _ => panic!("Unable to build biodb at {}", program.display()),
+    }
+}
+
+/**
+ * Minimal test runner for integration tests built with harness = false, which
+ * is required so the test executable can act as the mock programs.  Exits
+ * non-zero if any test fails.
+ */
+pub fn run_tests(tests: &[(&str, fn())]) {
+    println!("\nrunning {} tests", tests.len());
+    let mut failed: Vec<&str> = Vec::new();
+    for (name, test) in tests {
+        match std::panic::catch_unwind(test) {
+            Ok(_) => println!("test {} ... ok", name),
+            Err(_e) => {
+                println!("test {} ... FAILED", name);
+                failed.push(name);
+            }
+        }
+    }
+
+    if failed.len() > 0 {
+        println!("\nfailures: {}\n", failed.join(", "));
+        std::process::exit(101);
+    }
+    println!("\ntest result: ok. {} passed\n", tests.len());
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/**
+ * Stand-ins for exonerate, fastatranslate and blastp.  The test executable
+ * symlinks itself under each program name, and the programs under test are
+ * pointed at those links.  Once invoked through a link, run_if_invoked() takes
+ * over and behaves as the named program, so the main() of each test must call
+ * it before anything else.
+ *
+ * Output is deterministic, exonerate returns the full target in frame 1 up to
+ * the first stop codon, and blastp scores every database sequence by its best
+ * ungapped alignment against the query.
+ */
+pub const EXONERATE: &str = "exonerate";
+pub const TRANSLATE: &str = "fastatranslate";
+pub const BLAST: &str = "blastp";
+
+/// Paths to the installed stand-in programs.
+pub struct MockTools {
+    pub exonerate: PathBuf,
+    pub translate: PathBuf,
+    pub blast: PathBuf,
+}
+
+pub fn install(dir: &Path) -> MockTools {
+    let exe = std::env::current_exe().expect("Unable to determine path of test executable");
+    fs::create_dir_all(&dir).expect("Unable to create mock tools directory");
+
+    // Link each program to the test executable
+    let link = |name: &str| -> PathBuf {
+        let path = dir.join(name);
+        let _ = fs::remove_file(&path);
+        if let Err(e) = symlink(&exe, &path) {
+            panic!(
+                "Unable to create mock program {}, error: {}",
+                path.display(),
+                e
+            );
+        }
+        path
+    };
+
+    MockTools {
+        exonerate: link(EXONERATE),
+        translate: link(TRANSLATE),
+        blast: link(BLAST),
+    }
+}
+
+/// Run as one of the stand-in programs and exit, if invoked as one.
+pub fn run_if_invoked() {
+    let args: Vec<String> = std::env::args().collect();
+    let name = Path::new(&args[0])
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let res = match name.as_str() {
+        EXONERATE => exonerate(&args[1..]),
+        TRANSLATE => fastatranslate(&args[1..]),
+        BLAST => blastp(&args[1..]),
+        _ => return,
+    };
+
+    if let Err(e) = res {
+        eprintln!("{}: {}", name, e);
+        process::exit(1);
+    }
+    process::exit(0);
+}
+
+fn exonerate(args: &[String]) -> Result<(), String> {
+    // Read files
+    let query = read_fasta(&get_arg(&args, "--query")?)?;
+    let target = read_fasta(&get_arg(&args, "--target")?)?;
+    let (_, query_seq) = query.first().ok_or("Empty query file")?;
+    let (_, target_seq) = target.first().ok_or("Empty target file")?;
+
+    // Get coding region
+    let aa = translate(&target_seq);
+    let aa = aa.split('*').next().unwrap_or("").to_string();
+    let cdna = &target_seq[..aa.len() * 3];
+
+    // Give --ryo ">cdna %tcb %tce\n%tcs>aa %qab %qae\n%qas"
+    print!(
+        ">cdna 0 {}\n{}\n>aa 0 {}\n{}\n-- completed exonerate analysis\n",
+        cdna.len(),
+        cdna,
+        query_seq.len().min(aa.len()),
+        aa
+    );
+    Ok(())
+}
+
+fn fastatranslate(args: &[String]) -> Result<(), String> {
+    let filename = args.last().ok_or("No input file given")?;
+    for (header, seq) in read_fasta(&filename)? {
+        let id = header.split_whitespace().next().unwrap_or("");
+        println!(">{} [translate(1)]\n{}", id, translate(&seq));
+    }
+    Ok(())
+}
+
+fn blastp(args: &[String]) -> Result<(), String> {
+    // Get arguments
+    let outfmt = get_arg(&args, "-outfmt")?;
+    let evalue_threshold = get_arg(&args, "-evalue")?
+        .parse::<f64>()
+        .map_err(|e| e.to_string())?;
+    let max_targets = get_arg(&args, "-max_target_seqs")?
+        .parse::<usize>()
+        .map_err(|e| e.to_string())?;
+    let db = read_fasta(&get_arg(&args, "-db")?)?;
+    let queries = read_fasta(&get_arg(&args, "-query")?)?;
+    let outfile = get_arg(&args, "-out")?;
+
+    // Get columns
+    let columns: Vec<&str> = outfmt
+        .split_whitespace()
+        .filter(|c| *c != "6" && *c != "7")
+        .collect();
+    let descriptions = columns
+        .iter()
+        .map(|c| column_description(&c))
+        .collect::<Vec<String>>();
+
+    let mut out = String::new();
+    for (query_header, query_seq) in &queries {
+        let query_id = query_header.split_whitespace().next().unwrap_or("");
+
+        // Score database sequences
+        let mut hits: Vec<HashMap<&str, String>> = db
+            .iter()
+            .filter_map(|(h, s)| align(&query_id, &query_seq, &h, &s))
+            .filter(|h| h["evalue"].parse::<f64>().unwrap() <= evalue_threshold)
+            .collect();
+        hits.sort_by(|a, b| {
+            let score = |h: &HashMap<&str, String>| h["bitscore"].parse::<f32>().unwrap();
+            score(b)
+                .partial_cmp(&score(a))
+                .unwrap()
+                .then(a["sseqid"].cmp(&b["sseqid"]))
+        });
+        hits.truncate(max_targets);
+
+        // Format
+        out.push_str(&format!(
+            "# BLASTP 2.12.0+\n# Query: {}\n# Database: {}\n# Fields: {}\n# {} hits found\n",
+            query_header,
+            get_arg(&args, "-db")?,
+            descriptions.join(", "),
+            hits.len()
+        ));
+        for hit in hits {
+            let line = columns
+                .iter()
+                .map(|c| hit.get(c).map(|v| v.to_string()).unwrap_or("0".to_string()))
+                .collect::<Vec<String>>();
+            out.push_str(&format!("{}\n", line.join("\t")));
+        }
+    }
+    out.push_str("# BLAST processed 1 queries\n");
+
+    // Save file
+    let mut fh = fs::File::create(&outfile).map_err(|e| format!("{}, {}", outfile, e))?;
+    fh.write_all(&out.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/**
+ * Best ungapped alignment of the query against the subject.  Bitscore is two
+ * bits per identical residue, and the evalue follows from it.
+ */
+fn align(
+    query_id: &str,
+    query: &str,
+    subject_id: &str,
+    subject: &str,
+) -> Option<HashMap<&'static str, String>> {
+    let (q, s) = (query.as_bytes(), subject.as_bytes());
+    let mut best: Option<(usize, usize, usize, usize)> = None;
+
+    // Go through offsets of subject against query
+    for offset in -(s.len() as i64 - 1)..(q.len() as i64) {
+        let q_start = offset.max(0) as usize;
+        let q_end = (s.len() as i64 + offset).min(q.len() as i64) as usize;
+        let matches = (q_start..q_end)
+            .filter(|i| q[*i] == s[(*i as i64 - offset) as usize])
+            .count();
+        if matches > 0 && best.map_or(true, |b| matches > b.0) {
+            best = Some((matches, q_start, q_end, (q_start as i64 - offset) as usize));
+        }
+    }
+
+    let (matches, q_start, q_end, s_start) = best?;
+    let length = q_end - q_start;
+    let bitscore = matches as f32 * 2.0;
+    let subject_id = subject_id.split_whitespace().next().unwrap_or("");
+
+    let mut hit: HashMap<&'static str, String> = HashMap::new();
+    hit.insert("qseqid", query_id.to_string());
+    hit.insert("sseqid", subject_id.to_string());
+    hit.insert(
+        "pident",
+        format!("{:.2}", matches as f32 * 100.0 / length as f32),
+    );
+    hit.insert("length", length.to_string());
+    hit.insert("mismatch", (length - matches).to_string());
+    hit.insert("gapopen", "0".to_string());
+    hit.insert("qstart", (q_start + 1).to_string());
+    hit.insert("qend", q_end.to_string());
+    hit.insert("sstart", (s_start + 1).to_string());
+    hit.insert("send", (s_start + length).to_string());
+    hit.insert("evalue", format!("{:.2e}", blast_evalue(bitscore)));
+    hit.insert("bitscore", format!("{:.1}", bitscore));
+    hit.insert("score", (matches * 5).to_string());
+    hit.insert("qlen", q.len().to_string());
+    hit.insert("slen", s.len().to_string());
+    hit.insert(
+        "qcovs",
+        format!("{:.0}", length as f32 * 100.0 / q.len() as f32),
+    );
+    Some(hit)
+}
+
+/// Evalue given by the mock blastp for the bitscore.
+pub fn blast_evalue(bitscore: f32) -> f64 {
+    let evalue = 10f64.powf(-(bitscore as f64) / 10.0);
+    format!("{:.2e}", evalue).parse::<f64>().unwrap()
+}
+
+fn column_description(column: &str) -> String {
+    match column {
+        "qseqid" => "query acc.ver",
+        "sseqid" => "subject acc.ver",
+        "pident" => "% identity",
+        "length" => "alignment length",
+        "mismatch" => "mismatches",
+        "gapopen" => "gap opens",
+        "qstart" => "q. start",
+        "qend" => "q. end",
+        "sstart" => "s. start",
+        "send" => "s. end",
+        "evalue" => "evalue",
+        "bitscore" => "bit score",
+        "score" => "score",
+        "qlen" => "query length",
+        "slen" => "subject length",
+        "qcovs" => "% query coverage per subject",
+        other => other,
+    }
+    .to_string()
+}
+
+/// Translate a nucleotide sequence in frame 1 with the standard genetic code.
+pub fn translate(sequence: &str) -> String {
+    let bases = sequence.to_uppercase().replace("U", "T");
+    bases
+        .as_bytes()
+        .chunks(3)
+        .filter(|c| c.len() == 3)
+        .map(|c| codon_to_aa(&String::from_utf8_lossy(c)))
+        .collect()
+}
+
+pub fn reverse_complement(sequence: &str) -> String {
+    sequence
+        .chars()
+        .rev()
+        .map(|c| match c {
+            'A' => 'T',
+            'T' => 'A',
+            'G' => 'C',
+            'C' => 'G',
+            other => other,
+        })
+        .collect()
+}
+
+fn codon_to_aa(codon: &str) -> char {
+    match codon {
+        "TTT" | "TTC" => 'F',
+        "TTA" | "TTG" | "CTT" | "CTC" | "CTA" | "CTG" => 'L',
+        "ATT" | "ATC" | "ATA" => 'I',
+        "ATG" => 'M',
+        "GTT" | "GTC" | "GTA" | "GTG" => 'V',
+        "TCT" | "TCC" | "TCA" | "TCG" | "AGT" | "AGC" => 'S',
+        "CCT" | "CCC" | "CCA" | "CCG" => 'P',
+        "ACT" | "ACC" | "ACA" | "ACG" => 'T',
+        "GCT" | "GCC" | "GCA" | "GCG" => 'A',
+        "TAT" | "TAC" => 'Y',
+        "TAA" | "TAG" | "TGA" => '*',
+        "CAT" | "CAC" => 'H',
+        "CAA" | "CAG" => 'Q',
+        "AAT" | "AAC" => 'N',
+        "AAA" | "AAG" => 'K',
+        "GAT" | "GAC" => 'D',
+        "GAA" | "GAG" => 'E',
+        "TGT" | "TGC" => 'C',
+        "TGG" => 'W',
+        "CGT" | "CGC" | "CGA" | "CGG" | "AGA" | "AGG" => 'R',
+        "GGT" | "GGC" | "GGA" | "GGG" => 'G',
+        _ => 'X',
+    }
+}
+
+fn get_arg(args: &[String], name: &str) -> Result<String, String> {
+    match args.iter().position(|a| a == name) {
+        Some(pos) if pos + 1 < args.len() => Ok(args[pos + 1].to_string()),
+        _ => Err(format!("Missing argument {}", name)),
+    }
+}
+
+fn read_fasta(filename: &str) -> Result<Vec<(String, String)>, String> {
+    let contents =
+        fs::read_to_string(&filename).map_err(|e| format!("Unable to read {}, {}", filename, e))?;
+
+    let mut records: Vec<(String, String)> = Vec::new();
+    for line in contents.lines() {
+        if let Some(header) = line.strip_prefix('>') {
+            records.push((header.trim().to_string(), String::new()));
+        } else if let Some(last) = records.last_mut() {
+            last.1.push_str(line.trim());
+        }
+    }
+
+    Ok(records)
+}